@@ -1,4 +1,45 @@
-async function fetchSettings() {
+// Phiên đăng nhập Admin: gắn CSRF token cho request thay đổi dữ liệu, 401 -> về trang đăng nhập
+    const adminSession = { username: null, role: 'viewer', csrf: null };
+    // Vai trò: viewer < operator (bật/tắt route, module, reload) < admin (tính năng bảo mật, settings)
+    const ROLE_RANK = { viewer: 0, operator: 1, admin: 2 };
+    function can(minRole) { return (ROLE_RANK[adminSession.role] ?? 0) >= ROLE_RANK[minRole]; }
+    // Ẩn các nút tĩnh có data-min-role mà vai trò hiện tại không dùng được
+    function applyRoleVisibility(root) {
+      (root || document).querySelectorAll('[data-min-role]').forEach(el => {
+        if (!can(el.dataset.minRole)) el.style.display = 'none';
+      });
+    }
+    function lockToggle(tg, minRole) {
+      if (!can(minRole)) { tg.input.disabled = true; tg.root.title = 'Cần quyền ' + minRole; }
+      return tg;
+    }
+    const rawFetch = window.fetch.bind(window);
+    window.fetch = async (url, opts = {}) => {
+      const method = (opts.method || 'GET').toUpperCase();
+      if (method !== 'GET' && method !== 'HEAD' && adminSession.csrf) {
+        opts.headers = Object.assign({}, opts.headers || {}, { 'X-CSRF-Token': adminSession.csrf });
+      }
+      const res = await rawFetch(url, opts);
+      if (res.status === 401 && String(url).startsWith('/admin')) { location.href = '/admin/login'; }
+      return res;
+    };
+    async function loadSession() {
+      const res = await fetch('/admin/session');
+      if (!res.ok) { throw new Error('Not logged in'); }
+      const s = await res.json();
+      adminSession.username = s.username;
+      adminSession.role = s.role || 'viewer';
+      adminSession.csrf = s.csrf_token;
+      const el = document.getElementById('current-user');
+      if (el) el.textContent = '👤 ' + s.username + ' (' + adminSession.role + ')';
+      applyRoleVisibility();
+      return s;
+    }
+    async function logout() {
+      await fetch('/admin/logout', { method:'POST' });
+      location.href = '/admin/login';
+    }
+    async function fetchSettings() {
       const res = await fetch('/admin/settings');
       if (!res.ok) { throw new Error('Failed to load /admin/settings: '+res.status); }
       return res.json();
@@ -116,7 +157,7 @@ async function fetchSettings() {
 
       btnContainer.appendChild(enableAllBtn);
       btnContainer.appendChild(disableAllBtn);
-      container.appendChild(btnContainer);
+      if (can('operator')) container.appendChild(btnContainer);
 
       const table = document.createElement('table');
       table.className = 'settings-table';
@@ -135,6 +176,7 @@ async function fetchSettings() {
           // Auto-save disabled routes
           await fetch('/admin/routes', { method:'POST', headers:{'Content-Type':'application/json'}, body: JSON.stringify({ disabled_routes: disabledRoutes }) });
         });
+        lockToggle(tg, 'operator');
         td2.appendChild(tg.root);
         tr.appendChild(td1);
         tr.appendChild(td2);
@@ -216,6 +258,7 @@ async function fetchSettings() {
         return;
       }
       if (cmd.action==='enable-feature' || cmd.action==='disable-feature') {
+        if (!can('admin')) { alert('Cần quyền admin để bật/tắt tính năng'); return; }
         const feat = cmd.target; const on = cmd.action==='enable-feature';
         // Update both *_enabled flag and disabled_features for consistency
         const s = await fetchSettings();
@@ -287,6 +330,37 @@ async function fetchSettings() {
           input.type = 'number'; input.value = (ex[f.key] ?? f.default ?? 0);
           input.onchange = () => { featureExtras[m.name] = featureExtras[m.name]||{}; featureExtras[m.name][f.key] = Number(input.value); scheduleSaveFeatureExtras(); };
           li.appendChild(input);
+        } else if (f.type === 'string') {
+          const input = document.createElement('input');
+          input.type = f.secret === true ? 'password' : 'text'; input.value = (ex[f.key] ?? f.default ?? '');
+          if (f.secret === true) input.placeholder = 'not set (value or env:NAME)';
+          input.autocomplete = 'off'; input.style.width = '320px';
+          input.onchange = () => { featureExtras[m.name] = featureExtras[m.name]||{}; featureExtras[m.name][f.key] = input.value.trim(); scheduleSaveFeatureExtras(); };
+          li.appendChild(input);
+        } else if (f.type === 'boolean') {
+          const tg = makeToggle(Boolean(ex[f.key] ?? f.default ?? false), (on) => {
+            featureExtras[m.name] = featureExtras[m.name]||{}; featureExtras[m.name][f.key] = on; scheduleSaveFeatureExtras();
+          });
+          li.appendChild(tg.root);
+        } else if (f.type === 'json') {
+          // Giá trị JSON tự do (vd: route_rules); chỉ lưu khi parse hợp lệ
+          const ta = document.createElement('textarea');
+          ta.rows = 8; ta.cols = 60; ta.spellcheck = false;
+          if (f.example !== undefined) ta.placeholder = JSON.stringify(f.example, null, 2);
+          const cur = (ex[f.key] !== undefined) ? ex[f.key] : (f.default ?? null);
+          ta.value = JSON.stringify(cur, null, 2);
+          ta.onchange = () => {
+            try {
+              const v = JSON.parse(ta.value || 'null');
+              ta.style.borderColor = '';
+              featureExtras[m.name] = featureExtras[m.name]||{}; featureExtras[m.name][f.key] = v;
+              scheduleSaveFeatureExtras();
+            } catch (e) {
+              ta.style.borderColor = 'var(--danger)';
+              ta.title = 'JSON không hợp lệ: ' + e.message;
+            }
+          };
+          li.appendChild(ta);
         } else if (f.type === 'string_list') {
           const ta = document.createElement('textarea');
           ta.placeholder = 'One per line'; ta.rows = 4; ta.cols = 24;
@@ -398,6 +472,233 @@ async function fetchSettings() {
         ul.appendChild(li);
       }
       container.appendChild(ul);
+      if (m.name === 'api_key') renderApiKeysPanel(container);
+      if (m.name === 'quota') renderQuotaPanel(container);
+      if (m.name === 'waf') renderWafEventsPanel(container);
+      if (m.name === 'ip_filter') renderIpBansPanel(container);
+      // Cấu hình tính năng bảo mật chỉ admin được sửa
+      if (!can('admin')) {
+        container.querySelectorAll('button').forEach(el => { el.style.display = 'none'; });
+        container.querySelectorAll('input, textarea, select').forEach(el => { el.disabled = true; });
+      }
+    }
+    // Danh sách API key (không bao giờ hiển thị key gốc, trừ một lần ngay khi tạo)
+    function renderApiKeysPanel(container) {
+      const box = document.createElement('div');
+      box.style.marginTop = '16px';
+      box.innerHTML = '<h3>API Keys</h3>';
+      const fmt = (t) => t ? new Date(t * 1000).toLocaleString() : '—';
+      const list = (v) => (v || '').split(',').map(x => x.trim()).filter(Boolean);
+      const created = document.createElement('p');
+      const table = document.createElement('table');
+      table.className = 'settings-table';
+      async function reload() {
+        const res = await fetch('/admin/api-keys');
+        if (!res.ok) { table.innerHTML = '<tr><td>Cần quyền admin</td></tr>'; return; }
+        const keys = (await res.json()).keys || [];
+        table.innerHTML = '<tr><th>Name</th><th>Prefix</th><th>Scopes</th><th>Routes</th><th>Expires</th><th>Req/min</th><th>Last used</th><th></th></tr>';
+        for (const k of keys) {
+          const tr = document.createElement('tr');
+          const cells = [k.name || k.id, k.prefix + '…', (k.scopes||[]).join(' ') || '—', (k.routes||[]).join(', ') || 'all',
+            fmt(k.expires_at), k.rate_limit_per_minute || '∞', fmt(k.last_used_at)];
+          for (const c of cells) { const td = document.createElement('td'); td.textContent = String(c); tr.appendChild(td); }
+          const actions = document.createElement('td');
+          const tg = makeToggle(!k.disabled, async (on) => {
+            await fetch('/admin/api-keys/' + k.id, { method: 'PUT', headers: {'Content-Type':'application/json'}, body: JSON.stringify({ disabled: !on }) });
+          });
+          const del = document.createElement('button');
+          del.className = 'btn'; del.textContent = '🗑️';
+          del.onclick = async () => {
+            if (!confirm('Xóa API key ' + (k.name || k.id) + '?')) return;
+            await fetch('/admin/api-keys/' + k.id, { method: 'DELETE' });
+            reload();
+          };
+          actions.appendChild(tg.root); actions.appendChild(del);
+          tr.appendChild(actions);
+          table.appendChild(tr);
+        }
+      }
+      const form = document.createElement('div');
+      form.style.cssText = 'display: flex; gap: 8px; flex-wrap: wrap; margin: 8px 0;';
+      const inputs = {};
+      for (const [key, ph, type] of [['name','Name','text'],['scopes','Scopes (a,b)','text'],['routes','Routes (/greet/*)','text'],['expires_in_days','Expires in days','number'],['rate_limit_per_minute','Req/min','number']]) {
+        const i = document.createElement('input'); i.type = type; i.placeholder = ph; inputs[key] = i; form.appendChild(i);
+      }
+      const add = document.createElement('button');
+      add.className = 'btn'; add.textContent = '➕ Tạo key';
+      add.onclick = async () => {
+        const body = { name: inputs.name.value.trim(), scopes: list(inputs.scopes.value), routes: list(inputs.routes.value) };
+        if (inputs.expires_in_days.value) body.expires_in_days = Number(inputs.expires_in_days.value);
+        if (inputs.rate_limit_per_minute.value) body.rate_limit_per_minute = Number(inputs.rate_limit_per_minute.value);
+        const res = await fetch('/admin/api-keys', { method: 'POST', headers: {'Content-Type':'application/json'}, body: JSON.stringify(body) });
+        const data = await res.json();
+        created.textContent = data.ok ? ('Key mới (chỉ hiển thị một lần): ' + data.api_key) : ('Lỗi: ' + data.error);
+        Object.values(inputs).forEach(i => { i.value = ''; });
+        reload();
+      };
+      form.appendChild(add);
+      box.appendChild(form);
+      box.appendChild(created);
+      box.appendChild(table);
+      container.appendChild(box);
+      reload();
+    }
+    // Mức dùng quota của từng client trong kỳ hiện tại
+    function renderQuotaPanel(container) {
+      const box = document.createElement('div');
+      box.style.marginTop = '16px';
+      box.innerHTML = '<h3>Quota Usage</h3>';
+      const fmt = (t) => t ? new Date(t * 1000).toLocaleString() : '—';
+      const table = document.createElement('table');
+      table.className = 'settings-table';
+      async function reload() {
+        const res = await fetch('/admin/quota');
+        if (!res.ok) { table.innerHTML = '<tr><td>Cần quyền admin</td></tr>'; return; }
+        const clients = (await res.json()).clients || [];
+        table.innerHTML = '<tr><th>Client</th><th>Period</th><th>Used / Limit</th><th>Remaining</th><th>Resets</th><th></th></tr>';
+        if (clients.length === 0) { table.innerHTML += '<tr><td colspan="6">Chưa có client nào dùng quota trong kỳ này</td></tr>'; }
+        for (const c of clients) {
+          const periods = c.periods || [];
+          periods.forEach((p, i) => {
+            const tr = document.createElement('tr');
+            const cells = [i === 0 ? c.client : '', p.period + ' (' + p.label + ')', p.used + ' / ' + p.limit, p.remaining, fmt(p.reset_at)];
+            for (const v of cells) { const td = document.createElement('td'); td.textContent = String(v); tr.appendChild(td); }
+            const actions = document.createElement('td');
+            if (i === 0) {
+              const rst = document.createElement('button');
+              rst.className = 'btn'; rst.textContent = '↺ Reset';
+              rst.onclick = async () => {
+                if (!confirm('Đặt lại quota của ' + c.client + '?')) return;
+                await fetch('/admin/quota/' + encodeURIComponent(c.client), { method: 'DELETE' });
+                reload();
+              };
+              actions.appendChild(rst);
+            }
+            tr.appendChild(actions);
+            table.appendChild(tr);
+          });
+        }
+      }
+      const refresh = document.createElement('button');
+      refresh.className = 'btn'; refresh.textContent = '⟳ Refresh';
+      refresh.onclick = reload;
+      box.appendChild(refresh);
+      box.appendChild(table);
+      container.appendChild(box);
+      reload();
+    }
+    // Nhật ký WAF: request bị chặn / khớp rule gần đây (tra theo correlation id)
+    function renderWafEventsPanel(container) {
+      const box = document.createElement('div');
+      box.style.marginTop = '16px';
+      box.innerHTML = '<h3>WAF Events</h3>';
+      const bar = document.createElement('div');
+      bar.style.cssText = 'display: flex; gap: 8px; flex-wrap: wrap; margin: 8px 0;';
+      const action = document.createElement('select');
+      for (const [v, t] of [['', 'All'], ['blocked', 'Blocked'], ['detected', 'Detected']]) {
+        const o = document.createElement('option'); o.value = v; o.textContent = t; action.appendChild(o);
+      }
+      const rule = document.createElement('input'); rule.type = 'number'; rule.placeholder = 'Rule ID';
+      const stats = document.createElement('p');
+      const table = document.createElement('table');
+      table.className = 'settings-table';
+      async function reload() {
+        const qs = new URLSearchParams({ limit: '100' });
+        if (action.value) qs.set('action', action.value);
+        if (rule.value) qs.set('rule', rule.value);
+        const res = await fetch('/admin/waf/events?' + qs.toString());
+        if (!res.ok) { table.innerHTML = '<tr><td>Cần quyền admin</td></tr>'; return; }
+        const data = await res.json();
+        const st = data.stats || {};
+        stats.textContent = 'Blocked: ' + (st.blocked || 0) + ' · Detected: ' + (st.detected || 0) + ' · Buffered: ' + (st.buffered || 0) + '/' + (st.capacity || 0);
+        table.innerHTML = '<tr><th>Time</th><th>Correlation ID</th><th>Action</th><th>Request</th><th>Client</th><th>Score</th><th>Rules</th></tr>';
+        for (const e of (data.events || [])) {
+          const tr = document.createElement('tr');
+          const req = e.method + ' ' + e.path + (e.query ? '?' + e.query : '');
+          const rules = (e.matches || []).map(x => x.id + (x.detect_only ? '*' : '') + ' ' + x.target + ': ' + x.matched).join('\n');
+          const cells = [new Date(e.timestamp).toLocaleString(), e.correlation_id, e.action + (e.would_block && e.action !== 'blocked' ? ' (would block)' : ''),
+            req, e.client_ip || '—', e.score + '/' + e.threshold, rules];
+          for (const c of cells) { const td = document.createElement('td'); td.textContent = String(c); td.style.whiteSpace = 'pre-wrap'; tr.appendChild(td); }
+          table.appendChild(tr);
+        }
+      }
+      const refresh = document.createElement('button');
+      refresh.className = 'btn'; refresh.textContent = '⟳ Refresh';
+      refresh.onclick = reload;
+      const clear = document.createElement('button');
+      clear.className = 'btn'; clear.textContent = '🗑️ Clear';
+      clear.onclick = async () => {
+        if (!confirm('Xóa nhật ký WAF?')) return;
+        await fetch('/admin/waf/events', { method: 'DELETE' });
+        reload();
+      };
+      action.onchange = reload;
+      rule.onchange = reload;
+      bar.appendChild(action); bar.appendChild(rule); bar.appendChild(refresh); bar.appendChild(clear);
+      box.appendChild(bar);
+      box.appendChild(stats);
+      box.appendChild(table);
+      container.appendChild(box);
+      reload();
+    }
+    // IP đang bị ban (tự động hoặc thủ công), ban / gỡ ban thủ công
+    function renderIpBansPanel(container) {
+      const box = document.createElement('div');
+      box.style.marginTop = '16px';
+      box.innerHTML = '<h3>Banned IPs</h3>';
+      const fmt = (t) => t ? new Date(t * 1000).toLocaleString() : 'never';
+      const bar = document.createElement('div');
+      bar.style.cssText = 'display: flex; gap: 8px; flex-wrap: wrap; margin: 8px 0;';
+      const ip = document.createElement('input'); ip.placeholder = 'IP address';
+      const secs = document.createElement('input'); secs.type = 'number'; secs.placeholder = 'Seconds (0 = forever)';
+      const errors = document.createElement('p');
+      errors.style.color = '#c0392b';
+      const table = document.createElement('table');
+      table.className = 'settings-table';
+      async function reload() {
+        const res = await fetch('/admin/ip-filter');
+        if (!res.ok) { table.innerHTML = '<tr><td>Cần quyền admin</td></tr>'; return; }
+        const data = await res.json();
+        errors.textContent = ((data.config || {}).errors || []).join('\n');
+        const bans = data.bans || [];
+        table.innerHTML = '<tr><th>IP</th><th>Reason</th><th>Banned</th><th>Expires</th><th></th></tr>';
+        if (bans.length === 0) { table.innerHTML += '<tr><td colspan="5">Không có IP nào bị ban</td></tr>'; }
+        for (const b of bans) {
+          const tr = document.createElement('tr');
+          for (const v of [b.ip, b.reason, fmt(b.banned_at), fmt(b.expires_at)]) { const td = document.createElement('td'); td.textContent = String(v); tr.appendChild(td); }
+          const actions = document.createElement('td');
+          const del = document.createElement('button');
+          del.className = 'btn'; del.textContent = '✖ Unban';
+          del.onclick = async () => {
+            if (!confirm('Gỡ ban ' + b.ip + '?')) return;
+            await fetch('/admin/ip-filter/bans/' + encodeURIComponent(b.ip), { method: 'DELETE' });
+            reload();
+          };
+          actions.appendChild(del);
+          tr.appendChild(actions);
+          table.appendChild(tr);
+        }
+      }
+      const add = document.createElement('button');
+      add.className = 'btn'; add.textContent = '🚫 Ban';
+      add.onclick = async () => {
+        if (!ip.value.trim()) return;
+        const body = { ip: ip.value.trim() };
+        if (secs.value !== '') body.duration_secs = Number(secs.value);
+        const res = await fetch('/admin/ip-filter/bans', { method: 'POST', headers: { 'Content-Type': 'application/json' }, body: JSON.stringify(body) });
+        if (!res.ok) { alert((await res.json().catch(() => ({}))).error || 'Ban failed'); return; }
+        ip.value = ''; secs.value = '';
+        reload();
+      };
+      const refresh = document.createElement('button');
+      refresh.className = 'btn'; refresh.textContent = '⟳ Refresh';
+      refresh.onclick = reload;
+      bar.appendChild(ip); bar.appendChild(secs); bar.appendChild(add); bar.appendChild(refresh);
+      box.appendChild(bar);
+      box.appendChild(errors);
+      box.appendChild(table);
+      container.appendChild(box);
+      reload();
     }
     function renderFeatureTabs(manifests, groups) {
       const tabsEl = document.getElementById('feature-tabs');
@@ -509,6 +810,7 @@ async function fetchSettings() {
           } catch(_) {}
         });
 
+        lockToggle(tg, 'admin');
         footer.appendChild(meta);
         footer.appendChild(tg.root);
 
@@ -576,6 +878,7 @@ async function fetchSettings() {
           } catch(_) {}
         });
 
+        lockToggle(tg, 'operator');
         footer.appendChild(meta);
         footer.appendChild(tg.root);
 
@@ -743,8 +1046,17 @@ async function fetchSettings() {
         };
       }
     }
+    // Các khóa bị overlay features.{APP_ENV}.json hoặc biến môi trường APP__* ghi đè: sửa trên console không có hiệu lực
+    function renderOverrides(keys) {
+      const el = document.getElementById('config-overrides');
+      if (!el) return;
+      if (!keys.length) { el.style.display = 'none'; return; }
+      el.style.display = '';
+      el.textContent = '⚠️ Bị ghi đè bởi overlay/env (xem /admin/config/effective): ' + keys.join(', ');
+    }
     async function init() {
       try {
+        await loadSession();
         const s = await fetchSettings();
         console.log('Settings:', s);
         featureManifests = await fetchManifests();
@@ -772,6 +1084,7 @@ async function fetchSettings() {
         disabledRoutes = s.settings.disabled_routes || [];
         featureExtras = s.settings.feature_extras || {};
         renderFeatures(s.settings);
+        renderOverrides(s.overridden || []);
         renderModuleRoutesTabs(routes.groups);
         renderFeatureTabs(featureManifests, routes.groups);
         // Cập nhật thống kê nhanh
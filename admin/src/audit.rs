@@ -0,0 +1,177 @@
+// Audit log dạng JSON-lines (chỉ ghi thêm): mọi request thay đổi dữ liệu của admin và mọi lần rebuild router
+use axum::body::Body;
+use axum::http::{Method, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use module_utils::ClientIp;
+use serde_json::Value;
+use std::io::{BufRead, Write};
+use tracing::warn;
+
+use crate::auth::AdminIdentity;
+use crate::load_base_settings;
+
+pub const AUDIT_FILE: &str = "./admin/logs/audit.jsonl";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub path: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: String,
+    // "admin_request" | "router_reload"
+    pub kind: String,
+    pub actor: Option<String>,
+    pub ip: Option<String>,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub status: Option<u16>,
+    // Nguồn kích hoạt rebuild: "admin" | "watcher" | "startup"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<Change>,
+}
+
+// Khóa ghi để các dòng JSON không bị xen kẽ khi nhiều request ghi cùng lúc
+static WRITE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+pub fn append(entry: &AuditEntry) {
+    let _guard = WRITE_LOCK.lock();
+    let path = std::path::Path::new(AUDIT_FILE);
+    if let Some(parent) = path.parent() { std::fs::create_dir_all(parent).ok(); }
+    let line = match serde_json::to_string(entry) {
+        Ok(l) => l,
+        Err(_) => return,
+    };
+    let res = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| writeln!(f, "{}", line));
+    if let Err(e) = res {
+        warn!("⚠️ Không ghi được audit log: {}", e);
+    }
+}
+
+// Ghi nhận một lần rebuild router/spec (từ admin, watcher hoặc lúc khởi động)
+pub fn record_reload(source: &str, detail: Option<String>) {
+    append(&AuditEntry {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        kind: "router_reload".to_string(),
+        actor: None,
+        ip: None,
+        method: None,
+        path: None,
+        status: None,
+        source: Some(source.to_string()),
+        detail,
+        changes: Vec::new(),
+    });
+}
+
+// So sánh hai cây JSON, trả về danh sách khóa thay đổi (object được duyệt đệ quy)
+pub fn json_diff(before: &Value, after: &Value) -> Vec<Change> {
+    fn walk(prefix: &str, a: &Value, b: &Value, out: &mut Vec<Change>) {
+        match (a, b) {
+            (Value::Object(ma), Value::Object(mb)) => {
+                let mut keys: Vec<&String> = ma.keys().chain(mb.keys()).collect();
+                keys.sort();
+                keys.dedup();
+                for k in keys {
+                    let path = if prefix.is_empty() { k.clone() } else { format!("{}.{}", prefix, k) };
+                    walk(&path, ma.get(k).unwrap_or(&Value::Null), mb.get(k).unwrap_or(&Value::Null), out);
+                }
+            }
+            _ if a != b => out.push(Change { path: prefix.to_string(), before: a.clone(), after: b.clone() }),
+            _ => {}
+        }
+    }
+    let mut out = Vec::new();
+    walk("", before, after, &mut out);
+    out
+}
+
+// Diff trên giá trị thật (secret đổi vẫn được ghi nhận), sau đó che secret trong before/after
+fn redacted_diff(before: &Value, after: &Value) -> Vec<Change> {
+    let mut changes = json_diff(before, after);
+    for c in &mut changes {
+        crate::secrets::redact_at(&c.path, &mut c.before);
+        crate::secrets::redact_at(&c.path, &mut c.after);
+    }
+    changes
+}
+
+fn settings_value() -> Value {
+    serde_json::to_value(load_base_settings()).unwrap_or(Value::Null)
+}
+
+// Middleware: ghi lại request thay đổi dữ liệu kèm diff settings trước/sau
+pub async fn audit_layer(req: Request<Body>, next: Next) -> Response {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.run(req).await;
+    }
+    let method = req.method().to_string();
+    let path = format!("/admin{}", req.uri().path());
+    let actor = req.extensions().get::<AdminIdentity>().map(|i| i.username.clone());
+    let ip = req.extensions().get::<ClientIp>().map(|c| c.ip.to_string());
+
+    let before = settings_value();
+    let resp = next.run(req).await;
+    let after = settings_value();
+
+    append(&AuditEntry {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        kind: "admin_request".to_string(),
+        actor,
+        ip,
+        method: Some(method),
+        path: Some(path),
+        status: Some(resp.status().as_u16()),
+        source: None,
+        detail: None,
+        changes: redacted_diff(&before, &after),
+    });
+    resp
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub kind: Option<String>,
+    pub path: Option<String>,
+    pub source: Option<String>,
+    // RFC3339; so sánh chuỗi vì mọi timestamp đều ghi theo UTC cùng định dạng
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<usize>,
+}
+
+// Đọc audit log theo bộ lọc, mới nhất trước
+pub fn query(q: &AuditQuery) -> Vec<AuditEntry> {
+    let Ok(file) = std::fs::File::open(AUDIT_FILE) else { return Vec::new() };
+    let limit = q.limit.unwrap_or(200).min(5000);
+    let mut out: Vec<AuditEntry> = std::io::BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|l| serde_json::from_str::<AuditEntry>(&l).ok())
+        .filter(|e| q.kind.as_ref().is_none_or(|k| &e.kind == k))
+        .filter(|e| q.actor.as_ref().is_none_or(|a| e.actor.as_ref() == Some(a)))
+        .filter(|e| q.source.as_ref().is_none_or(|s| e.source.as_ref() == Some(s)))
+        .filter(|e| q.path.as_ref().is_none_or(|p| e.path.as_deref().unwrap_or("").contains(p.as_str())))
+        .filter(|e| q.since.as_ref().is_none_or(|s| e.timestamp.as_str() >= s.as_str()))
+        .filter(|e| q.until.as_ref().is_none_or(|u| e.timestamp.as_str() <= u.as_str()))
+        .collect();
+    out.reverse();
+    out.truncate(limit);
+    out
+}
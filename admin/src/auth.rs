@@ -0,0 +1,573 @@
+// Xác thực Admin: tài khoản cục bộ (argon2), session cookie/bearer token, CSRF và IP allowlist
+use rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::Json;
+use ipnet::IpNet;
+use module_utils::{ClientIp, ClientIpSource};
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::load_settings;
+
+pub const USERS_FILE: &str = "./admin/config/users.json";
+// Mật khẩu admin sinh ngẫu nhiên lúc khởi tạo (chmod 0600), không bao giờ ghi vào log
+pub const INITIAL_PASSWORD_FILE: &str = "./admin/config/initial_admin_password";
+pub const SESSION_COOKIE: &str = "admin_session";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+// Vai trò theo thứ tự quyền tăng dần: viewer < operator < admin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // Chỉ xem settings hiện tại (secret đã che) và routes
+    Viewer,
+    // Bật/tắt module, route, reload router; xem lịch sử cấu hình và cấu hình hiệu lực
+    Operator,
+    // Sửa tính năng bảo mật, settings, rollback và quản lý tài khoản
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+// Tài khoản tạo trước khi có phân quyền được giữ quyền admin
+fn default_role() -> Role { Role::Admin }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminUser {
+    pub username: String,
+    pub password_hash: String,
+    #[serde(default = "default_role")]
+    pub role: Role,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsersFile {
+    users: Vec<AdminUser>,
+}
+
+// Danh tính admin đã xác thực, được gắn vào request extensions cho các handler phía sau
+#[derive(Debug, Clone)]
+pub struct AdminIdentity {
+    pub username: String,
+    pub role: Role,
+}
+
+struct Session {
+    username: String,
+    role: Role,
+    csrf: String,
+    expires_at: Instant,
+}
+
+static SESSIONS: Lazy<RwLock<HashMap<String, Session>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+fn random_token() -> String {
+    let mut buf = [0u8; 32];
+    OsRng.fill_bytes(&mut buf);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(_) => false,
+    }
+}
+
+pub fn load_users() -> Vec<AdminUser> {
+    std::fs::read_to_string(USERS_FILE)
+        .ok()
+        .and_then(|t| serde_json::from_str::<UsersFile>(&t).ok())
+        .map(|f| f.users)
+        .unwrap_or_default()
+}
+
+fn save_users(users: &[AdminUser]) -> std::io::Result<()> {
+    let path = std::path::Path::new(USERS_FILE);
+    if let Some(parent) = path.parent() { std::fs::create_dir_all(parent).ok(); }
+    let text = serde_json::to_string_pretty(&UsersFile { users: users.to_vec() }).unwrap_or_else(|_| "{}".to_string());
+    crate::write_atomic(path, &text)
+}
+
+// Tạo mới tài khoản, hoặc đổi mật khẩu/vai trò của tài khoản đã có
+pub fn upsert_user(username: &str, password: Option<&str>, role: Option<Role>) -> Result<(), String> {
+    let username = username.trim();
+    if username.is_empty() { return Err("username is required".into()); }
+    let password_hash = match password {
+        Some(p) if p.len() < 8 => return Err("password must be at least 8 characters".into()),
+        Some(p) => Some(hash_password(p)?),
+        None => None,
+    };
+    let mut users = load_users();
+    match users.iter_mut().find(|u| u.username == username) {
+        Some(u) => {
+            if let Some(h) = password_hash { u.password_hash = h; }
+            if let Some(r) = role { u.role = r; }
+        }
+        None => users.push(AdminUser {
+            username: username.to_string(),
+            password_hash: password_hash.ok_or("password is required for new users")?,
+            role: role.unwrap_or(Role::Viewer),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }),
+    }
+    if !users.iter().any(|u| u.role == Role::Admin) {
+        return Err("at least one admin user is required".into());
+    }
+    save_users(&users).map_err(|e| e.to_string())?;
+    if password.is_some() {
+        // Đổi mật khẩu: đăng xuất mọi session cũ (vd: mật khẩu bị lộ)
+        revoke_sessions(username);
+    } else if let Some(r) = role {
+        // Đồng bộ vai trò cho các session đang mở của user
+        for sess in SESSIONS.write().values_mut().filter(|s| s.username == username) { sess.role = r; }
+    }
+    Ok(())
+}
+
+fn revoke_sessions(username: &str) {
+    SESSIONS.write().retain(|_, s| s.username != username);
+}
+
+pub fn delete_user(username: &str) -> Result<(), String> {
+    let mut users = load_users();
+    let before = users.len();
+    users.retain(|u| u.username != username);
+    if users.len() == before { return Err(format!("user {} not found", username)); }
+    if !users.iter().any(|u| u.role == Role::Admin) { return Err("cannot delete the last admin user".into()); }
+    save_users(&users).map_err(|e| e.to_string())?;
+    // Thu hồi các session còn sống của user vừa xóa
+    revoke_sessions(username);
+    Ok(())
+}
+
+// Ghi mật khẩu sinh ngẫu nhiên vào file chỉ chủ sở hữu đọc được
+fn write_initial_password(password: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    let path = std::path::Path::new(INITIAL_PASSWORD_FILE);
+    if let Some(parent) = path.parent() { std::fs::create_dir_all(parent)?; }
+    let mut opts = std::fs::OpenOptions::new();
+    opts.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    let mut f = opts.open(path)?;
+    // File đã tồn tại từ trước thì mode của open không áp dụng
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        f.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    writeln!(f, "{}", password)
+}
+
+// Khi chưa có tài khoản nào: tạo từ ADMIN_USERNAME/ADMIN_PASSWORD hoặc sinh mật khẩu ngẫu nhiên.
+// Mật khẩu sinh ra được ghi vào INITIAL_PASSWORD_FILE; chỉ in ra stderr khi không ghi được file và đang chạy trên terminal
+pub fn ensure_bootstrap_user() {
+    use std::io::IsTerminal;
+    if !load_users().is_empty() { return; }
+    let username = std::env::var("ADMIN_USERNAME").unwrap_or_else(|_| "admin".to_string());
+    let (password, generated) = match std::env::var("ADMIN_PASSWORD") {
+        Ok(p) if !p.is_empty() => (p, false),
+        _ => (random_token()[..20].to_string(), true),
+    };
+    match upsert_user(&username, Some(&password), Some(Role::Admin)) {
+        Ok(()) if generated => match write_initial_password(&password) {
+            Ok(()) => warn!("🔐 Created admin user '{}'; generated password written to {} (delete it after first login)", username, INITIAL_PASSWORD_FILE),
+            Err(e) if std::io::stderr().is_terminal() => {
+                warn!("⚠️ Could not write {}: {}", INITIAL_PASSWORD_FILE, e);
+                eprintln!("Generated password for admin user '{}': {}", username, password);
+            }
+            Err(e) => warn!("⚠️ Created admin user '{}' but could not write {}: {}; set ADMIN_PASSWORD and remove users.json to recreate it", username, INITIAL_PASSWORD_FILE, e),
+        },
+        Ok(()) => info!("🔐 Created admin user '{}' from ADMIN_USERNAME/ADMIN_PASSWORD", username),
+        Err(e) => warn!("⚠️ Could not create bootstrap admin user: {}", e),
+    }
+}
+
+// ---- Chống dò mật khẩu: backoff theo username và theo IP ----
+
+// Số lần sai được phép trước khi bắt đầu khóa; sau đó thời gian khóa tăng gấp đôi mỗi lần sai
+const LOGIN_FREE_ATTEMPTS: u32 = 5;
+const LOGIN_BASE_LOCK_SECS: u64 = 2;
+const LOGIN_MAX_LOCK_SECS: u64 = 15 * 60;
+// Bộ đếm không còn sai thêm sau khoảng này thì được quên
+const LOGIN_FORGET_SECS: u64 = 60 * 60;
+const LOGIN_MAX_TRACKED: usize = 10_000;
+
+struct LoginFailures {
+    count: u32,
+    last: Instant,
+    locked_until: Option<Instant>,
+}
+
+static LOGIN_FAILURES: Lazy<Mutex<HashMap<String, LoginFailures>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn throttle_keys(username: &str, ip: Option<IpAddr>) -> Vec<String> {
+    let mut keys = vec![format!("user:{}", username)];
+    if let Some(ip) = ip { keys.push(format!("ip:{}", ip)); }
+    keys
+}
+
+// Số giây còn bị khóa (lớn nhất giữa user và IP)
+fn locked_for(keys: &[String], now: Instant) -> Option<u64> {
+    let map = LOGIN_FAILURES.lock();
+    keys.iter()
+        .filter_map(|k| map.get(k)?.locked_until)
+        .filter(|until| *until > now)
+        .map(|until| (until - now).as_secs().max(1))
+        .max()
+}
+
+fn record_failure(keys: &[String], now: Instant) {
+    let mut map = LOGIN_FAILURES.lock();
+    if map.len() >= LOGIN_MAX_TRACKED {
+        let forget = Duration::from_secs(LOGIN_FORGET_SECS);
+        map.retain(|_, f| now.duration_since(f.last) < forget || f.locked_until.is_some_and(|u| u > now));
+    }
+    for k in keys {
+        // Vẫn đầy (đang bị dò trên diện rộng): không theo dõi thêm key mới, key đã có vẫn bị khóa tiếp
+        if map.len() >= LOGIN_MAX_TRACKED && !map.contains_key(k) { continue; }
+        let f = map.entry(k.clone()).or_insert(LoginFailures { count: 0, last: now, locked_until: None });
+        if now.duration_since(f.last) >= Duration::from_secs(LOGIN_FORGET_SECS) { f.count = 0; }
+        f.count += 1;
+        f.last = now;
+        if f.count > LOGIN_FREE_ATTEMPTS {
+            let exp = (f.count - LOGIN_FREE_ATTEMPTS - 1).min(20);
+            let secs = LOGIN_BASE_LOCK_SECS.saturating_mul(1 << exp).min(LOGIN_MAX_LOCK_SECS);
+            f.locked_until = Some(now + Duration::from_secs(secs));
+        }
+    }
+}
+
+fn clear_failures(keys: &[String]) {
+    let mut map = LOGIN_FAILURES.lock();
+    for k in keys { map.remove(k); }
+}
+
+pub struct LoginResult {
+    pub token: String,
+    pub csrf: String,
+    pub ttl: Duration,
+    pub role: Role,
+}
+
+pub enum LoginError {
+    InvalidCredentials,
+    // Quá nhiều lần sai: số giây phải chờ
+    Throttled(u64),
+}
+
+pub fn login(username: &str, password: &str, ip: Option<IpAddr>) -> Result<LoginResult, LoginError> {
+    let keys = throttle_keys(username, ip);
+    let now = Instant::now();
+    // Đang bị khóa: từ chối trước khi verify (không tốn argon2, không lộ mật khẩu đúng hay sai)
+    if let Some(secs) = locked_for(&keys, now) {
+        return Err(LoginError::Throttled(secs));
+    }
+    let user = load_users().into_iter().find(|u| u.username == username);
+    // Vẫn chạy verify khi user không tồn tại để thời gian phản hồi không lộ thông tin
+    let ok = match &user {
+        Some(u) => verify_password(password, &u.password_hash),
+        None => { let _ = hash_password(password); false }
+    };
+    let Some(user) = user.filter(|_| ok) else {
+        record_failure(&keys, now);
+        warn!("🔐 Failed admin login for '{}' from {:?}", username, ip);
+        return Err(LoginError::InvalidCredentials);
+    };
+    clear_failures(&keys);
+    let ttl = Duration::from_secs(load_settings().admin_session_ttl_secs.max(60));
+    let token = random_token();
+    let csrf = random_token();
+    let mut sessions = SESSIONS.write();
+    sessions.retain(|_, s| s.expires_at > now);
+    sessions.insert(token.clone(), Session {
+        username: user.username.clone(),
+        role: user.role,
+        csrf: csrf.clone(),
+        expires_at: now + ttl,
+    });
+    Ok(LoginResult { token, csrf, ttl, role: user.role })
+}
+
+pub fn logout(token: &str) {
+    SESSIONS.write().remove(token);
+}
+
+// Trả về (danh tính, csrf) nếu token còn hiệu lực
+pub fn session_info(token: &str) -> Option<(AdminIdentity, String)> {
+    let sessions = SESSIONS.read();
+    let s = sessions.get(token)?;
+    if s.expires_at <= Instant::now() { return None; }
+    Some((AdminIdentity { username: s.username.clone(), role: s.role }, s.csrf.clone()))
+}
+
+fn cookie_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|kv| kv.trim().split_once('='))
+        .find(|(k, _)| *k == SESSION_COOKIE)
+        .map(|(_, v)| v.to_string())
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let v = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = v.trim().split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("bearer") { Some(token.trim().to_string()) } else { None }
+}
+
+// Token của request hiện tại: bearer header được ưu tiên, sau đó tới cookie
+pub fn request_token(headers: &HeaderMap) -> Option<(String, bool)> {
+    bearer_token(headers).map(|t| (t, false)).or_else(|| cookie_token(headers).map(|t| (t, true)))
+}
+
+// Request đến qua HTTPS: URI có scheme https, hoặc proxy tin cậy (đã được dùng để giải quyết ClientIp)
+// báo qua X-Forwarded-Proto / Forwarded proto=; client kết nối trực tiếp không tự khai báo được
+pub fn is_https(uri_scheme: Option<&str>, headers: &HeaderMap, client: Option<&ClientIp>) -> bool {
+    if uri_scheme.is_some_and(|s| s.eq_ignore_ascii_case("https")) { return true; }
+    if !client.is_some_and(|c| c.source != ClientIpSource::Connection) { return false; }
+    let first = |name: &str| -> Option<String> {
+        let v = headers.get(name)?.to_str().ok()?;
+        Some(v.split(',').next().unwrap_or("").trim().to_ascii_lowercase())
+    };
+    if let Some(proto) = first("x-forwarded-proto") { return proto == "https"; }
+    // Forwarded: phần tử đầu tiên là hop phía client
+    first("forwarded").is_some_and(|elem| {
+        elem.split(';')
+            .filter_map(|kv| kv.split_once('='))
+            .any(|(k, v)| k.trim() == "proto" && v.trim().trim_matches('"') == "https")
+    })
+}
+
+// Cookie qua HTTPS có thêm Secure để trình duyệt không gửi lại qua HTTP
+pub fn session_cookie(token: &str, ttl: Duration, secure: bool) -> String {
+    let secure = if secure { "; Secure" } else { "" };
+    format!("{}={}; Path=/admin; HttpOnly; SameSite=Strict; Max-Age={}{}", SESSION_COOKIE, token, ttl.as_secs(), secure)
+}
+
+pub fn clear_cookie(secure: bool) -> String {
+    let secure = if secure { "; Secure" } else { "" };
+    format!("{}=; Path=/admin; HttpOnly; SameSite=Strict; Max-Age=0{}", SESSION_COOKIE, secure)
+}
+
+fn ip_allowed(ip: Option<IpAddr>, allowlist: &[String]) -> bool {
+    if allowlist.is_empty() { return true; }
+    let Some(ip) = ip else { return false };
+    allowlist.iter().any(|entry| {
+        let e = entry.trim();
+        if e == "*" { return true; }
+        if let Ok(net) = e.parse::<IpNet>() { return net.contains(&ip); }
+        e.parse::<IpAddr>().map(|a| a == ip).unwrap_or(false)
+    })
+}
+
+fn is_public(path: &str) -> bool {
+    path == "/login" || path.starts_with("/assets/")
+}
+
+fn unauthorized(path: &str) -> Response {
+    // Trang giao diện chuyển về màn hình đăng nhập; API trả 401 JSON
+    if path == "/" || path.is_empty() {
+        return Redirect::to("/admin/login").into_response();
+    }
+    (StatusCode::UNAUTHORIZED, Json(json!({"ok": false, "error": "authentication required"}))).into_response()
+}
+
+// Guard cho toàn bộ router /admin (path ở đây đã bỏ tiền tố /admin do nest)
+pub async fn admin_auth_guard(mut req: Request<Body>, next: Next) -> Response {
+    let settings = load_settings();
+    let ip = req.extensions().get::<ClientIp>().map(|c| c.ip);
+    if !ip_allowed(ip, &settings.admin_ip_allowlist) {
+        warn!("⛔ Admin access denied for {:?} (not in allowlist)", ip);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let path = req.uri().path().to_string();
+    if is_public(&path) {
+        return next.run(req).await;
+    }
+
+    let Some((token, from_cookie)) = request_token(req.headers()) else {
+        return unauthorized(&path);
+    };
+    let Some((identity, csrf)) = session_info(&token) else {
+        return unauthorized(&path);
+    };
+
+    // CSRF: chỉ áp dụng cho request thay đổi dữ liệu dùng cookie (bearer token không bị gửi tự động)
+    let mutating = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if mutating && from_cookie {
+        let sent = req.headers().get(CSRF_HEADER).and_then(|v| v.to_str().ok()).unwrap_or("");
+        if sent.is_empty() || !module_utils::constant_time_eq(sent.as_bytes(), csrf.as_bytes()) {
+            return (StatusCode::FORBIDDEN, Json(json!({"ok": false, "error": "invalid CSRF token"}))).into_response();
+        }
+    }
+
+    req.extensions_mut().insert(identity);
+    next.run(req).await
+}
+
+// Yêu cầu vai trò tối thiểu cho một endpoint; dùng với from_fn_with_state(Role::X, require_role)
+pub async fn require_role(State(min): State<Role>, req: Request<Body>, next: Next) -> Response {
+    let role = req.extensions().get::<AdminIdentity>().map(|i| i.role);
+    match role {
+        Some(r) if r >= min => next.run(req).await,
+        Some(_) => forbidden_role(min),
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+pub fn forbidden_role(min: Role) -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({"ok": false, "error": format!("requires role {}", min.as_str())})),
+    ).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::middleware::{from_fn, from_fn_with_state};
+    use axum::routing::post;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    // Session tạo trực tiếp trong bộ nhớ (không cần users.json)
+    fn session(username: &str, role: Role, ttl: Duration) -> (String, String) {
+        let (token, csrf) = (random_token(), random_token());
+        let s = Session { username: username.into(), role, csrf: csrf.clone(), expires_at: Instant::now() + ttl };
+        SESSIONS.write().insert(token.clone(), s);
+        (token, csrf)
+    }
+
+    fn app() -> Router {
+        let ok = || async { "ok" };
+        Router::new()
+            .route("/routes", post(ok).route_layer(from_fn_with_state(Role::Operator, require_role)))
+            .route("/users", post(ok).route_layer(from_fn_with_state(Role::Admin, require_role)))
+            .layer(from_fn(admin_auth_guard))
+    }
+
+    async fn send(path: &str, headers: &[(&str, &str)]) -> StatusCode {
+        let mut req = Request::post(path);
+        for (k, v) in headers { req = req.header(*k, *v); }
+        let mut req = req.body(Body::empty()).unwrap();
+        let local = "127.0.0.1".parse().unwrap();
+        req.extensions_mut().insert(ClientIp { ip: local, peer: local, source: ClientIpSource::Connection });
+        app().oneshot(req).await.unwrap().status()
+    }
+
+    #[test]
+    fn wrong_password_does_not_verify() {
+        let hash = hash_password("correct horse battery").unwrap();
+        assert!(verify_password("correct horse battery", &hash));
+        assert!(!verify_password("correct horse battery!", &hash));
+        assert!(!verify_password("", &hash));
+        assert!(!verify_password("correct horse battery", "not-a-phc-hash"));
+    }
+
+    #[tokio::test]
+    async fn cookie_requests_need_matching_csrf_token() {
+        let (token, csrf) = session("csrf-user", Role::Admin, Duration::from_secs(60));
+        let cookie = format!("{}={}", SESSION_COOKIE, token);
+        assert_eq!(send("/users", &[("cookie", &cookie)]).await, StatusCode::FORBIDDEN);
+        assert_eq!(send("/users", &[("cookie", &cookie), (CSRF_HEADER, "")]).await, StatusCode::FORBIDDEN);
+        assert_eq!(send("/users", &[("cookie", &cookie), (CSRF_HEADER, &random_token())]).await, StatusCode::FORBIDDEN);
+        assert_eq!(send("/users", &[("cookie", &cookie), (CSRF_HEADER, &csrf)]).await, StatusCode::OK);
+        // Bearer token không bị trình duyệt tự gửi nên không cần CSRF
+        let bearer = format!("Bearer {}", token);
+        assert_eq!(send("/users", &[("authorization", &bearer)]).await, StatusCode::OK);
+        assert_eq!(send("/users", &[]).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn roles_are_enforced_per_route() {
+        let bearer = |role: Role| format!("Bearer {}", session("role-user", role, Duration::from_secs(60)).0);
+        let operator = bearer(Role::Operator);
+        assert_eq!(send("/routes", &[("authorization", &operator)]).await, StatusCode::OK);
+        assert_eq!(send("/users", &[("authorization", &operator)]).await, StatusCode::FORBIDDEN);
+        let viewer = bearer(Role::Viewer);
+        assert_eq!(send("/routes", &[("authorization", &viewer)]).await, StatusCode::FORBIDDEN);
+        let admin = bearer(Role::Admin);
+        assert_eq!(send("/users", &[("authorization", &admin)]).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn expired_sessions_are_rejected() {
+        let (token, _) = session("expired-user", Role::Admin, Duration::ZERO);
+        assert!(session_info(&token).is_none());
+        let bearer = format!("Bearer {}", token);
+        assert_eq!(send("/users", &[("authorization", &bearer)]).await, StatusCode::UNAUTHORIZED);
+        let (live, _) = session("expired-user", Role::Admin, Duration::from_secs(60));
+        assert!(session_info(&live).is_some());
+    }
+
+    #[test]
+    fn revoking_sessions_only_affects_that_user() {
+        let (a, _) = session("revoke-a", Role::Admin, Duration::from_secs(60));
+        let (b, _) = session("revoke-b", Role::Admin, Duration::from_secs(60));
+        revoke_sessions("revoke-a");
+        assert!(session_info(&a).is_none());
+        assert!(session_info(&b).is_some());
+    }
+
+    #[test]
+    fn secure_cookie_only_over_https() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        let ip = "10.0.0.1".parse().unwrap();
+        let direct = ClientIp { ip, peer: ip, source: ClientIpSource::Connection };
+        let proxied = ClientIp { source: ClientIpSource::XForwardedFor, ..direct };
+        // Header do client kết nối trực tiếp tự gửi không được tin
+        assert!(!is_https(None, &headers, Some(&direct)));
+        assert!(!is_https(None, &headers, None));
+        assert!(is_https(None, &headers, Some(&proxied)));
+        assert!(is_https(Some("https"), &HeaderMap::new(), None));
+        headers.insert("x-forwarded-proto", "http".parse().unwrap());
+        assert!(!is_https(None, &headers, Some(&proxied)));
+
+        let mut fwd = HeaderMap::new();
+        fwd.insert("forwarded", "for=1.2.3.4;proto=https, for=10.0.0.2;proto=http".parse().unwrap());
+        assert!(is_https(None, &fwd, Some(&proxied)));
+
+        assert!(session_cookie("t", Duration::from_secs(60), true).ends_with("; Secure"));
+        assert!(!session_cookie("t", Duration::from_secs(60), false).contains("Secure"));
+        assert!(clear_cookie(true).contains("Max-Age=0; Secure"));
+    }
+}
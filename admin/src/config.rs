@@ -0,0 +1,201 @@
+// Cấu hình nhiều lớp: mặc định -> features.json -> features.{APP_ENV}.json -> biến môi trường APP__*
+// Ví dụ: APP__FEATURE_EXTRAS__RATE_LIMIT__RPS=20 ghi đè feature_extras.rate_limit.rps
+// Giá trị env được parse như JSON (true, 20, ["/a"]...), nếu không hợp lệ thì coi là chuỗi.
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::warn;
+
+use crate::FeaturesSettings;
+
+pub const CONFIG_DIR: &str = "./admin/config";
+pub const BASE_FILE: &str = "./admin/config/features.json";
+pub const ENV_PREFIX: &str = "APP__";
+
+pub fn app_env() -> String {
+    std::env::var("APP_ENV").unwrap_or_else(|_| "dev".to_string())
+}
+
+pub fn overlay_path(env: &str) -> PathBuf {
+    PathBuf::from(CONFIG_DIR).join(format!("features.{}.json", env))
+}
+
+// Các biến môi trường khởi động (trước đây đọc rải rác trong main.rs)
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeConfig {
+    pub app_env: String,
+    pub app_port: String,
+    pub hot_reload: bool,
+    pub app_autoload: bool,
+}
+
+impl RuntimeConfig {
+    pub fn from_env() -> Self {
+        Self {
+            app_env: app_env(),
+            app_port: std::env::var("APP_PORT").unwrap_or_else(|_| "3000".to_string()),
+            hot_reload: std::env::var("HOT_RELOAD").unwrap_or_else(|_| "1".to_string()) == "1",
+            app_autoload: std::env::var("APP_AUTOLOAD").unwrap_or_else(|_| "0".to_string()) == "1",
+        }
+    }
+
+    // Giá trị kèm nguồn ("env:APP_PORT" hoặc "default")
+    pub fn describe(&self) -> Value {
+        let src = |var: &str| if std::env::var(var).is_ok() { format!("env:{}", var) } else { "default".to_string() };
+        json!({
+            "app_env": { "value": self.app_env, "source": src("APP_ENV") },
+            "app_port": { "value": self.app_port, "source": src("APP_PORT") },
+            "hot_reload": { "value": self.hot_reload, "source": src("HOT_RELOAD") },
+            "app_autoload": { "value": self.app_autoload, "source": src("APP_AUTOLOAD") },
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Effective {
+    pub value: Value,
+    // "feature_extras.rate_limit.rps" -> "file:features.prod.json" | "env:APP__..." | "default"
+    pub sources: BTreeMap<String, String>,
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() { key.to_string() } else { format!("{}.{}", prefix, key) }
+}
+
+// Gộp sâu: object được gộp theo key, các kiểu khác (mảng, scalar) bị thay thế nguyên
+fn merge(target: &mut Value, overlay: &Value, prefix: &str, source: &str, sources: &mut BTreeMap<String, String>) {
+    match (target, overlay) {
+        (Value::Object(t), Value::Object(o)) => {
+            // Object rỗng trước đó được tính là một lá; giờ có khóa con thì bỏ mục đó
+            if !o.is_empty() { sources.remove(prefix); }
+            for (k, v) in o {
+                let path = join(prefix, k);
+                let slot = t.entry(k.clone()).or_insert(Value::Null);
+                // Thay cả object -> xóa nguồn cũ của các khóa con không còn tồn tại
+                if !slot.is_object() || !v.is_object() {
+                    sources.retain(|p, _| !p.starts_with(&format!("{}.", path)));
+                }
+                merge(slot, v, &path, source, sources);
+            }
+        }
+        (t, o) => {
+            *t = o.clone();
+            mark(t, prefix, source, sources);
+        }
+    }
+}
+
+fn mark(v: &Value, prefix: &str, source: &str, sources: &mut BTreeMap<String, String>) {
+    match v {
+        Value::Object(m) if !m.is_empty() => {
+            for (k, vv) in m {
+                mark(vv, &join(prefix, k), source, sources);
+            }
+        }
+        _ => { sources.insert(prefix.to_string(), source.to_string()); }
+    }
+}
+
+fn read_json(path: &std::path::Path) -> Option<Value> {
+    let text = std::fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&text) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            warn!("⚠️ Bỏ qua {:?}: JSON không hợp lệ ({})", path, e);
+            None
+        }
+    }
+}
+
+// APP__A__B=v -> (["a","b"], tên biến, v)
+fn env_overrides() -> Vec<(Vec<String>, String, Value)> {
+    let mut out: Vec<(Vec<String>, String, Value)> = std::env::vars()
+        .filter_map(|(k, v)| {
+            let rest = k.strip_prefix(ENV_PREFIX)?;
+            let path: Vec<String> = rest.split("__").map(|s| s.to_ascii_lowercase()).collect();
+            if path.iter().any(|s| s.is_empty()) { return None; }
+            let value = serde_json::from_str(&v).unwrap_or(Value::String(v));
+            Some((path, k, value))
+        })
+        .collect();
+    // Thứ tự ổn định: biến ngắn (cha) áp dụng trước biến dài (con)
+    out.sort_by(|a, b| a.0.len().cmp(&b.0.len()).then(a.1.cmp(&b.1)));
+    out
+}
+
+fn nest(path: &[String], value: Value) -> Value {
+    path.iter().rev().fold(value, |acc, k| {
+        let mut m = Map::new();
+        m.insert(k.clone(), acc);
+        Value::Object(m)
+    })
+}
+
+// Chỉ file gốc features.json: đây là lớp duy nhất admin được phép ghi
+pub fn load_base() -> FeaturesSettings {
+    read_json(std::path::Path::new(BASE_FILE))
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default()
+}
+
+pub fn resolve() -> Effective {
+    let mut sources = BTreeMap::new();
+    let mut value = serde_json::to_value(FeaturesSettings::default()).unwrap_or(Value::Null);
+    mark(&value, "", "default", &mut sources);
+
+    if let Some(base) = read_json(std::path::Path::new(BASE_FILE)) {
+        merge(&mut value, &base, "", "file:features.json", &mut sources);
+    }
+    let env = app_env();
+    let overlay = overlay_path(&env);
+    if let Some(ov) = read_json(&overlay) {
+        merge(&mut value, &ov, "", &format!("file:features.{}.json", env), &mut sources);
+    }
+    for (path, var, v) in env_overrides() {
+        merge(&mut value, &nest(&path, v), "", &format!("env:{}", var), &mut sources);
+    }
+    Effective { value, sources }
+}
+
+// Settings hiệu lực; nếu lớp ghi đè làm sai kiểu dữ liệu thì quay về file gốc
+pub fn load_effective() -> FeaturesSettings {
+    let eff = resolve();
+    match serde_json::from_value(eff.value) {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("⚠️ Cấu hình ghi đè không hợp lệ, dùng features.json: {}", e);
+            load_base()
+        }
+    }
+}
+
+// Các khóa đang bị ghi đè bởi overlay hoặc env (admin lưu vào file gốc sẽ không có hiệu lực với chúng)
+pub fn overridden_keys(eff: &Effective) -> Vec<String> {
+    eff.sources
+        .iter()
+        .filter(|(_, src)| src.as_str() != "default" && src.as_str() != "file:features.json")
+        .map(|(k, _)| k.clone())
+        .collect()
+}
+
+pub fn describe() -> Value {
+    let mut eff = resolve();
+    crate::secrets::redact_at("", &mut eff.value);
+    let env = app_env();
+    let overlay = overlay_path(&env);
+    let env_vars: Vec<String> = env_overrides().into_iter().map(|(_, var, _)| var).collect();
+    json!({
+        "env": env,
+        "layers": [
+            { "name": "default" },
+            { "name": "base", "path": BASE_FILE, "present": std::path::Path::new(BASE_FILE).exists() },
+            { "name": "overlay", "path": overlay.to_string_lossy(), "present": overlay.exists() },
+            { "name": "env", "vars": env_vars },
+        ],
+        "overridden": overridden_keys(&eff),
+        "settings": eff.value,
+        "sources": eff.sources,
+        "runtime": RuntimeConfig::from_env().describe(),
+    })
+}
@@ -0,0 +1,119 @@
+// Lịch sử cấu hình: mỗi lần lưu features.json sẽ ghi thêm một phiên bản vào history/
+// kèm tác giả, thời điểm và blake3 hash để có thể xem diff và rollback.
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use similar::TextDiff;
+
+use crate::FeaturesSettings;
+
+pub const HISTORY_DIR: &str = "./admin/config/history";
+
+// Hai lần lưu đồng thời không được lấy cùng số phiên bản
+static RECORD_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsVersion {
+    pub version: u64,
+    pub author: String,
+    pub timestamp: String,
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VersionFile {
+    #[serde(flatten)]
+    meta: SettingsVersion,
+    settings: FeaturesSettings,
+}
+
+fn version_path(version: u64) -> PathBuf {
+    Path::new(HISTORY_DIR).join(format!("v{:06}.json", version))
+}
+
+// Chuỗi JSON chuẩn hóa (key đã sắp xếp) để hash và diff ổn định giữa các lần lưu
+pub fn canonical_text(s: &FeaturesSettings) -> String {
+    serde_json::to_value(s)
+        .and_then(|v| serde_json::to_string_pretty(&v))
+        .unwrap_or_else(|_| "{}".to_string())
+}
+
+pub fn hash_text(text: &str) -> String {
+    blake3::hash(text.as_bytes()).to_hex().to_string()
+}
+
+// Danh sách phiên bản, mới nhất trước
+pub fn list() -> Vec<SettingsVersion> {
+    let mut out: Vec<SettingsVersion> = Vec::new();
+    if let Ok(rd) = std::fs::read_dir(HISTORY_DIR) {
+        for e in rd.flatten() {
+            let p = e.path();
+            if p.extension().map(|x| x != "json").unwrap_or(true) { continue; }
+            if let Ok(text) = std::fs::read_to_string(&p) {
+                if let Ok(f) = serde_json::from_str::<VersionFile>(&text) {
+                    out.push(f.meta);
+                }
+            }
+        }
+    }
+    out.sort_by_key(|v| std::cmp::Reverse(v.version));
+    out
+}
+
+pub fn latest() -> Option<SettingsVersion> {
+    list().into_iter().next()
+}
+
+pub fn load(version: u64) -> Option<(SettingsVersion, FeaturesSettings)> {
+    let text = std::fs::read_to_string(version_path(version)).ok()?;
+    let f: VersionFile = serde_json::from_str(&text).ok()?;
+    // Snapshot ghi trước khi có cơ chế che secret vẫn được che khi đọc ra
+    Some((f.meta, crate::secrets::redact_settings(&f.settings)))
+}
+
+// Ghi một phiên bản mới; bỏ qua nếu nội dung trùng với phiên bản gần nhất
+pub fn record(s: &FeaturesSettings, author: &str, note: Option<String>) -> std::io::Result<SettingsVersion> {
+    let _guard = RECORD_LOCK.lock();
+    let hash = hash_text(&canonical_text(s));
+    let last = latest();
+    if let Some(last) = &last {
+        if last.hash == hash { return Ok(last.clone()); }
+    }
+    std::fs::create_dir_all(HISTORY_DIR)?;
+    let mut meta = SettingsVersion {
+        version: last.map(|v| v.version + 1).unwrap_or(1),
+        author: author.to_string(),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        hash,
+        note,
+    };
+    // create_new: process khác (vd instance thứ hai) đã ghi số này thì lấy số kế tiếp, không ghi đè
+    loop {
+        // Snapshot không chứa secret (hash vẫn tính trên giá trị thật để phát hiện secret thay đổi)
+        let file = VersionFile { meta: meta.clone(), settings: crate::secrets::redact_settings(s) };
+        let text = serde_json::to_string_pretty(&file).unwrap_or_else(|_| "{}".to_string());
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(version_path(meta.version)) {
+            Ok(mut f) => {
+                f.write_all(text.as_bytes())?;
+                f.sync_all()?;
+                return Ok(meta);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => meta.version += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Unified diff giữa hai snapshot (dạng JSON chuẩn hóa)
+pub fn diff(from: &FeaturesSettings, to: &FeaturesSettings, from_label: &str, to_label: &str) -> String {
+    let a = canonical_text(from);
+    let b = canonical_text(to);
+    TextDiff::from_lines(&a, &b)
+        .unified_diff()
+        .context_radius(3)
+        .header(from_label, to_label)
+        .to_string()
+}
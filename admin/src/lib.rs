@@ -1,16 +1,26 @@
 use axum::{Router, Json, response::Html};
-use axum::http::{Request, StatusCode};
+use axum::http::{header, StatusCode};
 use axum::response::Response;
-use axum::middleware::{from_fn, Next};
-use axum::body::Body;
-use axum::extract::ConnectInfo;
-use std::net::{SocketAddr, IpAddr, Ipv4Addr};
+use axum::middleware::{from_fn, from_fn_with_state};
 use axum::extract::Json as AxumJson;
+use axum::Extension;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, Map, json};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use once_cell::sync::Lazy;
 use std::collections::HashMap;
+use axum::extract::{Path as AxumPath, Query};
+use axum::response::IntoResponse;
+
+pub mod audit;
+pub mod auth;
+pub mod config;
+pub mod history;
+pub mod secrets;
+
+use auth::{AdminIdentity, Role};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -26,6 +36,13 @@ pub struct FeaturesSettings {
     pub disabled_features: Vec<String>,
     pub route_rate_limits: HashMap<String, u32>,
     pub feature_extras: Map<String, Value>,
+    // IP/CIDR được phép truy cập /admin; danh sách rỗng = không giới hạn
+    pub admin_ip_allowlist: Vec<String>,
+    pub admin_session_ttl_secs: u64,
+    // IP/CIDR reverse proxy tin cậy: chỉ khi kết nối đến từ đây mới đọc IP client từ header client_ip_header
+    pub trusted_proxies: Vec<String>,
+    // Header mà proxy tin cậy đặt IP client: "x-forwarded-for" | "forwarded" | "x-real-ip"
+    pub client_ip_header: String,
 }
 
 impl Default for FeaturesSettings {
@@ -42,67 +59,158 @@ impl Default for FeaturesSettings {
             disabled_features: Vec::new(),
             route_rate_limits: HashMap::new(),
             feature_extras: Map::new(),
+            admin_ip_allowlist: vec!["127.0.0.1/32".to_string(), "::1/128".to_string()],
+            admin_session_ttl_secs: 8 * 3600,
+            trusted_proxies: Vec::new(),
+            client_ip_header: "x-forwarded-for".to_string(),
         }
     }
 }
 
+// Settings hiệu lực (đã gộp overlay theo APP_ENV và biến môi trường APP__*)
+// Được cache vì mọi middleware đều gọi trên từng request; cache bị xóa khi admin lưu và khi watcher
+// thấy file cấu hình đổi (biến môi trường không đổi trong lúc chạy)
 pub fn load_settings() -> FeaturesSettings {
-    let path = std::path::Path::new("./admin/config/features.json");
-    if let Ok(text) = std::fs::read_to_string(path) {
-        serde_json::from_str(&text).unwrap_or_default()
-    } else {
-        FeaturesSettings::default()
-    }
+    if let Some(s) = SETTINGS_CACHE.read().as_ref() { return s.clone(); }
+    // Chỉ ghi vào cache nếu không có invalidate nào xảy ra trong lúc đọc file (tránh cache giá trị cũ)
+    let generation = SETTINGS_GENERATION.load(Ordering::Acquire);
+    let s = config::load_effective();
+    let mut cache = SETTINGS_CACHE.write();
+    if SETTINGS_GENERATION.load(Ordering::Acquire) == generation { *cache = Some(s.clone()); }
+    s
+}
+
+static SETTINGS_CACHE: Lazy<RwLock<Option<FeaturesSettings>>> = Lazy::new(|| RwLock::new(None));
+static SETTINGS_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// Gọi sau khi features.json / features.{APP_ENV}.json thay đổi
+pub fn invalidate_settings() {
+    let mut cache = SETTINGS_CACHE.write();
+    SETTINGS_GENERATION.fetch_add(1, Ordering::AcqRel);
+    *cache = None;
+}
+
+// Chỉ nội dung features.json: dùng khi admin sửa rồi lưu, để không ghi các giá trị ghi đè xuống file
+pub fn load_base_settings() -> FeaturesSettings {
+    config::load_base()
 }
 
 pub fn save_settings(s: &FeaturesSettings) -> std::io::Result<()> {
-    let path = std::path::Path::new("./admin/config/features.json");
-    if let Some(parent) = path.parent() { std::fs::create_dir_all(parent).ok(); }
-    let text = serde_json::to_string_pretty(s).unwrap_or_else(|_| "{}".to_string());
-    std::fs::write(path, text)
+    save_settings_as(s, "system", None).map(|_| ())
 }
 
-// Middleware chặn truy cập /admin và /admin/settings chỉ cho phép từ 127.0.0.1 và Host=localhost
-async fn admin_access_guard(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
-    let path = req.uri().path();
-    // Chỉ áp dụng guard cho đúng hai endpoint yêu cầu
-    let must_guard = path == "/admin" || path == "/admin/" || path == "/admin/settings";
-    if !must_guard {
-        return Ok(next.run(req).await);
+// Lưu features.json (ghi nguyên tử) và thêm một phiên bản vào lịch sử
+pub fn save_settings_as(s: &FeaturesSettings, author: &str, note: Option<String>) -> std::io::Result<history::SettingsVersion> {
+    let path = std::path::Path::new(config::BASE_FILE);
+    if let Some(parent) = path.parent() { std::fs::create_dir_all(parent).ok(); }
+    // Lần lưu đầu tiên: giữ lại cấu hình hiện có làm phiên bản gốc để có thể rollback về
+    if history::latest().is_none() && path.exists() {
+        history::record(&load_base_settings(), "initial", None)?;
     }
+    // Secret gửi lại dưới dạng đã che (UI, rollback về snapshot đã che) giữ giá trị đang lưu
+    let mut s = s.clone();
+    secrets::restore(&mut s.feature_extras, &load_base_settings().feature_extras);
+    write_atomic(path, &history::canonical_text(&s))?;
+    invalidate_settings();
+    history::record(&s, author, note)
+}
 
-    // Kiểm tra Host header
-    let host = req
-        .headers()
-        .get("host")
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
-    let host_base = host.split(':').next().unwrap_or("");
-    let host_ok = host_base.eq_ignore_ascii_case("localhost");
-
-    // Lấy IP client từ ConnectInfo (cần into_make_service_with_connect_info ở app)
-    let ip_ok = if let Some(ci) = req.extensions().get::<ConnectInfo<SocketAddr>>() {
-        match ci.0.ip() {
-            IpAddr::V4(v4) => v4 == Ipv4Addr::LOCALHOST,
-            IpAddr::V6(v6) => v6.is_loopback(),
-        }
-    } else {
-        false
-    };
-
-    if host_ok && ip_ok {
-        Ok(next.run(req).await)
-    } else {
-        Err(StatusCode::FORBIDDEN)
-    }
+// Ghi ra file tạm rồi rename để reader không bao giờ thấy file ghi dở
+pub(crate) fn write_atomic(path: &std::path::Path, text: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, text)?;
+    std::fs::rename(&tmp, path)
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+    against: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RollbackBody {
+    version: u64,
+}
+
+#[derive(Deserialize)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct UserBody {
+    username: String,
+    password: Option<String>,
+    role: Option<Role>,
+}
+
+// Các khóa settings mà operator được phép sửa (bật/tắt module); còn lại cần admin
+const OPERATOR_SETTINGS_KEYS: &[&str] = &["disabled_modules"];
+
+fn not_found(msg: String) -> Response {
+    (StatusCode::NOT_FOUND, Json(json!({"ok": false, "error": msg}))).into_response()
 }
 
 // Build admin router to be nested under "/admin"
+// - extra: các route admin bổ sung từ app (vd: /features-manifest), được bảo vệ cùng guard
 pub fn build_router(
     live_spec: Arc<RwLock<Value>>,
     reload_fn: Arc<dyn Fn() + Send + Sync + 'static>,
+    extra: Router,
 ) -> Router {
+    auth::ensure_bootstrap_user();
     Router::new()
+        .route("/login", axum::routing::get(|| async move {
+            Html(module_utils::read_asset!("assets/login.html"))
+        }))
+        .route("/login", axum::routing::post(|uri: axum::http::Uri, headers: axum::http::HeaderMap, client: Option<Extension<module_utils::ClientIp>>, AxumJson(body): AxumJson<Credentials>| async move {
+            let secure = auth::is_https(uri.scheme_str(), &headers, client.as_deref());
+            match auth::login(&body.username, &body.password, client.map(|c| c.ip)) {
+                Ok(r) => (
+                    [(header::SET_COOKIE, auth::session_cookie(&r.token, r.ttl, secure))],
+                    Json(json!({"ok": true, "username": body.username, "role": r.role, "token": r.token, "csrf_token": r.csrf})),
+                ).into_response(),
+                Err(auth::LoginError::InvalidCredentials) => (StatusCode::UNAUTHORIZED, Json(json!({"ok": false, "error": "invalid credentials"}))).into_response(),
+                Err(auth::LoginError::Throttled(secs)) => (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, secs.to_string())],
+                    Json(json!({"ok": false, "error": "too many failed login attempts", "retry_after": secs})),
+                ).into_response(),
+            }
+        }))
+        .route("/logout", axum::routing::post(|uri: axum::http::Uri, headers: axum::http::HeaderMap, client: Option<Extension<module_utils::ClientIp>>| async move {
+            if let Some((token, _)) = auth::request_token(&headers) { auth::logout(&token); }
+            let secure = auth::is_https(uri.scheme_str(), &headers, client.as_deref());
+            ([(header::SET_COOKIE, auth::clear_cookie(secure))], Json(json!({"ok": true})))
+        }))
+        .route("/session", axum::routing::get(|headers: axum::http::HeaderMap| async move {
+            let info = auth::request_token(&headers).and_then(|(t, _)| auth::session_info(&t));
+            match info {
+                Some((who, csrf)) => Json(json!({"username": who.username, "role": who.role, "csrf_token": csrf})).into_response(),
+                None => StatusCode::UNAUTHORIZED.into_response(),
+            }
+        }))
+        // Quản lý tài khoản: chỉ admin
+        .route("/users", axum::routing::get(|| async move {
+            let users: Vec<Value> = auth::load_users()
+                .into_iter()
+                .map(|u| json!({"username": u.username, "role": u.role, "created_at": u.created_at}))
+                .collect();
+            Json(json!({ "users": users }))
+        }).route_layer(from_fn_with_state(Role::Admin, auth::require_role)))
+        .route("/users", axum::routing::post(|AxumJson(body): AxumJson<UserBody>| async move {
+            match auth::upsert_user(&body.username, body.password.as_deref(), body.role) {
+                Ok(()) => Json(json!({"ok": true})).into_response(),
+                Err(e) => (StatusCode::BAD_REQUEST, Json(json!({"ok": false, "error": e}))).into_response(),
+            }
+        }).route_layer(from_fn_with_state(Role::Admin, auth::require_role)))
+        .route("/users/:username", axum::routing::delete(|AxumPath(username): AxumPath<String>| async move {
+            match auth::delete_user(&username) {
+                Ok(()) => Json(json!({"ok": true})).into_response(),
+                Err(e) => (StatusCode::BAD_REQUEST, Json(json!({"ok": false, "error": e}))).into_response(),
+            }
+        }).route_layer(from_fn_with_state(Role::Admin, auth::require_role)))
         .route("/", axum::routing::get({
             move || async move {
                 // Read HTML from assets
@@ -130,7 +238,9 @@ pub fn build_router(
         }))
         .route("/settings", axum::routing::get({
             move || async move {
-                let s = load_settings();
+                // Trả về lớp features.json (lớp admin chỉnh sửa); các khóa bị overlay/env ghi đè liệt kê riêng
+                let s = load_base_settings();
+                let overridden = config::overridden_keys(&config::resolve());
                 let mut modules: Vec<String> = Vec::new();
                 if let Ok(rd) = std::fs::read_dir("./modules") {
                     for e in rd.flatten() {
@@ -143,18 +253,41 @@ pub fn build_router(
                     }
                     modules.sort();
                 }
-                Json(json!({ "settings": s, "modules": modules }))
+                // Secret luôn được che; "secrets" cho biết khóa nào đã có giá trị
+                let secret_status = secrets::status(&s.feature_extras);
+                Json(json!({ "settings": secrets::redact_settings(&s), "secrets": secret_status, "modules": modules, "overridden": overridden }))
             }
         }))
         .route("/settings", axum::routing::post({
             let reload_fn = reload_fn.clone();
-            move |AxumJson(body): AxumJson<serde_json::Value>| async move {
-                let mut s = load_settings();
+            move |Extension(who): Extension<AdminIdentity>, AxumJson(body): AxumJson<serde_json::Value>| async move {
+                // Operator chỉ được bật/tắt module; các khóa khác (tính năng bảo mật, extras...) cần admin
+                if who.role < Role::Admin {
+                    let only_operator_keys = body
+                        .as_object()
+                        .map(|o| o.keys().all(|k| OPERATOR_SETTINGS_KEYS.contains(&k.as_str())))
+                        .unwrap_or(false);
+                    if !only_operator_keys { return auth::forbidden_role(Role::Admin); }
+                }
+                let mut s = load_base_settings();
                 if let Some(v) = body.get("rate_limit_enabled").and_then(|v| v.as_bool()) { s.rate_limit_enabled = v; }
                 if let Some(v) = body.get("rate_limit_per_second").and_then(|v| v.as_u64()) { s.rate_limit_per_second = v as u32; }
                 if let Some(v) = body.get("waf_enabled").and_then(|v| v.as_bool()) { s.waf_enabled = v; }
                 if let Some(v) = body.get("oauth2_enabled").and_then(|v| v.as_bool()) { s.oauth2_enabled = v; }
                 if let Some(v) = body.get("admin_console_enabled").and_then(|v| v.as_bool()) { s.admin_console_enabled = v; }
+                if let Some(v) = body.get("admin_ip_allowlist").and_then(|v| v.as_array()) {
+                    s.admin_ip_allowlist = v.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect();
+                }
+                if let Some(v) = body.get("admin_session_ttl_secs").and_then(|v| v.as_u64()) { s.admin_session_ttl_secs = v; }
+                if let Some(v) = body.get("trusted_proxies").and_then(|v| v.as_array()) {
+                    s.trusted_proxies = v.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect();
+                }
+                if let Some(v) = body.get("client_ip_header").and_then(|v| v.as_str()) {
+                    if module_utils::ClientIpSource::from_header_name(v).is_none() {
+                        return (StatusCode::BAD_REQUEST, Json(json!({"ok": false, "error": format!("client_ip_header must be x-forwarded-for, forwarded or x-real-ip (got {:?})", v)}))).into_response();
+                    }
+                    s.client_ip_header = v.trim().to_ascii_lowercase();
+                }
                 if let Some(v) = body.get("disabled_modules").and_then(|v| v.as_array()) {
                     s.disabled_modules = v.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect();
                 }
@@ -175,19 +308,77 @@ pub fn build_router(
                 if let Some(obj) = body.get("feature_extras").and_then(|v| v.as_object()) {
                     s.feature_extras = obj.clone();
                 }
-                let _ = save_settings(&s);
+                // Ghi thất bại thì không reload: router tiếp tục chạy với cấu hình cũ
+                if let Err(e) = save_settings_as(&s, &who.username, None) {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"ok": false, "error": e.to_string()}))).into_response();
+                }
                 // Trigger reload via provided closure
                 (reload_fn)();
-                Json(json!({"ok":true}))
+                Json(json!({"ok":true})).into_response()
             }
-        }))
+        }).route_layer(from_fn_with_state(Role::Operator, auth::require_role)))
+        // Lịch sử cấu hình: danh sách phiên bản, snapshot, diff và rollback (viewer chỉ xem settings hiện tại đã che)
+        .route("/settings/history", axum::routing::get(|| async move {
+            Json(json!({ "versions": history::list() }))
+        }).route_layer(from_fn_with_state(Role::Operator, auth::require_role)))
+        .route("/settings/history/:version", axum::routing::get(|AxumPath(version): AxumPath<u64>| async move {
+            match history::load(version) {
+                Some((meta, settings)) => Json(json!({ "version": meta, "settings": settings })).into_response(),
+                None => not_found(format!("version {} not found", version)),
+            }
+        }).route_layer(from_fn_with_state(Role::Operator, auth::require_role)))
+        .route("/settings/history/:version/diff", axum::routing::get(
+            |AxumPath(version): AxumPath<u64>, Query(q): Query<DiffQuery>| async move {
+                let Some((_, to)) = history::load(version) else {
+                    return not_found(format!("version {} not found", version));
+                };
+                // Mặc định so với phiên bản liền trước; "current" so với file đang dùng
+                let against = q.against.unwrap_or_else(|| "previous".to_string());
+                let (from_label, from) = match against.as_str() {
+                    "current" => ("current".to_string(), load_base_settings()),
+                    "previous" => match history::load(version.saturating_sub(1)) {
+                        Some((_, s)) => (format!("v{}", version - 1), s),
+                        None => ("empty".to_string(), FeaturesSettings::default()),
+                    },
+                    other => match other.trim_start_matches('v').parse::<u64>().ok().and_then(history::load) {
+                        Some((meta, s)) => (format!("v{}", meta.version), s),
+                        None => return not_found(format!("version {} not found", other)),
+                    },
+                };
+                let to_label = format!("v{}", version);
+                let diff = history::diff(&from, &to, &from_label, &to_label);
+                Json(json!({ "from": from_label, "to": to_label, "diff": diff })).into_response()
+            }
+        ).route_layer(from_fn_with_state(Role::Operator, auth::require_role)))
+        .route("/settings/rollback", axum::routing::post({
+            let reload_fn = reload_fn.clone();
+            // Snapshot lịch sử không chứa secret (đã che): rollback khôi phục mọi thứ khác và giữ secret hiện tại.
+            // secrets_not_restored liệt kê các secret đó khi giá trị hiện tại khác lúc snapshot (hash khác nhau)
+            move |Extension(who): Extension<AdminIdentity>, AxumJson(body): AxumJson<RollbackBody>| async move {
+                let Some((target, snapshot)) = history::load(body.version) else {
+                    return not_found(format!("version {} not found", body.version));
+                };
+                let note = Some(format!("rollback to v{}", body.version));
+                match save_settings_as(&snapshot, &who.username, note) {
+                    Ok(meta) => {
+                        (reload_fn)();
+                        let not_restored = if meta.hash == target.hash { Vec::new() } else { secrets::redacted_paths(&snapshot.feature_extras) };
+                        Json(json!({ "ok": true, "version": meta, "secrets_not_restored": not_restored })).into_response()
+                    }
+                    Err(e) => (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(json!({ "ok": false, "error": e.to_string() })),
+                    ).into_response(),
+                }
+            }
+        }).route_layer(from_fn_with_state(Role::Admin, auth::require_role)))
         .route("/reload", axum::routing::post({
             let reload_fn = reload_fn.clone();
             move || async move {
                 (reload_fn)();
                 Json(json!({"ok":true}))
             }
-        }))
+        }).route_layer(from_fn_with_state(Role::Operator, auth::require_role)))
         .route("/routes", axum::routing::get({
             let live_spec = live_spec.clone();
             move || async move {
@@ -225,17 +416,29 @@ pub fn build_router(
         }))
         .route("/routes", axum::routing::post({
             let reload_fn = reload_fn.clone();
-            move |AxumJson(body): AxumJson<serde_json::Value>| async move {
-                let mut s = load_settings();
+            move |Extension(who): Extension<AdminIdentity>, AxumJson(body): AxumJson<serde_json::Value>| async move {
+                let mut s = load_base_settings();
                 if let Some(v) = body.get("disabled_routes").and_then(|v| v.as_array()) {
                     s.disabled_routes = v.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect();
                 }
-                let _ = save_settings(&s);
+                if let Err(e) = save_settings_as(&s, &who.username, None) {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"ok": false, "error": e.to_string()}))).into_response();
+                }
                 // Áp dụng ngay: reload OpenAPI và router
                 (reload_fn)();
-                Json(json!({"ok": true}))
+                Json(json!({"ok": true})).into_response()
             }
-        }))
-        // Áp dụng guard IP/Host cho một số endpoint nhạy cảm
-        .layer(from_fn(admin_access_guard))
+        }).route_layer(from_fn_with_state(Role::Operator, auth::require_role)))
+        // Cấu hình hiệu lực sau khi gộp các lớp, kèm nguồn của từng giá trị
+        .route("/config/effective", axum::routing::get(|| async move {
+            Json(config::describe())
+        }).route_layer(from_fn_with_state(Role::Operator, auth::require_role)))
+        // Audit log: lọc theo actor/kind/path/source/since/until, mới nhất trước
+        .route("/audit", axum::routing::get(|Query(q): Query<audit::AuditQuery>| async move {
+            Json(json!({ "entries": audit::query(&q) }))
+        }).route_layer(from_fn_with_state(Role::Admin, auth::require_role)))
+        .merge(extra)
+        // Audit chạy sau guard để biết actor; guard xác thực + IP allowlist bọc ngoài cùng
+        .layer(from_fn(audit::audit_layer))
+        .layer(from_fn(auth::admin_auth_guard))
 }
\ No newline at end of file
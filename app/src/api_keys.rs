@@ -0,0 +1,88 @@
+// Feature api_key: guard xác thực API key và route Admin quản lý key
+use axum::body::Body;
+use axum::extract::Path as AxumPath;
+use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::middleware::{from_fn_with_state, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, put};
+use axum::{Json, Router};
+use admin::auth::{require_role, Role};
+use api_key::{ApiKeyConfig, KeyError, KeyPolicy};
+use serde_json::json;
+
+// Giống CORS: chỉ bật khi plugin có trong build và không bị disable
+pub fn enabled(s: &admin::FeaturesSettings) -> bool {
+    crate::features_loader::has_feature("./build", "api_key") && !s.disabled_features.iter().any(|f| f == "api_key")
+}
+
+// Ghi last_used_at đã gom trong bộ nhớ xuống file (gọi định kỳ từ main)
+pub fn flush() {
+    if let Err(e) = api_key::flush() {
+        tracing::warn!("⚠️ api_key: cannot write {}: {}", api_key::KEYS_FILE, e);
+    }
+}
+
+pub async fn guard(mut req: Request<Body>, next: Next) -> Response {
+    let settings = admin::load_settings();
+    let cfg = ApiKeyConfig::from_extras(settings.feature_extras.get("api_key"));
+    let path = req.uri().path().to_string();
+    let headers = req.headers();
+    let raw = cfg.extract(|name| headers.get(name).and_then(|v| v.to_str().ok())).map(|s| s.to_string());
+    let result = match raw {
+        Some(k) => api_key::authenticate(&k, &path),
+        None => Err(KeyError::Missing),
+    };
+    match result {
+        Ok(key) => {
+            // Principal cho các lớp sau (oauth2 guard, plugin ABI ctx)
+            req.extensions_mut().insert(key.principal());
+            next.run(req).await
+        }
+        // Vượt rate limit của key luôn bị chặn, kể cả trên route không bắt buộc key
+        Err(e @ KeyError::RateLimited(_)) => reject(&e),
+        Err(e) if cfg.requires_key(&path) => reject(&e),
+        // Route không bắt buộc: key lỗi/không có thì coi như request ẩn danh
+        Err(_) => next.run(req).await,
+    }
+}
+
+fn reject(e: &KeyError) -> Response {
+    let status = StatusCode::from_u16(e.status()).unwrap_or(StatusCode::UNAUTHORIZED);
+    let mut resp = (status, Json(e.body())).into_response();
+    if let KeyError::RateLimited(secs) = e {
+        if let Ok(v) = HeaderValue::from_str(&secs.to_string()) {
+            resp.headers_mut().insert(header::RETRY_AFTER, v);
+        }
+    }
+    resp
+}
+
+fn bad_request(msg: String) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({"ok": false, "error": msg}))).into_response()
+}
+
+// Route admin (nest dưới /admin); key gốc chỉ trả về một lần khi tạo
+pub fn admin_routes() -> Router {
+    let admin_only = || from_fn_with_state(Role::Admin, require_role);
+    Router::new()
+        .route("/api-keys", get(|| async move {
+            let keys: Vec<_> = api_key::list_keys().iter().map(|k| k.summary()).collect();
+            Json(json!({"keys": keys}))
+        }).post(|Json(policy): Json<KeyPolicy>| async move {
+            match api_key::create_key(policy) {
+                Ok((k, secret)) => Json(json!({"ok": true, "key": k.summary(), "api_key": secret})).into_response(),
+                Err(e) => bad_request(e),
+            }
+        }).route_layer(admin_only()))
+        .route("/api-keys/:id", put(|AxumPath(id): AxumPath<String>, Json(policy): Json<KeyPolicy>| async move {
+            match api_key::update_key(&id, policy) {
+                Ok(k) => Json(json!({"ok": true, "key": k.summary()})).into_response(),
+                Err(e) => bad_request(e),
+            }
+        }).delete(|AxumPath(id): AxumPath<String>| async move {
+            match api_key::delete_key(&id) {
+                Ok(()) => Json(json!({"ok": true})).into_response(),
+                Err(e) => bad_request(e),
+            }
+        }).route_layer(admin_only()))
+}
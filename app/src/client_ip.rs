@@ -0,0 +1,43 @@
+// Giải quyết IP client một lần cho mỗi request và gắn module_utils::ClientIp vào extension;
+// admin, các feature (rate limit, quota, waf, ip_filter) và plugin (RequestContext.client_ip) đều đọc từ đây
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use module_utils::{ClientIp, ClientIpSource};
+use std::net::SocketAddr;
+
+// Header IP client theo cấu hình; giá trị sai -> không tin header nào (dùng IP kết nối)
+fn header_source(s: &admin::FeaturesSettings) -> ClientIpSource {
+    ClientIpSource::from_header_name(&s.client_ip_header).unwrap_or(ClientIpSource::Connection)
+}
+
+// Gọi khi dựng router: báo cấu hình IP client sai / cấu hình cũ không còn được dùng
+pub fn log_errors(s: &admin::FeaturesSettings) {
+    if ClientIpSource::from_header_name(&s.client_ip_header).is_none() {
+        tracing::warn!("⚠️ client_ip_header {:?} is not x-forwarded-for, forwarded or x-real-ip; proxy headers are ignored", s.client_ip_header);
+    }
+    if s.feature_extras.get("rate_limit").and_then(|o| o.get("trusted_proxies")).is_some() {
+        tracing::warn!("⚠️ feature_extras.rate_limit.trusted_proxies is no longer used; move the entries to trusted_proxies");
+    }
+}
+
+pub async fn resolve(mut req: Request<Body>, next: Next) -> Response {
+    if let Some(peer) = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|ci| ci.0.ip()) {
+        let settings = admin::load_settings();
+        let trusted = module_utils::parse_trusted(&settings.trusted_proxies);
+        let headers = req.headers();
+        let client = module_utils::resolve_client_ip(
+            peer,
+            |name| {
+                let values: Vec<&str> = headers.get_all(name).iter().filter_map(|v| v.to_str().ok()).collect();
+                (!values.is_empty()).then(|| values.join(", "))
+            },
+            &trusted,
+            header_source(&settings),
+        );
+        req.extensions_mut().insert::<ClientIp>(client);
+    }
+    next.run(req).await
+}
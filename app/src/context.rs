@@ -0,0 +1,39 @@
+// Dựng RequestContext gửi sang plugin (ABI ctx) từ request hiện tại
+use axum::body::{Body, Bytes};
+use axum::http::{request::Parts, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use module_utils::{ClientIp, CspNonce, Principal, RequestContext};
+use std::collections::BTreeMap;
+
+// Giới hạn body giống mặc định của extractor Bytes trong axum
+const BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+// Header chứa thông tin xác thực không chuyển cho plugin (đã được xác thực thành Principal)
+const REDACTED_HEADERS: &[&str] = &["authorization", "cookie", "x-api-key"];
+
+pub fn from_parts(parts: &Parts) -> RequestContext {
+    let headers: BTreeMap<String, String> = parts
+        .headers
+        .iter()
+        .filter(|(k, _)| !REDACTED_HEADERS.contains(&k.as_str()))
+        .filter_map(|(k, v)| v.to_str().ok().map(|v| (k.as_str().to_string(), v.to_string())))
+        .collect();
+    RequestContext {
+        method: parts.method.as_str().to_string(),
+        path: parts.uri.path().to_string(),
+        query: parts.uri.query().map(|q| q.to_string()),
+        headers,
+        principal: parts.extensions.get::<Principal>().cloned(),
+        client_ip: parts.extensions.get::<ClientIp>().map(|c| c.ip.to_string()),
+        csp_nonce: parts.extensions.get::<CspNonce>().map(|n| n.0.clone()),
+    }
+}
+
+// Tách request thành (context, body) cho handler ctx
+pub async fn split(req: Request<Body>) -> Result<(RequestContext, Bytes), Response> {
+    let (parts, body) = req.into_parts();
+    let bytes = axum::body::to_bytes(body, BODY_LIMIT)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE.into_response())?;
+    Ok((from_parts(&parts), bytes))
+}
@@ -0,0 +1,58 @@
+// Feature cors: middleware áp chính sách CORS của crate cors cho mọi route plugin, tự trả lời preflight
+use axum::body::Body;
+use axum::http::{HeaderName, HeaderValue, Method, Request, StatusCode};
+use axum::middleware::{from_fn_with_state, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use admin::auth::{require_role, Role};
+
+// Plugin có trong build và không bị disable
+pub fn enabled(s: &admin::FeaturesSettings) -> bool {
+    crate::features_loader::has_feature("./build", "cors") && !s.disabled_features.iter().any(|f| f == "cors")
+}
+
+fn apply(resp: &mut Response, headers: Vec<(&'static str, String)>) {
+    for (name, value) in headers {
+        let Ok(v) = HeaderValue::from_str(&value) else { continue };
+        let name = HeaderName::from_static(name);
+        // Vary có thể đã được handler đặt: thêm chứ không ghi đè
+        if name == http::header::VARY { resp.headers_mut().append(name, v); } else { resp.headers_mut().insert(name, v); }
+    }
+}
+
+// Chạy ngoài cùng: preflight được trả lời ở đây (204) nên không phụ thuộc route có khai báo OPTIONS
+// và không bị các guard xác thực chặn (trình duyệt không gửi credentials khi preflight)
+pub async fn guard(req: Request<Body>, next: Next) -> Response {
+    let settings = admin::load_settings();
+    let Some(extras) = settings.feature_extras.get("cors") else { return next.run(req).await };
+    let cfg = cors::config_for(Some(extras));
+    let Some(policy) = cfg.policy_for(req.uri().path()) else { return next.run(req).await };
+    let origin = req.headers().get(http::header::ORIGIN).and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    let preflight = req.method() == Method::OPTIONS && req.headers().contains_key(http::header::ACCESS_CONTROL_REQUEST_METHOD);
+    if let (true, Some(o)) = (preflight, origin.as_deref()) {
+        let mut resp = StatusCode::NO_CONTENT.into_response();
+        apply(&mut resp, policy.preflight_headers(o));
+        return resp;
+    }
+    let headers = policy.response_headers(origin.as_deref());
+    let mut resp = next.run(req).await;
+    apply(&mut resp, headers);
+    resp
+}
+
+// Cảnh báo cấu hình sai khi dựng router (route có lỗi không được áp CORS)
+pub fn log_errors(s: &admin::FeaturesSettings) {
+    let Some(extras) = s.feature_extras.get("cors") else { return };
+    for e in &cors::config_for(Some(extras)).errors {
+        tracing::warn!("⚠️ cors: {}", e);
+    }
+}
+
+// Route admin (nest dưới /admin): chính sách đã phân giải và lỗi cấu hình
+pub fn admin_routes() -> Router {
+    Router::new().route("/cors", get(|| async move {
+        let settings = admin::load_settings();
+        Json(cors::config_for(settings.feature_extras.get("cors")).as_ref().clone())
+    }).route_layer(from_fn_with_state(Role::Admin, require_role)))
+}
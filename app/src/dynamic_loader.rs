@@ -3,7 +3,7 @@ use libloading::Library;
 use admin::load_settings;
 use std::path::Path;
 use walkdir::WalkDir;
-use crate::types::{MethodSet, RawHandler, RawHandlerWithBody, RawRoutePath, RawStr};
+use crate::types::{MethodSet, RawCtxHandler, RawHandler, RawHandlerWithBody, RawRoutePath, RawStr};
 use crate::util::path_to_route;
 use tracing::{info, warn};
 use serde_json::Value;
@@ -78,13 +78,23 @@ impl DynamicModules {
                                             let sym_bytes = handler_sym.as_bytes().to_vec();
                                             
                                             // Get or create MethodSet for this path
-                                            let method_set = path_methods.entry(path.to_string()).or_insert(MethodSet {
-                                                get: None,
-                                                post: None,
-                                                put: None,
-                                                delete: None,
-                                            });
-                                            
+                                            let method_set = path_methods.entry(path.to_string()).or_default();
+
+                                            // Handler khai báo Principal/RequestContext: nạp theo ABI ctx
+                                            if item.get("abi").and_then(|v| v.as_str()) == Some("ctx") {
+                                                if let Ok(sym) = lib.get::<RawCtxHandler>(&sym_bytes) {
+                                                    let slot = match method {
+                                                        "post" => &mut method_set.post_ctx,
+                                                        "put" => &mut method_set.put_ctx,
+                                                        "delete" => &mut method_set.delete_ctx,
+                                                        _ => &mut method_set.get_ctx,
+                                                    };
+                                                    *slot = Some(*sym);
+                                                    info!("🧩 Loaded {} ({}) - method: {} (ctx)", path, handler_sym, method.to_uppercase());
+                                                }
+                                                continue;
+                                            }
+
                                             // Load the appropriate handler based on method
                                             match method {
                                                 "get" => {
@@ -118,7 +128,7 @@ impl DynamicModules {
                                         // Add all accumulated routes, skipping disabled ones
                                         for (path, methods) in path_methods {
                                             if settings.disabled_routes.iter().any(|r| r == &path) { continue; }
-                                            if methods.get.is_some() || methods.post.is_some() || methods.put.is_some() || methods.delete.is_some() {
+                                            if !methods.is_empty() {
                                                 routes.insert(path, methods);
                                             }
                                         }
@@ -148,8 +158,8 @@ impl DynamicModules {
                                 let put: Option<RawHandlerWithBody> = lib.get::<RawHandlerWithBody>(b"put_bytes").ok().map(|sym| *sym);
                                 let delete: Option<RawHandler> = lib.get::<RawHandler>(b"delete").ok().map(|sym| *sym);
 
-                                let methods = MethodSet { get, post, put, delete };
-                                if methods.get.is_some() || methods.post.is_some() || methods.put.is_some() || methods.delete.is_some() {
+                                let methods = MethodSet { get, post, put, delete, ..Default::default() };
+                                if !methods.is_empty() {
                                     info!("🧩 Loaded {}", route);
                                     routes.insert(route.clone(), methods);
                                     let _keep_alive: &'static Library = Box::leak(Box::new(lib));
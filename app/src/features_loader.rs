@@ -127,7 +127,6 @@ pub fn load_features(features_src_dir: &str, build_dir: &str) -> Vec<String> {
 pub fn collect_manifests(build_dir: &str) -> Vec<Value> {
     // Tự động phát hiện features từ thư mục features/
     let feature_names = discover_feature_names("./features");
-    let settings = load_settings();
     let mut manifests: Vec<Value> = Vec::new();
     let build_dir_path = Path::new(build_dir);
 
@@ -180,6 +179,8 @@ pub fn collect_manifests(build_dir: &str) -> Vec<Value> {
         }
     }
 
+    // Khóa "secret": true trong manifest được che ở mọi API/log của admin
+    admin::secrets::register_manifests(&manifests);
     manifests
 }
 
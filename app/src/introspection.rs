@@ -0,0 +1,180 @@
+// Introspection token opaque (RFC 7662) cho feature oauth2, cache kết quả theo TTL
+use oauth2::{AuthError, IntrospectionConfig, JwtConfig};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// Giới hạn số token được cache để không phình bộ nhớ khi bị spam token rác
+const MAX_ENTRIES: usize = 10_000;
+// Phản hồi active=false chỉ cache ngắn (token có thể vừa được cấp ở upstream)
+const NEGATIVE_TTL: Duration = Duration::from_secs(10);
+
+struct Entry {
+    response: Value,
+    expires_at: Instant,
+}
+
+static CACHE: Lazy<RwLock<HashMap<String, Entry>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+static HTTP: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap_or_default()
+});
+
+async fn fetch(token: &str, cfg: &IntrospectionConfig) -> Result<Value, AuthError> {
+    let mut req = HTTP
+        .post(&cfg.url)
+        .header("accept", "application/json")
+        .form(&[("token", token), ("token_type_hint", "access_token")]);
+    if !cfg.client_id.is_empty() {
+        req = req.basic_auth(&cfg.client_id, Some(&cfg.client_secret));
+    }
+    let res = req.send().await.and_then(|r| r.error_for_status()).map_err(|e| {
+        warn!("⚠️ Introspection {} lỗi: {}", cfg.url, e);
+        AuthError::Unavailable("introspection endpoint error".into())
+    })?;
+    res.json::<Value>().await.map_err(|e| {
+        warn!("⚠️ Introspection {} trả về dữ liệu không hợp lệ: {}", cfg.url, e);
+        AuthError::Unavailable("invalid introspection response".into())
+    })
+}
+
+fn store(key: String, response: Value, ttl: Duration) {
+    let mut cache = CACHE.write();
+    if cache.len() >= MAX_ENTRIES {
+        let now = Instant::now();
+        cache.retain(|_, e| e.expires_at > now);
+        if cache.len() >= MAX_ENTRIES { cache.clear(); }
+    }
+    cache.insert(key, Entry { response, expires_at: Instant::now() + ttl });
+}
+
+fn lookup(key: &str) -> Option<Value> {
+    CACHE.read().get(key).filter(|e| e.expires_at > Instant::now()).map(|e| e.response.clone())
+}
+
+// Chỉ dùng kết quả đã cache, không gọi authorization server (route công khai: token lạ không tạo request ra ngoài)
+pub fn cached(token: &str, jcfg: &JwtConfig) -> Option<Result<Value, AuthError>> {
+    lookup(&oauth2::token_fingerprint(token)).map(|r| oauth2::claims_from_introspection(&r, jcfg))
+}
+
+// Trả về claims khi token còn active; kết quả được cache tối đa introspection_cache_secs (và không quá exp)
+pub async fn verify(token: &str, icfg: &IntrospectionConfig, jcfg: &JwtConfig) -> Result<Value, AuthError> {
+    if icfg.url.is_empty() { return Err(AuthError::Misconfigured("introspection_url not set".into())); }
+    let key = oauth2::token_fingerprint(token);
+    let response = match lookup(&key) {
+        Some(r) => r,
+        None => {
+            let r = fetch(token, icfg).await?;
+            let active = r.get("active").and_then(|v| v.as_bool()) == Some(true);
+            let ttl = if active { Duration::from_secs(oauth2::introspection_ttl(&r, icfg)) } else { NEGATIVE_TTL.min(Duration::from_secs(icfg.cache_secs)) };
+            if !ttl.is_zero() { store(key, r.clone(), ttl); }
+            r
+        }
+    };
+    oauth2::claims_from_introspection(&response, jcfg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Authorization server giả: token bắt đầu bằng "active-" là active, "down-" trả 500, còn lại inactive
+    async fn stub() -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/introspect", listener.local_addr().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut sock, _)) = listener.accept().await else { return };
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                // Đọc hết header rồi đủ Content-Length byte body
+                loop {
+                    let n = sock.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 { break; }
+                    buf.extend_from_slice(&chunk[..n]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let len = head
+                            .lines()
+                            .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                            .unwrap_or(0);
+                        if body.len() >= len { break; }
+                    }
+                }
+                let text = String::from_utf8_lossy(&buf).to_string();
+                let body = text.split_once("\r\n\r\n").map(|(_, b)| b.to_string()).unwrap_or_default();
+                let (status, payload) = if body.contains("token=active-") {
+                    ("200 OK", json!({"active": true, "sub": "alice", "scope": "read", "exp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() + 3600}))
+                } else if body.contains("token=down-") {
+                    ("500 Internal Server Error", json!({}))
+                } else {
+                    ("200 OK", json!({"active": false}))
+                };
+                let payload = payload.to_string();
+                let resp = format!(
+                    "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    payload.len(),
+                    payload
+                );
+                let _ = sock.write_all(resp.as_bytes()).await;
+            }
+        });
+        (url, hits)
+    }
+
+    fn configs(url: &str, cache_secs: u64) -> (IntrospectionConfig, JwtConfig) {
+        let extras = json!({"introspection_url": url, "introspection_cache_secs": cache_secs});
+        (IntrospectionConfig::from_extras(Some(&extras)), JwtConfig::from_extras(Some(&extras)))
+    }
+
+    #[tokio::test]
+    async fn active_token_is_cached() {
+        let (url, hits) = stub().await;
+        let (icfg, jcfg) = configs(&url, 60);
+        assert!(cached("active-cached", &jcfg).is_none());
+        let claims = verify("active-cached", &icfg, &jcfg).await.unwrap();
+        assert_eq!(claims["sub"], "alice");
+        assert_eq!(verify("active-cached", &icfg, &jcfg).await.unwrap()["scope"], "read");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert!(cached("active-cached", &jcfg).is_some_and(|r| r.is_ok()));
+    }
+
+    #[tokio::test]
+    async fn inactive_token_is_cached_for_negative_ttl_only() {
+        let (url, hits) = stub().await;
+        // cache_secs = 1 < NEGATIVE_TTL: phản hồi inactive chỉ được giữ 1s
+        let (icfg, jcfg) = configs(&url, 1);
+        assert!(matches!(verify("inactive-1", &icfg, &jcfg).await, Err(AuthError::Inactive)));
+        assert!(matches!(verify("inactive-1", &icfg, &jcfg).await, Err(AuthError::Inactive)));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert!(cached("inactive-1", &jcfg).is_some_and(|r| r.is_err()));
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(cached("inactive-1", &jcfg).is_none());
+        assert!(matches!(verify("inactive-1", &icfg, &jcfg).await, Err(AuthError::Inactive)));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn upstream_errors_are_unavailable_and_not_cached() {
+        let (url, hits) = stub().await;
+        let (icfg, jcfg) = configs(&url, 60);
+        assert!(matches!(verify("down-1", &icfg, &jcfg).await, Err(AuthError::Unavailable(_))));
+        assert!(matches!(verify("down-1", &icfg, &jcfg).await, Err(AuthError::Unavailable(_))));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert!(cached("down-1", &jcfg).is_none());
+    }
+}
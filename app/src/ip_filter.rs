@@ -0,0 +1,131 @@
+// Feature ip_filter: chặn IP theo allow/deny CIDR, tự động ban IP vi phạm nhiều lần, route Admin quản lý ban
+use axum::body::Body;
+use axum::extract::Path as AxumPath;
+use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::middleware::{from_fn_with_state, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use admin::auth::{require_role, Role};
+use ip_filter::{Access, IpFilterConfig, Offense};
+use serde::Deserialize;
+use serde_json::json;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Layer toàn cục được gắn một lần; trạng thái bật/tắt cập nhật mỗi lần dựng lại router plugin
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_active(on: bool) {
+    ACTIVE.store(on, Ordering::Relaxed);
+}
+
+// Giống CORS: chỉ bật khi plugin có trong build và không bị disable
+pub fn enabled(s: &admin::FeaturesSettings) -> bool {
+    crate::features_loader::has_feature("./build", "ip_filter") && !s.disabled_features.iter().any(|f| f == "ip_filter")
+}
+
+fn config() -> std::sync::Arc<IpFilterConfig> {
+    ip_filter::config_for(admin::load_settings().feature_extras.get("ip_filter"))
+}
+
+fn forbidden(path: &str, detail: &str, retry_after: Option<u64>) -> Response {
+    let body = json!({
+        "type": "about:blank",
+        "title": "Forbidden",
+        "status": 403,
+        "detail": detail,
+        "instance": path,
+    });
+    let mut resp = (StatusCode::FORBIDDEN, Json(body)).into_response();
+    resp.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+    if let Some(secs) = retry_after {
+        resp.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(secs));
+    }
+    resp
+}
+
+// Layer toàn cục (ngay trong client_ip::resolve): chặn trước mọi route và guard khác, kể cả /admin và /oauth2/token,
+// sau đó đếm 401/429/WAF của response (kể cả đăng nhập admin sai) để tự động ban
+pub async fn guard(req: Request<Body>, next: Next) -> Response {
+    if !ACTIVE.load(Ordering::Relaxed) { return next.run(req).await; }
+    let settings = admin::load_settings();
+    let extras = settings.feature_extras.get("ip_filter");
+    let Some(ip) = req.extensions().get::<module_utils::ClientIp>().map(|c| c.ip) else { return next.run(req).await };
+    let cfg = ip_filter::config_for(extras);
+    let path = req.uri().path().to_string();
+    let now = ip_filter::now_secs();
+    match ip_filter::check(ip, &path, &cfg, now) {
+        Access::Allowed => {}
+        Access::Denied(reason) => {
+            tracing::info!("🚫 ip_filter: denied {} {} ({})", ip, path, reason);
+            return forbidden(&path, "Client IP address is not allowed", None);
+        }
+        Access::Banned(ban) => {
+            return forbidden(&path, "Client IP address is banned", ban.retry_after_secs(now));
+        }
+    }
+    let resp = next.run(req).await;
+    let offense = if resp.extensions().get::<crate::waf::Blocked>().is_some() {
+        Some(Offense::Waf)
+    } else {
+        Offense::from_status(resp.status().as_u16())
+    };
+    if let Some(ban) = offense.and_then(|o| ip_filter::record_offense(ip, o, &cfg, now)) {
+        tracing::warn!("🚫 ip_filter: banned {} for {}s ({})", ban.ip, cfg.ban.ban_secs, ban.reason);
+    }
+    resp
+}
+
+#[derive(Debug, Deserialize)]
+struct BanRequest {
+    ip: String,
+    // Thiếu = ban_duration_secs trong cấu hình; 0 = vĩnh viễn
+    duration_secs: Option<u64>,
+    reason: Option<String>,
+}
+
+fn bad_request(msg: String) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({"ok": false, "error": msg}))).into_response()
+}
+
+// Route admin (nest dưới /admin)
+pub fn admin_routes() -> Router {
+    let admin_only = || from_fn_with_state(Role::Admin, require_role);
+    Router::new()
+        // Cấu hình đang áp dụng (kèm lỗi cấu hình), ban và bộ đếm vi phạm
+        .route("/ip-filter", get(|| async move {
+            let cfg = config();
+            let now = ip_filter::now_secs();
+            Json(json!({"config": cfg.as_ref(), "bans": ip_filter::bans(now), "offenses": ip_filter::offenses(&cfg, now)}))
+        }).route_layer(admin_only()))
+        .route("/ip-filter/bans", get(|| async move {
+            Json(json!({"bans": ip_filter::bans(ip_filter::now_secs())}))
+        }).post(|Json(req): Json<BanRequest>| async move {
+            let Ok(ip) = req.ip.trim().parse::<IpAddr>() else { return bad_request(format!("invalid IP address {:?}", req.ip)) };
+            let duration = match req.duration_secs {
+                Some(0) => None,
+                Some(d) => Some(d),
+                None => Some(config().ban.ban_secs),
+            };
+            let ban = ip_filter::ban(ip, duration, req.reason.as_deref().unwrap_or("manual ban"), ip_filter::now_secs());
+            flush();
+            Json(json!({"ok": true, "ban": ban})).into_response()
+        }).route_layer(admin_only()))
+        .route("/ip-filter/bans/:ip", delete(|AxumPath(ip): AxumPath<String>| async move {
+            let Ok(addr) = ip.parse::<IpAddr>() else { return bad_request(format!("invalid IP address {:?}", ip)) };
+            if ip_filter::unban(addr) {
+                flush();
+                Json(json!({"ok": true})).into_response()
+            } else {
+                (StatusCode::NOT_FOUND, Json(json!({"ok": false, "error": format!("{} is not banned", ip)}))).into_response()
+            }
+        }).route_layer(admin_only()))
+}
+
+// Ghi danh sách ban xuống file (gọi định kỳ)
+pub fn flush() {
+    if let Err(e) = ip_filter::flush(&config(), ip_filter::now_secs()) {
+        tracing::warn!("⚠️ ip_filter: cannot write {}: {}", ip_filter::BANS_FILE, e);
+    }
+}
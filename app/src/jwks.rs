@@ -0,0 +1,103 @@
+// Cache JWKS cho feature oauth2: file cục bộ (nạp lại khi mtime đổi) hoặc URL (nạp lại theo TTL)
+use oauth2::{AuthError, JwkSet, JwtConfig};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+// Khi gặp kid lạ sẽ tải lại JWKS (xoay vòng khóa), nhưng không quá 1 lần / 30s
+const MIN_REFRESH: Duration = Duration::from_secs(30);
+
+struct Entry {
+    set: Arc<JwkSet>,
+    fetched_at: Instant,
+    mtime: Option<SystemTime>,
+}
+
+static CACHE: Lazy<RwLock<HashMap<String, Entry>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+static HTTP: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .unwrap_or_default()
+});
+
+async fn fetch(cfg: &JwtConfig) -> Option<(JwkSet, Option<SystemTime>)> {
+    if !cfg.jwks_file.is_empty() {
+        let mtime = std::fs::metadata(&cfg.jwks_file).and_then(|m| m.modified()).ok();
+        let text = match std::fs::read_to_string(&cfg.jwks_file) {
+            Ok(t) => t,
+            Err(e) => { warn!("⚠️ Không đọc được JWKS {}: {}", cfg.jwks_file, e); return None; }
+        };
+        return match oauth2::parse_jwks(&text) {
+            Ok(set) => Some((set, mtime)),
+            Err(e) => { warn!("⚠️ JWKS {} không hợp lệ: {}", cfg.jwks_file, e); None }
+        };
+    }
+    if !cfg.jwks_url.is_empty() {
+        let res = HTTP.get(&cfg.jwks_url).send().await.and_then(|r| r.error_for_status());
+        let text = match res {
+            Ok(r) => r.text().await.ok()?,
+            Err(e) => { warn!("⚠️ Không tải được JWKS {}: {}", cfg.jwks_url, e); return None; }
+        };
+        return match oauth2::parse_jwks(&text) {
+            Ok(set) => Some((set, None)),
+            Err(e) => { warn!("⚠️ JWKS {} không hợp lệ: {}", cfg.jwks_url, e); None }
+        };
+    }
+    None
+}
+
+async fn get(cfg: &JwtConfig, force: bool) -> Option<Arc<JwkSet>> {
+    let source = if !cfg.jwks_file.is_empty() { cfg.jwks_file.clone() } else { cfg.jwks_url.clone() };
+    if source.is_empty() { return None; }
+    let cached = CACHE.read().get(&source).map(|e| (e.set.clone(), e.fetched_at, e.mtime));
+    if let Some((set, fetched_at, mtime)) = &cached {
+        let age = fetched_at.elapsed();
+        let fresh = if !cfg.jwks_file.is_empty() {
+            std::fs::metadata(&cfg.jwks_file).and_then(|m| m.modified()).ok() == *mtime
+        } else {
+            age < Duration::from_secs(cfg.jwks_cache_secs)
+        };
+        if fresh && (!force || age < MIN_REFRESH) { return Some(set.clone()); }
+    }
+    match fetch(cfg).await {
+        Some((set, mtime)) => {
+            let set = Arc::new(set);
+            CACHE.write().insert(source, Entry { set: set.clone(), fetched_at: Instant::now(), mtime });
+            Some(set)
+        }
+        // Tải lỗi: dùng tạm bản cũ (nếu có) thay vì từ chối mọi request
+        None => cached.map(|(set, _, _)| set),
+    }
+}
+
+// JWKS từ cấu hình + khóa của authorization server nội bộ (nếu bật)
+async fn key_set(cfg: &JwtConfig, force: bool) -> Option<JwkSet> {
+    let remote = get(cfg, force).await;
+    let local = if cfg.local_issuer { oauth2::issuer::local_jwks() } else { None };
+    match (remote, local) {
+        (Some(r), Some(mut l)) => {
+            l.keys.extend(r.keys.iter().cloned());
+            Some(l)
+        }
+        (Some(r), None) => Some((*r).clone()),
+        (None, l) => l,
+    }
+}
+
+// Xác thực token theo cấu hình oauth2; tự tải lại JWKS một lần khi không tìm thấy kid
+pub async fn verify(token: &str, cfg: &JwtConfig) -> Result<Value, AuthError> {
+    if !cfg.needs_jwks() { return oauth2::verify_jwt(token, cfg, None); }
+    let set = key_set(cfg, false).await;
+    match oauth2::verify_jwt(token, cfg, set.as_ref()) {
+        Err(AuthError::UnknownKey) => {
+            let set = key_set(cfg, true).await;
+            oauth2::verify_jwt(token, cfg, set.as_ref())
+        }
+        r => r,
+    }
+}
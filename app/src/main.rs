@@ -5,6 +5,18 @@ mod router;
 mod watcher;
 mod features_loader;
 mod openapi;
+mod jwks;
+mod introspection;
+mod context;
+mod oauth2_server;
+mod api_keys;
+mod quota;
+mod ip_filter;
+mod waf;
+mod client_ip;
+mod cors;
+mod security_headers;
+mod request_signature;
 
 use axum::{Router, Json, response::Html};
 use parking_lot::RwLock;
@@ -15,9 +27,7 @@ use serde_json::Value;
 use tracing::info;
 use tower_http::trace::TraceLayer;
 use dotenvy::dotenv;
-use admin::{build_router as build_admin_router, load_settings};
-use std::path::PathBuf;
-use walkdir::WalkDir;
+use admin::build_router as build_admin_router;
 use reqwest::Client;
 
 // Autoreadme (deepwiki-rs)
@@ -37,10 +47,12 @@ async fn main() {
     // Load .env if present
     let _ = dotenv();
 
-    let mode = std::env::var("APP_ENV").unwrap_or("dev".into());
-    let port = std::env::var("APP_PORT").unwrap_or("3000".into());
-    let hot_reload = std::env::var("HOT_RELOAD").unwrap_or("1".into()) == "1";
-    let prod_autoload = std::env::var("APP_AUTOLOAD").unwrap_or("0".into()) == "1";
+    // APP_ENV cũng quyết định overlay admin/config/features.{APP_ENV}.json
+    let runtime = admin::config::RuntimeConfig::from_env();
+    let mode = runtime.app_env.clone();
+    let port = runtime.app_port.clone();
+    let hot_reload = runtime.hot_reload;
+    let prod_autoload = runtime.app_autoload;
     let module_dir = if mode == "prod" { "./build" } else { "./modules" };
     info!("🚀 Mode: {}, loading modules from {}", mode, module_dir);
 
@@ -50,6 +62,8 @@ async fn main() {
 
     // Load features ngay từ đầu nếu DLL đã có sẵn trong ./build
     let _ = features_loader::load_features("./features", "./build");
+    // Đăng ký khóa secret từ manifest trước khi admin phục vụ settings
+    let _ = features_loader::collect_manifests("./build");
 
     // DEV: Khởi động server trước; build & load modules/features bất đồng bộ
     if mode == "dev" {
@@ -66,6 +80,7 @@ async fn main() {
                 *live_router_clone.write() = router::build_router_from("./build");
                 *live_spec_clone.write() = openapi::build_openapi_from_modules("./modules", "./build");
                 let _ = features_loader::load_features("./features", "./build");
+                admin::audit::record_reload("startup", Some("initial build completed".to_string()));
                 tracing::info!("✅ Initial build completed and router/spec updated");
             });
         }
@@ -93,7 +108,29 @@ async fn main() {
         });
     }
 
-    let settings = load_settings();
+    // Settings được cache trong admin: file cấu hình đổi thì đọc lại
+    tokio::spawn(async move { watcher::watch_config(admin::config::CONFIG_DIR).await; });
+
+    // Dọn định kỳ trạng thái rate limit đã hết hạn (kể cả khi không còn traffic)
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(std::time::Duration::from_secs(60));
+        loop {
+            tick.tick().await;
+            let removed = rate_limit::sweep();
+            if removed > 0 { tracing::debug!("🧹 rate limit: removed {} expired entries", removed); }
+        }
+    });
+
+    // Ghi bộ đếm quota, danh sách IP bị ban và last_used_at của API key xuống file định kỳ (chỉ ghi khi có thay đổi)
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(std::time::Duration::from_secs(10));
+        loop {
+            tick.tick().await;
+            quota::flush();
+            ip_filter::flush();
+            api_keys::flush();
+        }
+    });
 
     // Tự động sinh README.md bằng Autoreadme (deepwiki-rs)
     tokio::spawn(async move {
@@ -106,7 +143,7 @@ async fn main() {
             }
         }
     });
-    let mut app = Router::new()
+    let app = Router::new()
         .route("/openapi.json", axum::routing::get({
             let live_spec = live_spec.clone();
             move || async move {
@@ -126,15 +163,39 @@ async fn main() {
                     *live_spec.write() = openapi::build_openapi_from_modules("./modules", "./build");
                     // Nạp lại feature plugins theo cấu hình mới
                     let _ = features_loader::load_features("./features", "./build");
+                    // Request admin gây ra reload đã được audit_layer ghi kèm actor
+                    admin::audit::record_reload("admin", None);
                 }
             };
             let reload_fn = std::sync::Arc::new(reload_fn);
-            build_admin_router(live_spec, reload_fn)
+            let extra = Router::new()
                 .route("/features-manifest", axum::routing::get(|| async move {
                     let v = features_loader::collect_manifests("./build");
                     Json(v)
                 }))
+                // Kích thước kho trạng thái rate limit (số key đang theo dõi, số entry đã bị dọn) + backend đang dùng
+                .route("/rate-limit/stats", axum::routing::get(|| async move {
+                    let settings = admin::load_settings();
+                    let extras = settings.feature_extras.get("rate_limit");
+                    let backend = rate_limit::BackendConfig::from_extras(extras)
+                        .map(|cfg| rate_limit::backend::backend_for(&cfg).status())
+                        .unwrap_or_else(|e| serde_json::json!({ "error": e.to_string() }));
+                    let mut v = serde_json::to_value(rate_limit::stats()).unwrap_or_default();
+                    v["backend"] = backend;
+                    Json(v)
+                }))
+                .merge(oauth2_server::admin_routes())
+                .merge(api_keys::admin_routes())
+                .merge(quota::admin_routes())
+                .merge(ip_filter::admin_routes())
+                .merge(cors::admin_routes())
+                .merge(security_headers::admin_routes())
+                .merge(request_signature::admin_routes())
+                .merge(waf::admin_routes());
+            build_admin_router(live_spec, reload_fn, extra)
         })
+        // Authorization server nội bộ của feature oauth2 (trả 404 khi chưa bật)
+        .merge(oauth2_server::public_routes())
         .route("/docs", axum::routing::get(|| async move {
             Html(r#"<!DOCTYPE html>
 <html>
@@ -166,6 +227,10 @@ async fn main() {
                 r.oneshot(req)
             })
         }))
+        // Chặn/ban IP cho mọi route (admin, oauth2 token, plugin); chạy sau client_ip::resolve
+        .layer(axum::middleware::from_fn(ip_filter::guard))
+        // Giải quyết IP client (qua trusted_proxies) trước mọi guard của admin lẫn router plugin
+        .layer(axum::middleware::from_fn(client_ip::resolve))
         // Global middleware: request tracing
         .layer(TraceLayer::new_for_http());
 
@@ -182,12 +247,6 @@ async fn main() {
     axum::serve(listener, svc).await.unwrap(); // ✅ axum 0.7 style
 }
 
-// Sinh README.md bằng Autoreadme
-async fn generate_readme_via_autoreadme() -> Result<(), Box<dyn std::error::Error>> {
-    // Autoreadme đã bị loại bỏ; stub để tránh lỗi biên dịch
-    Ok(())
-}
-
 async fn generate_readme_via_external_api() -> Result<(), Box<dyn std::error::Error>> {
     let url = std::env::var("README_API_URL")?;
     let method = std::env::var("README_API_METHOD").unwrap_or_else(|_| "GET".to_string());
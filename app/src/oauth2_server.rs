@@ -0,0 +1,205 @@
+// Endpoint của authorization server nội bộ (feature oauth2, khi bật local_issuer_enabled)
+// - Công khai: /oauth2/token, /.well-known/jwks.json, /.well-known/openid-configuration
+// - Admin (/admin/oauth2/...): quản lý client, khóa ký và cấp dev token
+use axum::extract::{Extension, Form, Path as AxumPath};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::from_fn_with_state;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use admin::auth::{require_role, Role};
+use module_utils::{ClientIp, ClientIpSource};
+use oauth2::issuer::{self, IssuerConfig};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+// Dev token tối đa 7 ngày
+const MAX_DEV_TOKEN_TTL: u64 = 7 * 86_400;
+
+fn issuer_config() -> Option<IssuerConfig> {
+    let s = admin::load_settings();
+    if !s.oauth2_enabled || s.disabled_features.iter().any(|f| f == "oauth2") { return None; }
+    let cfg = IssuerConfig::from_extras(s.feature_extras.get("oauth2"));
+    if cfg.enabled { Some(cfg) } else { None }
+}
+
+fn disabled() -> Response {
+    (StatusCode::NOT_FOUND, Json(json!({"error": "built-in token endpoint is disabled"}))).into_response()
+}
+
+// public_base_url nếu đã cấu hình; nếu không thì suy ra từ Host. X-Forwarded-Proto chỉ được tin khi
+// request đi qua proxy tin cậy (ClientIp lấy từ header proxy), client gửi trực tiếp không đổi được scheme
+fn base_url(cfg: &IssuerConfig, headers: &HeaderMap, client: Option<&ClientIp>) -> String {
+    if !cfg.public_base_url.is_empty() { return cfg.public_base_url.clone(); }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .filter(|h| !h.is_empty() && h.chars().all(|c| c.is_ascii_alphanumeric() || ".-:[]".contains(c)))
+        .unwrap_or("localhost");
+    let via_proxy = client.is_some_and(|c| c.source != ClientIpSource::Connection);
+    let proto = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .filter(|p| via_proxy && matches!(*p, "http" | "https"))
+        .unwrap_or("http");
+    format!("{}://{}", proto, host)
+}
+
+// Giới hạn mặc định của /oauth2/token theo IP (chống dò client_secret); ghi đè bằng feature_extras.oauth2.token_rate_limit
+fn token_rate_limit() -> rate_limit::Policy {
+    let base = rate_limit::Policy {
+        window: std::time::Duration::from_secs(60),
+        ..rate_limit::Policy::per_second(10, rate_limit::Algorithm::SlidingWindow)
+    };
+    let settings = admin::load_settings();
+    settings
+        .feature_extras
+        .get("oauth2")
+        .and_then(|o| o.get("token_rate_limit"))
+        .and_then(|v| rate_limit::Policy::from_value(v, &base))
+        .unwrap_or(base)
+}
+
+// Token response không được cache (RFC 6749 §5.1)
+fn no_store(status: StatusCode, body: Value) -> Response {
+    (status, [(header::CACHE_CONTROL, "no-store"), (header::PRAGMA, "no-cache")], Json(body)).into_response()
+}
+
+async fn token(headers: HeaderMap, client: Option<Extension<ClientIp>>, Form(form): Form<HashMap<String, String>>) -> Response {
+    let Some(cfg) = issuer_config() else { return disabled() };
+    // Rate limit theo IP trước khi kiểm tra credentials; 401 (sai secret) còn được ip_filter tính để tự động ban
+    let policy = token_rate_limit();
+    let ip = client.map(|Extension(c)| c.ip.to_string()).unwrap_or_else(|| "unknown".to_string());
+    let decision = rate_limit::check(&format!("oauth2_token|ip:{}", ip), &policy);
+    if !decision.allowed {
+        let mut resp = (StatusCode::TOO_MANY_REQUESTS, Json(decision.problem(&policy, "/oauth2/token"))).into_response();
+        resp.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+        if let Some(secs) = decision.retry_after_secs() {
+            resp.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        return resp;
+    }
+    let grant_type = form.get("grant_type").map(|s| s.as_str()).unwrap_or("");
+    if grant_type.is_empty() {
+        let e = issuer::invalid_request("grant_type is required");
+        return no_store(StatusCode::BAD_REQUEST, e.body());
+    }
+    if grant_type != "client_credentials" {
+        return no_store(StatusCode::BAD_REQUEST, issuer::unsupported_grant(grant_type).body());
+    }
+    // client_secret_basic ưu tiên, fallback client_secret_post
+    let auth = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok());
+    let creds = issuer::parse_basic_auth(auth).or_else(|| {
+        Some((form.get("client_id")?.clone(), form.get("client_secret")?.clone()))
+    });
+    let Some((client_id, secret)) = creds else {
+        let e = issuer::invalid_request("client credentials are required");
+        return no_store(StatusCode::BAD_REQUEST, e.body());
+    };
+    match issuer::client_credentials(&cfg, &client_id, &secret, form.get("scope").map(|s| s.as_str())) {
+        Ok(body) => no_store(StatusCode::OK, body),
+        Err(e) => {
+            let status = StatusCode::from_u16(e.status()).unwrap_or(StatusCode::BAD_REQUEST);
+            let mut resp = no_store(status, e.body());
+            if status == StatusCode::UNAUTHORIZED {
+                resp.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Basic"));
+            }
+            resp
+        }
+    }
+}
+
+pub fn public_routes() -> Router {
+    Router::new()
+        .route("/oauth2/token", post(token))
+        .route("/.well-known/jwks.json", get(|| async move {
+            if issuer_config().is_none() { return disabled(); }
+            Json(issuer::public_jwks()).into_response()
+        }))
+        .route("/.well-known/openid-configuration", get(|headers: HeaderMap, client: Option<Extension<ClientIp>>| async move {
+            let Some(cfg) = issuer_config() else { return disabled() };
+            let base = base_url(&cfg, &headers, client.as_ref().map(|Extension(c)| c));
+            Json(issuer::openid_configuration(&cfg, &base)).into_response()
+        }))
+}
+
+#[derive(Deserialize)]
+struct ClientBody {
+    client_id: Option<String>,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    scopes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct DevTokenBody {
+    sub: String,
+    #[serde(default)]
+    scopes: Vec<String>,
+    ttl_secs: Option<u64>,
+    #[serde(default)]
+    claims: Map<String, Value>,
+}
+
+fn bad_request(msg: String) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({"ok": false, "error": msg}))).into_response()
+}
+
+// Route admin, nest dưới /admin cùng guard xác thực; mọi thao tác yêu cầu role admin
+pub fn admin_routes() -> Router {
+    let admin_only = || from_fn_with_state(Role::Admin, require_role);
+    Router::new()
+        .route("/oauth2/clients", get(|| async move {
+            // Không trả secret_hash ra ngoài
+            let clients: Vec<Value> = issuer::list_clients()
+                .into_iter()
+                .map(|c| json!({"client_id": c.client_id, "name": c.name, "scopes": c.scopes, "created_at": c.created_at}))
+                .collect();
+            Json(json!({"clients": clients}))
+        }).route_layer(admin_only()))
+        .route("/oauth2/clients", post(|Json(body): Json<ClientBody>| async move {
+            match issuer::create_client(body.client_id.as_deref(), &body.name, body.scopes) {
+                Ok((c, secret)) => Json(json!({
+                    "ok": true,
+                    "client_id": c.client_id,
+                    "client_secret": secret,
+                    "scopes": c.scopes,
+                })).into_response(),
+                Err(e) => bad_request(e),
+            }
+        }).route_layer(admin_only()))
+        .route("/oauth2/clients/:client_id", delete(|AxumPath(id): AxumPath<String>| async move {
+            match issuer::delete_client(&id) {
+                Ok(()) => Json(json!({"ok": true})).into_response(),
+                Err(e) => bad_request(e),
+            }
+        }).route_layer(admin_only()))
+        .route("/oauth2/clients/:client_id/secret", post(|AxumPath(id): AxumPath<String>| async move {
+            match issuer::rotate_client_secret(&id) {
+                Ok(secret) => Json(json!({"ok": true, "client_id": id, "client_secret": secret})).into_response(),
+                Err(e) => bad_request(e),
+            }
+        }).route_layer(admin_only()))
+        .route("/oauth2/keys", get(|| async move {
+            Json(json!({"keys": issuer::key_summaries()}))
+        }).route_layer(admin_only()))
+        .route("/oauth2/keys/rotate", post(|| async move {
+            match issuer::rotate_keys() {
+                Ok(k) => Json(json!({"ok": true, "kid": k.kid})).into_response(),
+                Err(e) => bad_request(e.to_string()),
+            }
+        }).route_layer(admin_only()))
+        .route("/oauth2/dev-token", post(|Json(body): Json<DevTokenBody>| async move {
+            let Some(cfg) = issuer_config() else {
+                return bad_request("enable oauth2.local_issuer_enabled first".to_string());
+            };
+            if body.sub.trim().is_empty() { return bad_request("sub is required".to_string()); }
+            let ttl = body.ttl_secs.unwrap_or(cfg.token_ttl_secs).clamp(60, MAX_DEV_TOKEN_TTL);
+            match issuer::issue_token(&cfg, body.sub.trim(), &body.scopes, body.claims, ttl) {
+                Ok(t) => no_store(StatusCode::OK, json!({"access_token": t, "token_type": "Bearer", "expires_in": ttl})),
+                Err(e) => bad_request(e.to_string()),
+            }
+        }).route_layer(admin_only()))
+}
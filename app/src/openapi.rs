@@ -11,22 +11,142 @@ type RawHandlerWithBody = unsafe extern "C" fn(*const c_uchar, usize) -> *mut c_
 type RawRoutePath = unsafe extern "C" fn() -> *mut c_char;
 type RawStr = RawRoutePath;
 
-// Kiểm tra route có nằm trong danh sách bảo vệ OAuth2 hay không
-fn route_is_protected(route: &str, list: &[String]) -> bool {
-    fn normalize(p: &str) -> String {
-        if p == "/" { return "/".to_string(); }
-        p.trim_end_matches('/').to_string()
+// Security requirement cho một operation (các phần tử là lựa chọn thay thế nhau):
+// - route bắt buộc API key -> apiKeyAuth
+// - route được OAuth2 bảo vệ -> oauth2 kèm scope theo route_rules, hoặc API key nếu api_key bật và oauth2.accept_api_keys
+// - còn lại -> [] (không yêu cầu xác thực)
+fn security_for(settings: &admin::FeaturesSettings, route: &str, method: &str) -> Vec<Value> {
+    let api_key_on = crate::api_keys::enabled(settings);
+    let key_cfg = api_key::ApiKeyConfig::from_extras(settings.feature_extras.get("api_key"));
+    if api_key_on && key_cfg.requires_key(route) {
+        return vec![json!({"apiKeyAuth": []})];
     }
-    let path_norm = normalize(route);
-    if list.is_empty() { return false; }
-    list.iter().any(|item| {
-        let s = item.as_str();
-        if let Some(prefix) = s.strip_suffix("/*") {
-            let prefix_norm = normalize(prefix);
-            path_norm.starts_with(&prefix_norm)
-        } else {
-            normalize(s) == path_norm
+    let extras = settings.feature_extras.get("oauth2");
+    let protected_routes: Vec<String> = extras
+        .and_then(|v| v.get("protected_routes"))
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+    let (rules, _) = oauth2::parse_route_rules(extras);
+    let mut out = Vec::new();
+    if settings.oauth2_enabled && oauth2::route_is_protected(&protected_routes, &rules, route, method) {
+        let scopes = oauth2::required_scopes(&rules, route, method);
+        out.push(json!({"oauth2": scopes}));
+        if api_key_on && oauth2::accept_api_keys(extras) { out.push(json!({"apiKeyAuth": []})); }
+    }
+    out
+}
+
+fn security_schemes(settings: &admin::FeaturesSettings) -> Value {
+    let mut schemes = serde_json::Map::new();
+    if crate::api_keys::enabled(settings) {
+        let cfg = api_key::ApiKeyConfig::from_extras(settings.feature_extras.get("api_key"));
+        schemes.insert("apiKeyAuth".to_string(), json!({"type": "apiKey", "in": "header", "name": cfg.header_name}));
+    }
+    schemes.insert("oauth2".to_string(), oauth2_scheme(settings));
+    Value::Object(schemes)
+}
+
+fn rate_limit_headers() -> Value {
+    let int = |desc: &str| json!({"description": desc, "schema": {"type": "integer"}});
+    json!({
+        "RateLimit-Limit": int("Request quota of the current policy"),
+        "RateLimit-Remaining": int("Remaining requests in the current window"),
+        "RateLimit-Reset": int("Seconds until the quota is fully restored"),
+        "RateLimit-Policy": {"description": "Quota policy, e.g. 100;w=60", "schema": {"type": "string"}},
+        "Retry-After": int("Seconds to wait before retrying")
+    })
+}
+
+// Route chịu rate limit: thêm header RateLimit-* vào mọi response và response 429 (problem+json)
+fn document_rate_limits(settings: &admin::FeaturesSettings, paths: &mut serde_json::Map<String, Value>) {
+    let extras = settings.feature_extras.get("rate_limit");
+    let header_refs = json!({
+        "RateLimit-Limit": {"$ref": "#/components/headers/RateLimit-Limit"},
+        "RateLimit-Remaining": {"$ref": "#/components/headers/RateLimit-Remaining"},
+        "RateLimit-Reset": {"$ref": "#/components/headers/RateLimit-Reset"},
+        "RateLimit-Policy": {"$ref": "#/components/headers/RateLimit-Policy"}
+    });
+    for (route, item) in paths.iter_mut() {
+        let policy = rate_limit::resolve_policy(extras, route, settings.route_rate_limits.get(route).copied(), settings.rate_limit_per_second);
+        let Some(ops) = item.as_object_mut() else { continue };
+        for (method, op) in ops.iter_mut() {
+            if !matches!(method.as_str(), "get" | "post" | "put" | "delete") { continue; }
+            let Some(responses) = op.get_mut("responses").and_then(|r| r.as_object_mut()) else { continue };
+            for resp in responses.values_mut() {
+                resp["headers"] = header_refs.clone();
+            }
+            let mut rejected_headers = header_refs.clone();
+            rejected_headers["Retry-After"] = json!({"$ref": "#/components/headers/Retry-After"});
+            responses.insert("429".to_string(), json!({
+                "description": format!("Too Many Requests: {}", policy.describe()),
+                "headers": rejected_headers,
+                "content": {"application/problem+json": {"schema": {"$ref": "#/components/schemas/Problem"}}}
+            }));
         }
+    }
+}
+
+fn quota_headers() -> Value {
+    let int = |desc: &str| json!({"description": desc, "schema": {"type": "integer"}});
+    json!({
+        "X-Quota-Limit": int("Request quota of the binding period"),
+        "X-Quota-Remaining": int("Remaining quota in the current period"),
+        "X-Quota-Reset": int("Seconds until the period resets"),
+        "X-Quota-Period": {"description": "Binding quota period (day or month)", "schema": {"type": "string"}},
+        "Retry-After": int("Seconds to wait before retrying")
+    })
+}
+
+// Route chịu quota: thêm header X-Quota-* và gộp trường hợp hết quota vào response 429
+fn document_quotas(settings: &admin::FeaturesSettings, paths: &mut serde_json::Map<String, Value>) {
+    let cfg = quota::QuotaConfig::from_extras(settings.feature_extras.get("quota"));
+    if cfg.limits.is_empty() && cfg.client_limits.is_empty() { return; }
+    let header_refs = ["X-Quota-Limit", "X-Quota-Remaining", "X-Quota-Reset", "X-Quota-Period"]
+        .map(|h| (h.to_string(), json!({"$ref": format!("#/components/headers/{}", h)})));
+    let limits: Vec<String> = cfg.limits.iter().map(|(p, n)| format!("{} per {}", n, p.as_str())).collect();
+    for (route, item) in paths.iter_mut() {
+        if !cfg.applies_to(route) { continue; }
+        let cost = cfg.cost(route);
+        let Some(ops) = item.as_object_mut() else { continue };
+        for (method, op) in ops.iter_mut() {
+            if !matches!(method.as_str(), "get" | "post" | "put" | "delete") { continue; }
+            let Some(responses) = op.get_mut("responses").and_then(|r| r.as_object_mut()) else { continue };
+            let rejected = responses.entry("429".to_string()).or_insert_with(|| json!({
+                "description": "Too Many Requests",
+                "content": {"application/problem+json": {"schema": {"$ref": "#/components/schemas/Problem"}}}
+            }));
+            let desc = rejected["description"].as_str().unwrap_or("Too Many Requests").to_string();
+            let quota_desc = if limits.is_empty() { "per-client quota".to_string() } else { limits.join(", ") };
+            rejected["description"] = json!(format!("{}; quota exhausted: {} (cost {} per request)", desc, quota_desc, cost));
+            rejected["headers"]["Retry-After"] = json!({"$ref": "#/components/headers/Retry-After"});
+            for resp in responses.values_mut() {
+                if !resp["headers"].is_object() { resp["headers"] = json!({}); }
+                for (name, r) in &header_refs {
+                    resp["headers"][name] = r.clone();
+                }
+            }
+        }
+    }
+}
+
+// Định nghĩa scheme oauth2 (client credentials) với toàn bộ scope khai báo trong route_rules
+fn oauth2_scheme(settings: &admin::FeaturesSettings) -> Value {
+    let extras = settings.feature_extras.get("oauth2");
+    let token_url = extras
+        .and_then(|v| v.get("token_url"))
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("/oauth2/token");
+    let mut scopes = serde_json::Map::new();
+    for rule in oauth2::parse_route_rules(extras).0.into_iter().filter(|r| !r.invalid) {
+        for sc in rule.scopes {
+            scopes.entry(sc).or_insert_with(|| Value::String(format!("Required by {}", rule.pattern)));
+        }
+    }
+    json!({
+        "type": "oauth2",
+        "flows": { "clientCredentials": { "tokenUrl": token_url, "scopes": scopes } }
     })
 }
 
@@ -119,20 +239,7 @@ pub fn build_openapi_from_modules(modules_dir: &str, build_dir: &str) -> Value {
                                     if let Value::Object(ref mut map) = entry {
                                         // gắn nhãn module theo tên thư mục
                                         map.insert("x-module".to_string(), Value::String(folder_name.clone()));
-                                        // security cho operation: chỉ thêm bearerAuth nếu route đã tick
-                                        let oauth2_protected_routes: Vec<String> = if let Some(obj) = settings.feature_extras.get("oauth2").and_then(|v| v.as_object()) {
-                                            obj.get("protected_routes")
-                                                .and_then(|v| v.as_array())
-                                                .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect())
-                                                .unwrap_or_default()
-                                        } else {
-                                            Vec::new()
-                                        };
-                                        let security_vec = if settings.oauth2_enabled && route_is_protected(&route, &oauth2_protected_routes) {
-                                            vec![json!({"apiKeyAuth": []}), json!({"bearerAuth": []})]
-                                        } else {
-                                            vec![json!({"apiKeyAuth": []})]
-                                        };
+                                        let security_vec = security_for(&settings, &route, method);
                                         // ví dụ request body theo route
                                         let example = if route == "/greet/user" {
                                             json!({"name":"John","age":25})
@@ -211,20 +318,6 @@ pub fn build_openapi_from_modules(modules_dir: &str, build_dir: &str) -> Value {
                                 })
                                 .unwrap_or_else(|| "application/json".to_string());
 
-                            // security cho operation: chỉ thêm bearerAuth nếu route đã tick
-                            let oauth2_protected_routes: Vec<String> = if let Some(obj) = settings.feature_extras.get("oauth2").and_then(|v| v.as_object()) {
-                                obj.get("protected_routes")
-                                    .and_then(|v| v.as_array())
-                                    .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect())
-                                    .unwrap_or_default()
-                            } else {
-                                Vec::new()
-                            };
-                            let security_vec = if settings.oauth2_enabled && route_is_protected(&route, &oauth2_protected_routes) {
-                                vec![json!({"apiKeyAuth": []}), json!({"bearerAuth": []})]
-                            } else {
-                                vec![json!({"apiKeyAuth": []})]
-                            };
                             // ví dụ request body theo route
                             let example = if route == "/greet/user" {
                                 json!({"name":"John","age":25})
@@ -241,7 +334,7 @@ pub fn build_openapi_from_modules(modules_dir: &str, build_dir: &str) -> Value {
                                     "parameters": [
                                         {"name":"X-Custom-Header","in":"header","schema":{"type":"string"},"required":false}
                                     ],
-                                    "security": security_vec,
+                                    "security": security_for(&settings, &route, "get"),
                                     "responses": {"200": {"description": "OK", "content": {content_type.clone(): {}}}}
                                 }));
                             }
@@ -251,7 +344,7 @@ pub fn build_openapi_from_modules(modules_dir: &str, build_dir: &str) -> Value {
                                     "parameters": [
                                         {"name":"X-Custom-Header","in":"header","schema":{"type":"string"},"required":false}
                                     ],
-                                    "security": security_vec,
+                                    "security": security_for(&settings, &route, "post"),
                                     "requestBody": {"content": rb_content},
                                     "responses": {"200": {"description": "OK", "content": {content_type.clone(): {}}}}
                                 }));
@@ -262,7 +355,7 @@ pub fn build_openapi_from_modules(modules_dir: &str, build_dir: &str) -> Value {
                                     "parameters": [
                                         {"name":"X-Custom-Header","in":"header","schema":{"type":"string"},"required":false}
                                     ],
-                                    "security": security_vec,
+                                    "security": security_for(&settings, &route, "put"),
                                     "requestBody": {"content": rb_content},
                                     "responses": {"200": {"description": "OK", "content": {content_type.clone(): {}}}}
                                 }));
@@ -273,7 +366,7 @@ pub fn build_openapi_from_modules(modules_dir: &str, build_dir: &str) -> Value {
                                     "parameters": [
                                         {"name":"X-Custom-Header","in":"header","schema":{"type":"string"},"required":false}
                                     ],
-                                    "security": security_vec,
+                                    "security": security_for(&settings, &route, "delete"),
                                     "responses": {"200": {"description": "OK", "content": {content_type.clone(): {}}}}
                                 }));
                             }
@@ -302,20 +395,38 @@ pub fn build_openapi_from_modules(modules_dir: &str, build_dir: &str) -> Value {
         "title": "WebApp FastAPI RS",
         "version": "1.0.0"
     }));
-    doc.insert("components".to_string(), json!({
-        "securitySchemes": {
-            "apiKeyAuth": {
-                "type": "apiKey",
-                "in": "header",
-                "name": "Authorization"
-            },
-            "bearerAuth": {
-                "type": "http",
-                "scheme": "bearer",
-                "bearerFormat": "JWT"
+    let mut components = json!({
+        "securitySchemes": security_schemes(&settings)
+    });
+    let quota_on = crate::quota::enabled(&settings);
+    let mut headers = serde_json::Map::new();
+    if settings.rate_limit_enabled {
+        document_rate_limits(&settings, &mut paths);
+        if let Value::Object(h) = rate_limit_headers() { headers.extend(h); }
+    }
+    if quota_on {
+        document_quotas(&settings, &mut paths);
+        if let Value::Object(h) = quota_headers() { headers.extend(h); }
+    }
+    if !headers.is_empty() {
+        components["headers"] = Value::Object(headers);
+    }
+    if settings.rate_limit_enabled || quota_on {
+        components["schemas"] = json!({
+            "Problem": {
+                "type": "object",
+                "properties": {
+                    "type": {"type": "string"},
+                    "title": {"type": "string"},
+                    "status": {"type": "integer"},
+                    "detail": {"type": "string"},
+                    "instance": {"type": "string"},
+                    "retry_after": {"type": "integer"}
+                }
             }
-        }
-    }));
+        });
+    }
+    doc.insert("components".to_string(), components);
     doc.insert("paths".to_string(), Value::Object(paths));
     Value::Object(doc)
 }
\ No newline at end of file
@@ -0,0 +1,86 @@
+// Feature quota: trừ quota theo ngày/tháng cho từng client và route Admin xem/đặt lại bộ đếm
+use axum::body::Body;
+use axum::extract::Path as AxumPath;
+use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::middleware::{from_fn_with_state, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use admin::auth::{require_role, Role};
+use quota::QuotaConfig;
+use serde_json::json;
+
+// Giống CORS: chỉ bật khi plugin có trong build và không bị disable
+pub fn enabled(s: &admin::FeaturesSettings) -> bool {
+    crate::features_loader::has_feature("./build", "quota") && !s.disabled_features.iter().any(|f| f == "quota")
+}
+
+fn config() -> std::sync::Arc<QuotaConfig> {
+    quota::config_for(admin::load_settings().feature_extras.get("quota"))
+}
+
+pub async fn guard(req: Request<Body>, next: Next) -> Response {
+    let settings = admin::load_settings();
+    let extras = settings.feature_extras.get("quota");
+    let cfg = quota::config_for(extras);
+    let path = req.uri().path().to_string();
+    if !cfg.applies_to(&path) { return next.run(req).await; }
+    let strategy = rate_limit::KeyStrategy::parse(&cfg.key).unwrap_or(rate_limit::KeyStrategy::ApiKey);
+    let client = crate::router::request_client_key(&req, &strategy);
+    let now = quota::now_secs();
+    let decision = match quota::consume(&client, &path, &cfg, now) {
+        Ok(Some(d)) => d,
+        Ok(None) => return next.run(req).await,
+        Err(e) => {
+            tracing::debug!("quota: max_clients ({}) reached, rejecting new client {}", cfg.max_clients, client);
+            let mut resp = (StatusCode::SERVICE_UNAVAILABLE, Json(e.problem(&path))).into_response();
+            resp.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+            return resp;
+        }
+    };
+    let mut resp = if decision.allowed {
+        next.run(req).await
+    } else {
+        let mut resp = (StatusCode::TOO_MANY_REQUESTS, Json(decision.problem(&path, now))).into_response();
+        resp.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+        if let Some(secs) = decision.retry_after_secs(now) {
+            resp.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        resp
+    };
+    for (name, value) in decision.headers(now) {
+        if let Ok(v) = HeaderValue::from_str(&value) {
+            resp.headers_mut().insert(name, v);
+        }
+    }
+    resp
+}
+
+// Route admin (nest dưới /admin)
+pub fn admin_routes() -> Router {
+    let admin_only = || from_fn_with_state(Role::Admin, require_role);
+    Router::new()
+        .route("/quota", get(|| async move {
+            let cfg = config();
+            let defaults: serde_json::Map<_, _> = cfg.limits.iter().map(|(p, n)| (p.as_str().to_string(), json!(n))).collect();
+            Json(json!({"limits": defaults, "clients": quota::report(&cfg, quota::now_secs())}))
+        }).route_layer(admin_only()))
+        .route("/quota/:client", get(|AxumPath(client): AxumPath<String>| async move {
+            let cfg = config();
+            Json(json!({"client": client, "periods": quota::status(&client, &cfg, quota::now_secs())}))
+        }).delete(|AxumPath(client): AxumPath<String>| async move {
+            if quota::reset(&client) {
+                let _ = quota::flush(&config(), quota::now_secs());
+                Json(json!({"ok": true})).into_response()
+            } else {
+                (StatusCode::NOT_FOUND, Json(json!({"ok": false, "error": format!("no quota usage for {}", client)}))).into_response()
+            }
+        }).route_layer(admin_only()))
+}
+
+// Ghi bộ đếm xuống file (gọi định kỳ)
+pub fn flush() {
+    if let Err(e) = quota::flush(&config(), quota::now_secs()) {
+        tracing::warn!("⚠️ quota: cannot write {}: {}", quota::USAGE_FILE, e);
+    }
+}
@@ -0,0 +1,71 @@
+// Feature request_signature: xác thực chữ ký HMAC (hmac / GitHub / Stripe) trên body thô của route webhook
+use axum::body::Body;
+use axum::http::{header, HeaderValue, Request, StatusCode};
+use axum::middleware::{from_fn_with_state, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use admin::auth::{require_role, Role};
+use request_signature::SignatureError;
+use serde_json::json;
+
+// Plugin có trong build và không bị disable
+pub fn enabled(s: &admin::FeaturesSettings) -> bool {
+    crate::features_loader::has_feature("./build", "request_signature") && !s.disabled_features.iter().any(|f| f == "request_signature")
+}
+
+// Problem details kèm mã lỗi ổn định ("code") để bên gửi webhook phân biệt lý do
+fn reject(path: &str, e: &SignatureError) -> Response {
+    let status = StatusCode::from_u16(e.status()).unwrap_or(StatusCode::UNAUTHORIZED);
+    let body = json!({
+        "type": "about:blank",
+        "title": status.canonical_reason().unwrap_or("Unauthorized"),
+        "status": status.as_u16(),
+        "detail": e.detail(),
+        "instance": path,
+        "code": e.code(),
+    });
+    let mut resp = (status, Json(body)).into_response();
+    resp.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+    resp
+}
+
+// Chỉ route có trong cấu hình mới phải ký; body được đọc hết để kiểm tra rồi trả lại nguyên vẹn cho handler
+pub async fn guard(req: Request<Body>, next: Next) -> Response {
+    let settings = admin::load_settings();
+    let Some(extras) = settings.feature_extras.get("request_signature") else { return next.run(req).await };
+    let cfg = request_signature::config_for(Some(extras));
+    let path = req.uri().path().to_string();
+    let Some(policy) = cfg.policy_for(&path) else { return next.run(req).await };
+
+    let (head, body) = req.into_parts();
+    let bytes = match axum::body::to_bytes(body, cfg.max_body_bytes).await {
+        Ok(b) => b,
+        Err(_) => return reject(&path, &SignatureError::BodyTooLarge),
+    };
+    let headers = &head.headers;
+    let lookup = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    if let Err(e) = policy.verify(lookup, &bytes, request_signature::now_secs(), cfg.replay_cache_size) {
+        tracing::info!("🔏 request_signature: rejected {} {} ({})", head.method, path, e.code());
+        return reject(&path, &e);
+    }
+    next.run(Request::from_parts(head, Body::from(bytes))).await
+}
+
+// Cảnh báo cấu hình sai khi dựng router (route không có secret dùng được từ chối mọi request)
+pub fn log_errors(s: &admin::FeaturesSettings) {
+    let Some(extras) = s.feature_extras.get("request_signature") else { return };
+    for e in &request_signature::config_for(Some(extras)).errors {
+        tracing::warn!("⚠️ request_signature: {}", e);
+    }
+}
+
+// Route admin (nest dưới /admin): route được bảo vệ (không kèm secret), lỗi cấu hình, số mục chống replay
+pub fn admin_routes() -> Router {
+    Router::new().route("/request-signature", get(|| async move {
+        let settings = admin::load_settings();
+        let cfg = request_signature::config_for(settings.feature_extras.get("request_signature"));
+        let now = request_signature::now_secs();
+        Json(json!({"config": cfg.as_ref(), "replay_cache_entries": request_signature::verify::replay_cache_len(now)}))
+    }).route_layer(from_fn_with_state(Role::Admin, require_role)))
+}
@@ -6,16 +6,11 @@ use axum::{
 };
 use axum::body::Body;
 use axum::middleware::{from_fn, Next};
-use axum::response::Response;
-use crate::{dynamic_loader::DynamicModules, types::{call_no_body_async, call_with_body_async}};
+use axum::response::{IntoResponse, Response};
+use crate::{dynamic_loader::DynamicModules, types::{call_no_body_async, call_with_body_async, call_with_ctx_async, RawCtxHandler}};
 use admin::load_settings;
 // loại bỏ phụ thuộc compile-time vào crates feature; dùng helper nội bộ dựa trên cấu hình
-use tower_http::cors::{CorsLayer, Any};
-use http::{HeaderValue, Method};
-use std::time::Duration;
-use std::time::Instant;
-use std::collections::HashMap;
-use std::sync::{Mutex, OnceLock};
+use http::HeaderValue;
 
 fn normalize_path(p: &str) -> String {
     let mut s = p.trim().to_string();
@@ -25,81 +20,107 @@ fn normalize_path(p: &str) -> String {
     s
 }
 
-fn route_matches(pattern: &str, path: &str) -> bool {
-    let path = normalize_path(path);
-    let patt = normalize_path(pattern);
-    if let Some(prefix) = patt.strip_suffix("/*") {
-        path.starts_with(&normalize_path(prefix))
-    } else {
-        patt == path
+// OAuth2 guard delegates decision to the oauth2 feature crate
+async fn oauth2_guard(mut req: Request<Body>, next: Next) -> Response {
+    let settings = load_settings();
+    let path = req.uri().path().to_string();
+    let method = req.method().as_str().to_string();
+    let extras = settings.feature_extras.get("oauth2");
+    let protected_routes: Vec<String> = extras
+        .and_then(|v| v.get("protected_routes"))
+        .and_then(|v| v.as_array())
+        .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+    let (rules, _) = oauth2::parse_route_rules(extras);
+    let protected = oauth2::route_is_protected(&protected_routes, &rules, &path, &method);
+    // Rule sai cấu hình cho route này: từ chối trước cả khi xét token / API key (lỗi đã log lúc dựng router)
+    if let Some(r) = oauth2::matching_rules(&rules, &path, &method).into_iter().find(|r| r.invalid) {
+        return oauth2_reject(&oauth2::AuthError::InvalidRule(r.pattern.clone()));
     }
-}
 
-fn route_requires_auth(path: &str, protected_routes: &[String]) -> bool {
-    protected_routes.iter().any(|p| route_matches(p, path))
-}
-
-fn has_bearer(auth: Option<&str>) -> bool {
-    match auth { Some(s) => s.trim().to_ascii_lowercase().starts_with("bearer "), None => false }
-}
-
-fn waf_is_malicious(uri: &str, ua: Option<&str>, patterns: &[String]) -> bool {
-    let ual = ua.unwrap_or("").to_ascii_lowercase();
-    let uril = uri.to_ascii_lowercase();
-    patterns.iter().any(|p| {
-        let q = p.to_ascii_lowercase();
-        uril.contains(&q) || (!ual.is_empty() && ual.contains(&q))
-    })
+    let auth = req.headers().get("authorization").and_then(|v| v.to_str().ok());
+    let token = oauth2::bearer_token(auth).map(|t| t.to_string());
+    let mode = oauth2::TokenValidation::for_route(&rules, &path, &method, oauth2::TokenValidation::from_extras(extras));
+    if !protected {
+        // Route công khai: token hợp lệ (nếu có) vẫn được gắn Principal cho plugin, token lỗi thì bỏ qua.
+        // Token opaque chỉ dùng kết quả introspection đã cache: không gọi authorization server cho route công khai
+        if let Some(token) = token {
+            let result = match mode.resolve(&token) {
+                oauth2::TokenValidation::Introspection => {
+                    let cfg = oauth2::JwtConfig::from_extras(extras);
+                    crate::introspection::cached(&token, &cfg).map(|r| r.map(|c| (c, "introspection")))
+                }
+                _ => Some(validate_token(&token, mode, extras).await),
+            };
+            if let Some(Ok((claims, via))) = result {
+                req.extensions_mut().insert(oauth2::principal_from_claims(&claims, &oauth2::forward_claims(extras), via));
+                req.extensions_mut().insert(oauth2::Claims(claims));
+            }
+        }
+        return next.run(req).await;
+    }
+    let Some(token) = token else {
+        // Không có bearer token nhưng đã xác thực bằng API key (chỉ khi accept_api_keys bật): kiểm tra scope của key theo route_rules
+        let api_key = req.extensions().get::<module_utils::Principal>().filter(|p| p.api_key_id.is_some());
+        if let Some(p) = api_key.filter(|_| oauth2::accept_api_keys(extras)) {
+            let claims = serde_json::json!({"scope": p.scopes.join(" ")});
+            if let Err(e) = oauth2::check_requirements(&claims, &oauth2::matching_rules(&rules, &path, &method)) {
+                return oauth2_reject(&e);
+            }
+            return next.run(req).await;
+        }
+        return oauth2_reject(&oauth2::AuthError::MissingToken);
+    };
+    match validate_token(&token, mode, extras).await {
+        Ok((claims, via)) => {
+            // Kiểm tra scope/claim theo route_rules áp dụng cho path + method
+            if let Err(e) = oauth2::check_requirements(&claims, &oauth2::matching_rules(&rules, &path, &method)) {
+                return oauth2_reject(&e);
+            }
+            let principal = oauth2::principal_from_claims(&claims, &oauth2::forward_claims(extras), via);
+            req.extensions_mut().insert(principal);
+            req.extensions_mut().insert(oauth2::Claims(claims));
+            next.run(req).await
+        }
+        Err(oauth2::AuthError::Misconfigured(m)) => {
+            tracing::warn!("⚠️ oauth2 chưa cấu hình đúng: {}", m);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        // Lỗi phía authorization server, không phải do token của client
+        Err(oauth2::AuthError::Unavailable(m)) => {
+            tracing::warn!("⚠️ oauth2 không xác thực được token: {}", m);
+            StatusCode::SERVICE_UNAVAILABLE.into_response()
+        }
+        Err(e) => oauth2_reject(&e),
+    }
 }
 
-static RATE_LIMITERS: OnceLock<Mutex<HashMap<(String, String), (usize, Instant)>>> = OnceLock::new();
-fn rate_limit_check_allow(ip: &str, path: &str, limit: usize) -> bool {
-    let mtx = RATE_LIMITERS.get_or_init(|| Mutex::new(HashMap::new()));
-    let mut map = mtx.lock().unwrap();
-    let key = (ip.to_string(), normalize_path(path));
-    let now = Instant::now();
-    let entry = map.entry(key).or_insert((0, now));
-    if now.duration_since(entry.1).as_secs() >= 1 {
-        entry.0 = 0;
-        entry.1 = now;
+// Xác thực token theo chế độ của route; trả về claims và cách đã xác thực ("jwt" | "introspection")
+async fn validate_token(token: &str, mode: oauth2::TokenValidation, extras: Option<&serde_json::Value>) -> Result<(serde_json::Value, &'static str), oauth2::AuthError> {
+    let cfg = oauth2::JwtConfig::from_extras(extras);
+    match mode.resolve(token) {
+        oauth2::TokenValidation::Introspection => {
+            let icfg = oauth2::IntrospectionConfig::from_extras(extras);
+            crate::introspection::verify(token, &icfg, &cfg).await.map(|c| (c, "introspection"))
+        }
+        _ => crate::jwks::verify(token, &cfg).await.map(|c| (c, "jwt")),
     }
-    entry.0 += 1;
-    entry.0 <= limit
 }
 
-// WAF guard delegates decision to the waf feature crate
-async fn waf_guard(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
-    let settings = load_settings();
-    let uri = req.uri().to_string();
-    let ua = req.headers().get("user-agent").and_then(|v| v.to_str().ok());
-    let patterns: Vec<String> = settings
-        .feature_extras
-        .get("waf")
-        .and_then(|v| v.as_object())
-        .and_then(|o| o.get("patterns"))
-        .and_then(|v| v.as_array())
-        .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect())
-        .unwrap_or_default();
-    if waf_is_malicious(&uri, ua, &patterns) { return Err(StatusCode::FORBIDDEN); }
-    Ok(next.run(req).await)
+fn oauth2_reject(e: &oauth2::AuthError) -> Response {
+    let status = if e.is_forbidden() { StatusCode::FORBIDDEN } else { StatusCode::UNAUTHORIZED };
+    let mut resp = status.into_response();
+    if let Ok(v) = HeaderValue::from_str(&e.www_authenticate()) {
+        resp.headers_mut().insert(http::header::WWW_AUTHENTICATE, v);
+    }
+    resp
 }
 
-// OAuth2 guard delegates decision to the oauth2 feature crate
-async fn oauth2_guard(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
-    let settings = load_settings();
-    let path = req.uri().path().to_string();
-    let protected_routes: Vec<String> = if let Some(obj) = settings.feature_extras.get("oauth2").and_then(|v| v.as_object()) {
-        obj.get("protected_routes")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect())
-            .unwrap_or_default()
-    } else {
-        Vec::new()
-    };
-    if !route_requires_auth(&path, &protected_routes) { return Ok(next.run(req).await); }
-    let auth = req.headers().get("authorization").and_then(|v| v.to_str().ok());
-    if !has_bearer(auth) { return Err(StatusCode::UNAUTHORIZED); }
-    Ok(next.run(req).await)
+async fn ctx_handler(h: RawCtxHandler, req: Request<Body>) -> Response {
+    match crate::context::split(req).await {
+        Ok((ctx, body)) => call_with_ctx_async(h, ctx, body).await,
+        Err(resp) => resp,
+    }
 }
 
 pub fn build_router_from(base: &str) -> Router {
@@ -107,7 +128,6 @@ pub fn build_router_from(base: &str) -> Router {
     let mut r = Router::new();
 
     for (path, m) in mods.routes {
-        // Xây sub-router cho từng path để có thể áp lớp CORS riêng
         let mut sub = Router::new();
         if let Some(g) = m.get {
             sub = sub.route(&path, get(move || async move { call_no_body_async(g).await }));
@@ -125,131 +145,129 @@ pub fn build_router_from(base: &str) -> Router {
         if let Some(d) = m.delete {
             sub = sub.route(&path, delete(move || async move { call_no_body_async(d).await }));
         }
+        // Handler ABI ctx: nhận thêm RequestContext (kèm Principal nếu đã xác thực)
+        if let Some(h) = m.get_ctx { sub = sub.route(&path, get(move |req: Request<Body>| ctx_handler(h, req))); }
+        if let Some(h) = m.post_ctx { sub = sub.route(&path, post(move |req: Request<Body>| ctx_handler(h, req))); }
+        if let Some(h) = m.put_ctx { sub = sub.route(&path, put(move |req: Request<Body>| ctx_handler(h, req))); }
+        if let Some(h) = m.delete_ctx { sub = sub.route(&path, delete(move |req: Request<Body>| ctx_handler(h, req))); }
 
-        // Áp dụng CORS theo route nếu bật cors_enabled và có cấu hình
-        let s_for_cors = load_settings();
-        // Apply CORS only if plugin exists AND not disabled in settings
-        if crate::features_loader::has_feature("./build", "cors") && !s_for_cors.disabled_features.contains(&"cors".to_string()) {
-            if let Some(layer) = route_cors_layer(&path, &s_for_cors) {
-                sub = sub.layer(layer);
-            }
-        }
         r = r.merge(sub);
     }
 
     // Áp dụng middleware theo FeaturesSettings (logic nằm trong crates ở thư mục features)
     let s = load_settings();
+    crate::client_ip::log_errors(&s);
     if s.waf_enabled {
-        r = r.layer(from_fn(waf_guard));
+        r = r.layer(from_fn(crate::waf::guard));
     }
-    if s.oauth2_enabled { r = r.layer(from_fn(oauth2_guard)); }
+    // Quota chạy sau rate limit: request bị rate limit chặn không bị trừ quota
+    if crate::quota::enabled(&s) { r = r.layer(from_fn(crate::quota::guard)); }
+    // Rate limit chạy sau các guard xác thực để có thể đếm theo API key / user
     if s.rate_limit_enabled {
         r = r.layer(from_fn(rate_limit_guard));
     }
+    if s.oauth2_enabled {
+        for e in oauth2::parse_route_rules(s.feature_extras.get("oauth2")).1 {
+            tracing::warn!("⚠️ oauth2: {}", e);
+        }
+        r = r.layer(from_fn(oauth2_guard));
+    }
+    // Chạy trước oauth2 để API key hợp lệ cũng đáp ứng được route được OAuth2 bảo vệ
+    if crate::api_keys::enabled(&s) { r = r.layer(from_fn(crate::api_keys::guard)); }
+    // Webhook xác thực bằng chữ ký HMAC; chạy trong ip_filter để chữ ký sai (401) cũng bị tính vào tự động ban
+    if crate::request_signature::enabled(&s) {
+        crate::request_signature::log_errors(&s);
+        r = r.layer(from_fn(crate::request_signature::guard));
+    }
+    // ip_filter là layer toàn cục trong main.rs (bao cả /admin và /oauth2/token); ở đây chỉ bật/tắt theo cấu hình mới
+    crate::ip_filter::set_active(crate::ip_filter::enabled(&s));
+    // CORS bọc ngoài tất cả: preflight được trả lời trước mọi guard, response lỗi (401/403/429) vẫn có header CORS
+    if crate::cors::enabled(&s) {
+        crate::cors::log_errors(&s);
+        r = r.layer(from_fn(crate::cors::guard));
+    }
+    // Bọc cả CORS: preflight và response lỗi của mọi guard cũng có security header
+    if crate::security_headers::enabled(&s) {
+        crate::security_headers::log_errors(&s);
+        r = r.layer(from_fn(crate::security_headers::guard));
+    }
 
     r
 }
 
-async fn rate_limit_guard(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
-    // Lấy IP client (best-effort)
-    let ip = req.headers().get("x-forwarded-for").and_then(|v| v.to_str().ok()).unwrap_or("local");
-    let path = req.uri().path().to_string();
-    // Lấy cấu hình limit theo route nếu có, fallback về global
-    let settings = load_settings();
-    let path_norm = normalize_path(&path);
-    // Đọc per-route từ feature_extras nếu có, fallback sang trường cũ route_rate_limits
-    let per_route_limit = if let Some(obj) = settings.feature_extras.get("rate_limit").and_then(|v| v.as_object()) {
-        obj.get("route_limits")
-            .and_then(|v| v.as_object())
-            .and_then(|m| m.get(&path_norm))
-            .and_then(|v| v.as_u64())
-            .map(|n| n as u32)
-            .or_else(|| settings.route_rate_limits.get(&path_norm).copied())
-    } else {
-        settings.route_rate_limits.get(&path_norm).copied()
-    };
-    let global_default = if let Some(obj) = settings.feature_extras.get("rate_limit").and_then(|v| v.as_object()) {
-        obj.get("rps").and_then(|v| v.as_u64()).map(|n| n as u32).unwrap_or(settings.rate_limit_per_second)
-    } else {
-        settings.rate_limit_per_second
+// Định danh client theo chiến lược (ip, api_key, user, header...); Principal (nếu có) do api_key/oauth2 guard gắn trước đó,
+// IP lấy từ ClientIp do client_ip::resolve gắn
+pub(crate) fn request_client_key(req: &Request<Body>, strategy: &rate_limit::KeyStrategy) -> String {
+    let principal = req.extensions().get::<module_utils::Principal>();
+    let headers = req.headers();
+    let input = rate_limit::KeyInput {
+        client_ip: req.extensions().get::<module_utils::ClientIp>().map(|c| c.ip),
+        api_key_id: principal.and_then(|p| p.api_key_id.as_deref()),
+        subject: principal.and_then(|p| p.subject.as_deref()),
     };
-    let limit = per_route_limit.unwrap_or(global_default) as usize;
-    if !rate_limit_check_allow(ip, &path_norm, limit) { return Err(StatusCode::TOO_MANY_REQUESTS); }
-    Ok(next.run(req).await)
+    rate_limit::client_key(strategy, &input, |name| headers.get(name).and_then(|v| v.to_str().ok()))
 }
 
-fn route_cors_layer(path: &str, s: &admin::FeaturesSettings) -> Option<CorsLayer> {
-    let cors_obj = s.feature_extras.get("cors")?.as_object()?;
-    let path_norm = normalize_path(path);
-
-    // Nếu có danh sách bật theo route, chỉ áp dụng khi path nằm trong danh sách
-    if let Some(enabled) = cors_obj.get("enabled_routes").and_then(|v| v.as_array()) {
-        let enabled_list: Vec<String> = enabled.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect();
-        let is_enabled = enabled_list.iter().any(|item| {
-            if let Some(prefix) = item.strip_suffix("/*") {
-                path_norm.starts_with(&normalize_path(prefix))
-            } else {
-                normalize_path(item) == path_norm
+async fn rate_limit_guard(req: Request<Body>, next: Next) -> Response {
+    let path_norm = normalize_path(req.uri().path());
+    // Chính sách theo route (route_policies / route_limits) hoặc chung; fallback các trường cũ trong settings
+    let settings = load_settings();
+    let extras = settings.feature_extras.get("rate_limit");
+    rate_limit::set_max_entries(rate_limit::max_entries_from_extras(extras));
+    let policy = rate_limit::resolve_policy(
+        extras,
+        &path_norm,
+        settings.route_rate_limits.get(&path_norm).copied(),
+        settings.rate_limit_per_second,
+    );
+    let client = request_client_key(&req, &policy.key);
+    let decision = match rate_limit_check(extras, format!("{}|{}", client, path_norm), &policy).await {
+        Ok(d) => d,
+        Err(e) => {
+            match &e {
+                rate_limit::BackendError::Unavailable(_) => tracing::debug!("rate limit backend: {}", e),
+                _ => tracing::warn!("⚠️ rate limit backend: {}", e),
             }
-        });
-        if !is_enabled { return None; }
-    }
-
-    let mut layer = CorsLayer::new();
-
-    // origins
-    if let Some(origins) = cors_obj.get("origins").and_then(|v| v.as_array()) {
-        if origins.iter().any(|o| o.as_str() == Some("*")) {
-            layer = layer.allow_origin(Any);
-        } else {
-            let list: Vec<HeaderValue> = origins
-                .iter()
-                .filter_map(|o| o.as_str())
-                .filter_map(|s| s.parse::<HeaderValue>().ok())
-                .collect();
-            if !list.is_empty() { layer = layer.allow_origin(list); }
+            // fail-open: cho qua không kèm header RateLimit-*; fail-closed: 503
+            if rate_limit::FailureMode::from_extras(extras) == rate_limit::FailureMode::Open {
+                return next.run(req).await;
+            }
+            let mut resp = (StatusCode::SERVICE_UNAVAILABLE, axum::Json(rate_limit::Decision::unavailable_problem(&path_norm))).into_response();
+            resp.headers_mut().insert(http::header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+            resp.headers_mut().insert(http::header::RETRY_AFTER, HeaderValue::from_static("1"));
+            return resp;
         }
-    }
-    // methods
-    if let Some(methods) = cors_obj.get("methods").and_then(|v| v.as_array()) {
-        let list: Vec<Method> = methods
-            .iter()
-            .filter_map(|m| m.as_str())
-            .filter_map(|s| s.parse::<Method>().ok())
-            .collect();
-        if !list.is_empty() { layer = layer.allow_methods(list); }
-    }
-    // headers
-    if let Some(headers) = cors_obj.get("headers").and_then(|v| v.as_array()) {
-        if headers.iter().any(|h| h.as_str() == Some("*")) {
-            layer = layer.allow_headers(Any);
-        } else {
-            let list: Vec<http::HeaderName> = headers
-                .iter()
-                .filter_map(|h| h.as_str())
-                .filter_map(|s| s.parse::<http::HeaderName>().ok())
-                .collect();
-            if !list.is_empty() { layer = layer.allow_headers(list); }
+    };
+    let mut resp = if decision.allowed {
+        next.run(req).await
+    } else {
+        let mut resp = (StatusCode::TOO_MANY_REQUESTS, axum::Json(decision.problem(&policy, &path_norm))).into_response();
+        resp.headers_mut().insert(http::header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+        if let Some(secs) = decision.retry_after_secs() {
+            resp.headers_mut().insert(http::header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        resp
+    };
+    for (name, value) in decision.headers(&policy) {
+        if let Ok(v) = HeaderValue::from_str(&value) {
+            resp.headers_mut().insert(name, v);
         }
     }
-    // expose_headers
-    if let Some(headers) = cors_obj.get("expose_headers").and_then(|v| v.as_array()) {
-        let list: Vec<http::HeaderName> = headers
-            .iter()
-            .filter_map(|h| h.as_str())
-            .filter_map(|s| s.parse::<http::HeaderName>().ok())
-            .collect();
-        if !list.is_empty() { layer = layer.expose_headers(list); }
-    }
-    // allow_credentials (nhận bool hoặc 0/1)
-    if let Some(cred_val) = cors_obj.get("allow_credentials") {
-        let cred = cred_val.as_bool().unwrap_or_else(|| cred_val.as_u64().map(|n| n != 0).unwrap_or(false));
-        if cred { layer = layer.allow_credentials(true); }
-    }
-    // max_age
-    if let Some(age) = cors_obj.get("max_age").and_then(|v| v.as_u64()) {
-        layer = layer.max_age(Duration::from_secs(age));
-    }
+    resp
+}
 
-    Some(layer)
+// Kiểm tra trên backend đã cấu hình; backend qua mạng (redis) chạy trong spawn_blocking để không chặn runtime
+async fn rate_limit_check(
+    extras: Option<&serde_json::Value>,
+    key: String,
+    policy: &rate_limit::Policy,
+) -> Result<rate_limit::Decision, rate_limit::BackendError> {
+    let backend = rate_limit::backend::backend_for(&rate_limit::BackendConfig::from_extras(extras)?);
+    if !backend.is_remote() {
+        return rate_limit::check_with(backend.as_ref(), &key, policy);
+    }
+    let policy = policy.clone();
+    tokio::task::spawn_blocking(move || rate_limit::check_with(backend.as_ref(), &key, &policy))
+        .await
+        .unwrap_or_else(|e| Err(rate_limit::BackendError::Failed(e.to_string())))
 }
@@ -0,0 +1,51 @@
+// Feature security_headers: đặt CSP/HSTS/X-Frame-Options/... cho response của route plugin,
+// sinh nonce CSP cho mỗi request và chuyển cho handler qua RequestContext.csp_nonce
+use axum::body::Body;
+use axum::http::{HeaderName, HeaderValue, Request};
+use axum::middleware::{from_fn_with_state, Next};
+use axum::response::Response;
+use axum::routing::get;
+use axum::{Json, Router};
+use admin::auth::{require_role, Role};
+use module_utils::CspNonce;
+
+// Plugin có trong build và không bị disable
+pub fn enabled(s: &admin::FeaturesSettings) -> bool {
+    crate::features_loader::has_feature("./build", "security_headers") && !s.disabled_features.iter().any(|f| f == "security_headers")
+}
+
+// Không có feature_extras.security_headers vẫn áp giá trị mặc định của manifest
+pub async fn guard(mut req: Request<Body>, next: Next) -> Response {
+    let settings = admin::load_settings();
+    let cfg = security_headers::config_for(settings.feature_extras.get("security_headers"));
+    let Some(policy) = cfg.policy_for(req.uri().path()) else { return next.run(req).await };
+    // Chỉ sinh nonce khi CSP có "{nonce}"; handler đọc lại qua RequestContext.csp_nonce
+    let nonce = policy.uses_nonce().then(security_headers::new_nonce);
+    if let Some(n) = &nonce {
+        req.extensions_mut().insert(CspNonce(n.clone()));
+    }
+    let headers = policy.headers(nonce.as_deref());
+    let mut resp = next.run(req).await;
+    for (name, value) in headers {
+        let Ok(v) = HeaderValue::from_str(&value) else { continue };
+        let name = HeaderName::from_static(name);
+        // Header do handler / guard bên trong tự đặt được giữ nguyên
+        if !resp.headers().contains_key(&name) { resp.headers_mut().insert(name, v); }
+    }
+    resp
+}
+
+// Cảnh báo cấu hình sai khi dựng router (header không hợp lệ bị bỏ, các header khác vẫn được gửi)
+pub fn log_errors(s: &admin::FeaturesSettings) {
+    for e in &security_headers::config_for(s.feature_extras.get("security_headers")).errors {
+        tracing::warn!("⚠️ security_headers: {}", e);
+    }
+}
+
+// Route admin (nest dưới /admin): chính sách đã phân giải và lỗi cấu hình
+pub fn admin_routes() -> Router {
+    Router::new().route("/security-headers", get(|| async move {
+        let settings = admin::load_settings();
+        Json(security_headers::config_for(settings.feature_extras.get("security_headers")).as_ref().clone())
+    }).route_layer(from_fn_with_state(Role::Admin, require_role)))
+}
@@ -6,6 +6,8 @@ use serde_json::Value;
 
 pub type RawHandler = unsafe extern "C" fn() -> *mut c_char;
 pub type RawHandlerWithBody = unsafe extern "C" fn(*const c_uchar, usize) -> *mut c_char;
+// ABI ctx: (ctx_json_ptr, ctx_len, body_ptr, body_len) -> chuỗi kết quả
+pub type RawCtxHandler = unsafe extern "C" fn(*const c_uchar, usize, *const c_uchar, usize) -> *mut c_char;
 pub type RawRoutePath = unsafe extern "C" fn() -> *mut c_char;
 // Alias chung cho các symbol trả về chuỗi (vd: content_type)
 pub type RawStr = RawRoutePath;
@@ -16,6 +18,18 @@ pub struct MethodSet {
     pub post: Option<RawHandlerWithBody>,
     pub put: Option<RawHandlerWithBody>,
     pub delete: Option<RawHandler>,
+    // Handler nhận RequestContext (Principal...) theo ABI ctx
+    pub get_ctx: Option<RawCtxHandler>,
+    pub post_ctx: Option<RawCtxHandler>,
+    pub put_ctx: Option<RawCtxHandler>,
+    pub delete_ctx: Option<RawCtxHandler>,
+}
+
+impl MethodSet {
+    pub fn is_empty(&self) -> bool {
+        self.get.is_none() && self.post.is_none() && self.put.is_none() && self.delete.is_none()
+            && self.get_ctx.is_none() && self.post_ctx.is_none() && self.put_ctx.is_none() && self.delete_ctx.is_none()
+    }
 }
 
 pub unsafe fn call_no_body(h: RawHandler) -> Response {
@@ -32,6 +46,13 @@ pub unsafe fn call_with_body(h: RawHandlerWithBody, body: &[u8]) -> Response {
     to_response(text)
 }
 
+pub unsafe fn call_with_ctx(h: RawCtxHandler, ctx: &[u8], body: &[u8]) -> Response {
+    let ptr = h(ctx.as_ptr(), ctx.len(), body.as_ptr(), body.len());
+    let s = std::ffi::CString::from_raw(ptr);
+    let text = s.to_string_lossy().into_owned();
+    to_response(text)
+}
+
 // Async-friendly wrappers: execute plugin handlers in a blocking task to avoid
 // blocking the async runtime when plugins perform I/O or heavy CPU.
 pub async fn call_no_body_async(h: RawHandler) -> Response {
@@ -50,6 +71,15 @@ pub async fn call_with_body_async(h: RawHandlerWithBody, body: Bytes) -> Respons
             .body("Plugin panicked".into()).unwrap())
 }
 
+pub async fn call_with_ctx_async(h: RawCtxHandler, ctx: module_utils::RequestContext, body: Bytes) -> Response {
+    let ctx_json = serde_json::to_vec(&ctx).unwrap_or_default();
+    tokio::task::spawn_blocking(move || unsafe { call_with_ctx(h, &ctx_json, &body) })
+        .await
+        .unwrap_or_else(|_| Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body("Plugin panicked".into()).unwrap())
+}
+
 fn to_response(text: String) -> Response {
     // Unified error channel: "error:<code>:<body>"
     if let Some(rest) = text.strip_prefix("error:") {
@@ -90,20 +120,20 @@ fn to_response_with_status(text: String, status: StatusCode) -> Response {
     if let Some(rest) = text.strip_prefix("html:") {
         let mut resp = Html(rest.to_string()).into_response();
         *resp.status_mut() = status;
-        return resp;
+        resp
     } else if let Some(rest) = text.strip_prefix("text:") {
-        return Response::builder().status(status).header("Content-Type", "text/plain; charset=utf-8").body(rest.to_string().into()).unwrap();
+        Response::builder().status(status).header("Content-Type", "text/plain; charset=utf-8").body(rest.to_string().into()).unwrap()
     } else if let Some(rest) = text.strip_prefix("js:") {
-        return Response::builder().status(status).header("Content-Type", "application/javascript; charset=utf-8").body(rest.to_string().into()).unwrap();
+        Response::builder().status(status).header("Content-Type", "application/javascript; charset=utf-8").body(rest.to_string().into()).unwrap()
     } else if let Some(rest) = text.strip_prefix("css:") {
-        return Response::builder().status(status).header("Content-Type", "text/css; charset=utf-8").body(rest.to_string().into()).unwrap();
+        Response::builder().status(status).header("Content-Type", "text/css; charset=utf-8").body(rest.to_string().into()).unwrap()
     } else if let Some(rest) = text.strip_prefix("xml:") {
-        return Response::builder().status(status).header("Content-Type", "application/xml; charset=utf-8").body(rest.to_string().into()).unwrap();
+        Response::builder().status(status).header("Content-Type", "application/xml; charset=utf-8").body(rest.to_string().into()).unwrap()
     } else if let Some(rest) = text.strip_prefix("json:") {
         let v: Value = serde_json::from_str(rest).unwrap_or(Value::Null);
         let mut resp = Json(v).into_response();
         *resp.status_mut() = status;
-        return resp;
+        resp
     } else {
         // Backward-compatible: auto-detect JSON by first char, else HTML
         let trimmed = text.trim_start();
@@ -111,11 +141,11 @@ fn to_response_with_status(text: String, status: StatusCode) -> Response {
             let v: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
             let mut resp = Json(v).into_response();
             *resp.status_mut() = status;
-            return resp;
+            resp
         } else {
             let mut resp = Html(text).into_response();
             *resp.status_mut() = status;
-            return resp;
+            resp
         }
     }
 }
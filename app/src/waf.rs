@@ -0,0 +1,250 @@
+// Feature waf: guard chạy rule engine của crate waf, route Admin xem/thử ruleset và nhật ký chặn
+use axum::body::{Body, Bytes};
+use axum::extract::{Path as AxumPath, Query};
+use axum::http::{HeaderValue, Request, StatusCode};
+use axum::middleware::{from_fn_with_state, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use admin::auth::{require_role, Role};
+use serde::Deserialize;
+use serde_json::json;
+use waf::events::EventQuery;
+use waf::{Limits, RequestParts, RequestSummary, Violation};
+
+fn parts_of(req: &Request<Body>) -> RequestParts {
+    RequestParts {
+        method: req.method().as_str().to_string(),
+        path: req.uri().path().to_string(),
+        query: req.uri().query().map(|q| q.to_string()),
+        headers: req
+            .headers()
+            .iter()
+            .map(|(n, v)| (n.as_str().to_string(), String::from_utf8_lossy(v.as_bytes()).into_owned()))
+            .collect(),
+        body: None,
+        body_args: Vec::new(),
+    }
+}
+
+fn summary_of(req: &Request<Body>) -> RequestSummary {
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok()).map(|s| s.to_string());
+    RequestSummary {
+        method: req.method().as_str().to_string(),
+        path: req.uri().path().to_string(),
+        query: req.uri().query().map(|q| q.to_string()),
+        client_ip: req.extensions().get::<module_utils::ClientIp>().map(|c| c.ip.to_string()),
+        user_agent: header("user-agent"),
+        request_id: header("x-request-id"),
+    }
+}
+
+// Đánh dấu response bị WAF chặn (ip_filter đếm để tự động ban)
+#[derive(Debug, Clone, Copy)]
+pub struct Blocked;
+
+fn problem(status: StatusCode, title: &str, detail: &str, instance: &str, correlation_id: &str) -> Response {
+    let body = json!({
+        "type": "about:blank",
+        "title": title,
+        "status": status.as_u16(),
+        "detail": detail,
+        "instance": instance,
+        "correlation_id": correlation_id,
+    });
+    let mut resp = (status, Json(body)).into_response();
+    resp.headers_mut().insert(http::header::CONTENT_TYPE, HeaderValue::from_static("application/problem+json"));
+    if let Ok(v) = HeaderValue::from_str(correlation_id) {
+        resp.headers_mut().insert("x-correlation-id", v);
+    }
+    resp.extensions_mut().insert(Blocked);
+    resp
+}
+
+// Vi phạm giới hạn kích thước: luôn chặn, ghi vào nhật ký như một rule protocol
+fn reject(summary: &RequestSummary, violation: &Violation, threshold: u32) -> Response {
+    let correlation_id = waf::events::new_correlation_id();
+    let _ = waf::events::record(summary, &violation.verdict(threshold), correlation_id.clone());
+    tracing::warn!(
+        "🛡️ WAF rejected {} {} [{}] (rule {}: {})",
+        summary.method, summary.path, correlation_id, violation.rule_id, violation.detail
+    );
+    let status = StatusCode::from_u16(violation.status).unwrap_or(StatusCode::BAD_REQUEST);
+    problem(status, violation.title, &violation.detail, &summary.path, &correlation_id)
+}
+
+// Chuẩn bị body dùng chung cho guard và /waf/test: giới hạn kích thước, tách tham số theo Content-Type,
+// gắn body vào RequestParts khi inspect_body bật
+fn prepare_body(limits: &Limits, parts: &mut RequestParts, bytes: &[u8]) -> Result<(), Violation> {
+    if bytes.len() > limits.max_body_bytes { return Err(Violation::body_too_large(limits.max_body_bytes)); }
+    if !limits.inspect_body { return Ok(()); }
+    let content_type = parts.headers.iter().find(|(n, _)| n == "content-type").map(|(_, v)| v.as_str());
+    parts.body_args = waf::limits::parse_body(limits, waf::limits::body_kind(content_type), bytes)?;
+    if !bytes.is_empty() { parts.body = Some(String::from_utf8_lossy(bytes).into_owned()); }
+    Ok(())
+}
+
+pub async fn guard(req: Request<Body>, next: Next) -> Response {
+    let settings = admin::load_settings();
+    let extras = settings.feature_extras.get("waf");
+    let engine = waf::engine_for(extras);
+    let limits = Limits::from_extras(extras);
+    waf::events::set_capacity(waf::events::capacity_from_extras(extras));
+    // Chỉ giữ dữ liệu sở hữu qua các điểm await (Body không Sync nên &Request không Send)
+    let summary = summary_of(&req);
+    let mut parts = parts_of(&req);
+    if let Err(v) = waf::limits::check_headers(&limits, &parts.headers) {
+        return reject(&summary, &v, engine.threshold);
+    }
+
+    // Giới hạn body được áp trước khi body tới handler (call_with_body_async)
+    let content_length = req
+        .headers()
+        .get(http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if content_length.is_some_and(|n| n > limits.max_body_bytes) {
+        return reject(&summary, &Violation::body_too_large(limits.max_body_bytes), engine.threshold);
+    }
+    // Không tin vào Transfer-Encoding (HTTP/2 không có header này): thiếu Content-Length thì vẫn đọc body
+    let has_body = content_length != Some(0);
+    // Body luôn được đọc qua to_bytes để max_body_bytes áp cả cho body chunked (kể cả khi inspect_body tắt)
+    let req = if has_body {
+        let (head, body) = req.into_parts();
+        // Body chunked không có Content-Length: to_bytes dừng khi vượt giới hạn
+        let bytes = match axum::body::to_bytes(body, limits.max_body_bytes).await {
+            Ok(b) => b,
+            Err(_) => return reject(&summary, &Violation::body_too_large(limits.max_body_bytes), engine.threshold),
+        };
+        if let Err(v) = prepare_body(&limits, &mut parts, &bytes) {
+            return reject(&summary, &v, engine.threshold);
+        }
+        Request::from_parts(head, Body::from(bytes))
+    } else {
+        req
+    };
+
+    let verdict = engine.inspect(&parts);
+    if verdict.matches.is_empty() { return next.run(req).await; }
+
+    let correlation_id = waf::events::new_correlation_id();
+    let _ = waf::events::record(&summary, &verdict, correlation_id.clone());
+    if verdict.blocked {
+        tracing::warn!(
+            "🛡️ WAF blocked {} {} [{}] (score {}/{}, rules {:?})",
+            summary.method, summary.path, correlation_id, verdict.score, verdict.threshold, verdict.rule_ids()
+        );
+        return problem(StatusCode::FORBIDDEN, "Forbidden", "Request blocked by web application firewall", &summary.path, &correlation_id);
+    }
+    tracing::info!(
+        "🛡️ WAF detected {} {} [{}] (score {}/{}{}, rules {:?})",
+        summary.method,
+        summary.path,
+        correlation_id,
+        verdict.score,
+        verdict.threshold,
+        if verdict.would_block { ", would block" } else { "" },
+        verdict.rule_ids()
+    );
+    next.run(req).await
+}
+
+// Request mẫu để thử ruleset từ Admin
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TestRequest {
+    method: Option<String>,
+    // Có thể kèm query: "/greet/hi?name=x"
+    uri: String,
+    headers: std::collections::HashMap<String, String>,
+    body: Option<String>,
+}
+
+// Route admin (nest dưới /admin)
+pub fn admin_routes() -> Router {
+    let admin_only = || from_fn_with_state(Role::Admin, require_role);
+    Router::new()
+        .route("/waf/rules", get(|| async move {
+            let settings = admin::load_settings();
+            Json(waf::engine_for(settings.feature_extras.get("waf")).describe())
+        }).route_layer(admin_only()))
+        // Nhật ký chặn/khớp gần đây (mới nhất trước) + thống kê theo rule
+        .route("/waf/events", get(|Query(q): Query<EventQuery>| async move {
+            Json(json!({"events": waf::events::query(&q), "stats": waf::events::stats()}))
+        }).delete(|| async move {
+            waf::events::clear();
+            Json(json!({"ok": true}))
+        }).route_layer(admin_only()))
+        .route("/waf/events/:correlation_id", get(|AxumPath(id): AxumPath<String>| async move {
+            match waf::events::find(&id) {
+                Some(e) => Json(json!(e)).into_response(),
+                None => (StatusCode::NOT_FOUND, Json(json!({"ok": false, "error": format!("no WAF event {}", id)}))).into_response(),
+            }
+        }).route_layer(admin_only()))
+        .route("/waf/test", post(|body: Bytes| async move {
+            let t: TestRequest = match serde_json::from_slice(&body) {
+                Ok(t) => t,
+                Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"ok": false, "error": e.to_string()}))).into_response(),
+            };
+            let (path, query) = match t.uri.split_once('?') {
+                Some((p, q)) => (p.to_string(), Some(q.to_string())),
+                None => (t.uri.clone(), None),
+            };
+            let mut req = RequestParts {
+                method: t.method.unwrap_or_else(|| "GET".to_string()).to_ascii_uppercase(),
+                path,
+                query,
+                headers: t.headers.into_iter().map(|(k, v)| (k.to_ascii_lowercase(), v)).collect(),
+                body: None,
+                body_args: Vec::new(),
+            };
+            let settings = admin::load_settings();
+            let extras = settings.feature_extras.get("waf");
+            let engine = waf::engine_for(extras);
+            let limits = Limits::from_extras(extras);
+            // Áp cùng giới hạn và cách tách body như guard
+            let body = t.body.unwrap_or_default();
+            let checked = waf::limits::check_headers(&limits, &req.headers).and_then(|_| prepare_body(&limits, &mut req, body.as_bytes()));
+            match checked {
+                Ok(()) => Json(engine.inspect(&req)).into_response(),
+                Err(v) => Json(v.verdict(engine.threshold)).into_response(),
+            }
+        }).route_layer(admin_only()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(content_type: &str) -> RequestParts {
+        RequestParts {
+            method: "POST".into(),
+            path: "/upload".into(),
+            query: None,
+            headers: vec![("content-type".into(), content_type.into())],
+            body: None,
+            body_args: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn body_preparation_follows_limits() {
+        let limits = Limits { max_body_bytes: 64, ..Limits::default() };
+        // Content-Type không nhận diện được vẫn được kiểm tra như text
+        let mut p = parts("application/octet-stream");
+        prepare_body(&limits, &mut p, b"<script>alert(1)</script>").unwrap();
+        assert_eq!(p.body.as_deref(), Some("<script>alert(1)</script>"));
+        assert_eq!(p.body_args, vec![("body".to_string(), "<script>alert(1)</script>".to_string())]);
+
+        let mut p = parts("application/json");
+        assert_eq!(prepare_body(&limits, &mut p, b"{bad").unwrap_err().status, 400);
+        assert_eq!(prepare_body(&limits, &mut p, &[b'a'; 65]).unwrap_err().status, 413);
+
+        // inspect_body tắt: vẫn giới hạn kích thước nhưng không gắn body
+        let off = Limits { inspect_body: false, ..limits };
+        let mut p = parts("application/json");
+        prepare_body(&off, &mut p, b"{bad").unwrap();
+        assert!(p.body.is_none() && p.body_args.is_empty());
+        assert_eq!(prepare_body(&off, &mut p, &[b'a'; 65]).unwrap_err().status, 413);
+    }
+}
@@ -135,11 +135,16 @@ pub async fn watch_prod_build(build_path: &str, live_router: Arc<RwLock<Router>>
             let _ = load_features("./features", build_path);
 
             // Log gộp: hiển thị danh sách routes mới từ OpenAPI
-            let spec_for_log = if let Some(spec_lock) = &live_spec { Some(spec_lock.read().clone()) } else { None };
+            let changed: Vec<String> = ev.paths.iter()
+                .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+                .collect();
+            let spec_for_log = live_spec.as_ref().map(|spec_lock| spec_lock.read().clone());
+            let mut detail = format!("changed: {}", changed.join(", "));
             if let Some(spec) = spec_for_log {
                 if let Some(paths) = spec.get("paths").and_then(|p| p.as_object()) {
                     let mut names: Vec<String> = paths.keys().cloned().collect();
                     names.sort();
+                    detail = format!("{}; {} routes", detail, names.len());
                     info!("🔄 Router reloaded ({} routes): {}", names.len(), names.join(", "));
                 } else {
                     info!("🔄 Router reloaded from {}", build_path);
@@ -147,10 +152,38 @@ pub async fn watch_prod_build(build_path: &str, live_router: Arc<RwLock<Router>>
             } else {
                 info!("🔄 Router reloaded from {}", build_path);
             }
+            admin::audit::record_reload("watcher", Some(detail));
         }
     }
 }
 
+// Watch thư mục cấu hình: features.json / features.{APP_ENV}.json sửa tay (hoặc do deploy) thì xóa cache settings
+pub async fn watch_config(config_dir: &str) {
+    let (tx, mut rx) = mpsc::channel::<Event>(128);
+    let mut watcher = match RecommendedWatcher::new(
+        move |res| {
+            if let Ok(ev) = res { let _ = tx.blocking_send(ev); }
+        },
+        Config::default(),
+    ) {
+        Ok(w) => w,
+        Err(e) => { warn!("⚠️ Không theo dõi được {}: {}", config_dir, e); return; }
+    };
+    let _ = fs::create_dir_all(config_dir);
+    if let Err(e) = watcher.watch(Path::new(config_dir), RecursiveMode::NonRecursive) {
+        warn!("⚠️ Không theo dõi được {}: {}", config_dir, e);
+        return;
+    }
+
+    let is_settings_file = |p: &Path| {
+        p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("features") && n.ends_with(".json"))
+    };
+    while let Some(ev) = rx.recv().await {
+        if should_ignore_event(&ev) || !ev.paths.iter().any(|p| is_settings_file(p)) { continue; }
+        admin::invalidate_settings();
+    }
+}
+
 // Đọc tên package từ Cargo.toml trong thư mục plugin
 fn read_package_name(plugin_dir: &Path) -> Option<String> {
     let cargo_toml = plugin_dir.join("Cargo.toml");
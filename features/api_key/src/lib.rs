@@ -0,0 +1,484 @@
+#![allow(non_snake_case)]
+#[cfg(feature = "plugin")] use libc::c_char;
+#[cfg(feature = "plugin")] use std::ffi::CString;
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// C-ABI symbol used by the dynamic loader to identify the feature
+#[cfg(feature = "plugin")]
+#[no_mangle]
+pub extern "C" fn feature_name_api_key() -> *mut c_char {
+    CString::new("api_key").unwrap().into_raw()
+}
+
+// ---- Pure Rust logic below (used by the main app via rlib) ----
+
+pub const KEYS_FILE: &str = "./admin/config/api_keys.json";
+// Tiền tố giúp nhận diện key khi bị lộ (secret scanning) và phân biệt với token khác
+const KEY_PREFIX: &str = "ak_";
+// last_used_at đã lưu cũ hơn khoảng này thì lần flush kế tiếp mới ghi lại file
+const LAST_USED_FLUSH_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    // Vài ký tự đầu của key để nhận diện trong Admin (không đủ để dùng)
+    pub prefix: String,
+    // sha256(key) dạng hex; key gốc chỉ hiển thị một lần khi tạo
+    pub hash: String,
+    // Pattern route được phép dùng key ("/greet/*"); rỗng = mọi route
+    pub routes: Vec<String>,
+    pub scopes: Vec<String>,
+    pub created_at: u64,
+    // Unix timestamp; None = không hết hạn
+    pub expires_at: Option<u64>,
+    // Số request tối đa mỗi phút cho key này; None/0 = không giới hạn
+    pub rate_limit_per_minute: Option<u32>,
+    pub last_used_at: Option<u64>,
+    pub disabled: bool,
+}
+
+impl ApiKey {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|t| t <= now)
+    }
+
+    pub fn allows_route(&self, path: &str) -> bool {
+        self.routes.is_empty() || self.routes.iter().any(|p| module_utils::route_matches(p, path))
+    }
+
+    pub fn principal(&self) -> module_utils::Principal {
+        module_utils::Principal {
+            subject: Some(format!("api_key:{}", if self.name.is_empty() { &self.id } else { &self.name })),
+            scopes: self.scopes.clone(),
+            claims: Default::default(),
+            api_key_id: Some(self.id.clone()),
+            auth_method: Some("api_key".to_string()),
+        }
+    }
+
+    // Bản hiển thị cho Admin (không kèm hash)
+    pub fn summary(&self) -> Value {
+        serde_json::json!({
+            "id": self.id,
+            "name": self.name,
+            "prefix": self.prefix,
+            "routes": self.routes,
+            "scopes": self.scopes,
+            "created_at": self.created_at,
+            "expires_at": self.expires_at,
+            "rate_limit_per_minute": self.rate_limit_per_minute,
+            "last_used_at": self.last_used_at,
+            "disabled": self.disabled,
+        })
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn random_hex(n: usize) -> String {
+    let mut buf = vec![0u8; n];
+    OsRng.fill_bytes(&mut buf);
+    hex::encode(buf)
+}
+
+fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+// ---- Cấu hình (feature_extras.api_key) ----
+
+#[derive(Debug, Clone)]
+pub struct ApiKeyConfig {
+    // Route bắt buộc phải có API key
+    pub protected_routes: Vec<String>,
+    pub header_name: String,
+    // Chấp nhận thêm "Authorization: ApiKey <key>"
+    pub allow_authorization_header: bool,
+}
+
+impl ApiKeyConfig {
+    pub fn from_extras(extras: Option<&Value>) -> Self {
+        let get = |k: &str| extras.and_then(|o| o.get(k));
+        Self {
+            protected_routes: get("protected_routes")
+                .and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect())
+                .unwrap_or_default(),
+            header_name: get("header_name")
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .unwrap_or("X-API-Key")
+                .to_string(),
+            allow_authorization_header: get("allow_authorization_header").and_then(|v| v.as_bool()).unwrap_or(true),
+        }
+    }
+
+    pub fn requires_key(&self, path: &str) -> bool {
+        self.protected_routes.iter().any(|p| module_utils::route_matches(p, path))
+    }
+
+    // Lấy key từ header cấu hình hoặc Authorization: ApiKey <key>
+    pub fn extract<'a>(&self, header: impl Fn(&str) -> Option<&'a str>) -> Option<&'a str> {
+        if let Some(v) = header(&self.header_name).map(|v| v.trim()).filter(|v| !v.is_empty()) {
+            return Some(v);
+        }
+        if !self.allow_authorization_header { return None; }
+        let (scheme, key) = header("authorization")?.trim().split_once(' ')?;
+        let key = key.trim();
+        if scheme.eq_ignore_ascii_case("apikey") && !key.is_empty() { Some(key) } else { None }
+    }
+}
+
+// ---- Lỗi xác thực ----
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyError {
+    Missing,
+    Invalid,
+    Expired,
+    Disabled,
+    // 403: key hợp lệ nhưng không được gắn với route này
+    RouteNotAllowed,
+    // 429: vượt rate limit của key, kèm số giây cần chờ
+    RateLimited(u64),
+}
+
+impl KeyError {
+    pub fn status(&self) -> u16 {
+        match self {
+            KeyError::RouteNotAllowed => 403,
+            KeyError::RateLimited(_) => 429,
+            _ => 401,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            KeyError::Missing => "missing_api_key",
+            KeyError::Invalid => "invalid_api_key",
+            KeyError::Expired => "expired_api_key",
+            KeyError::Disabled => "disabled_api_key",
+            KeyError::RouteNotAllowed => "route_not_allowed",
+            KeyError::RateLimited(_) => "rate_limited",
+        }
+    }
+
+    pub fn body(&self) -> Value {
+        serde_json::json!({ "error": self.code() })
+    }
+}
+
+// ---- Lưu trữ ----
+
+struct Store {
+    mtime: Option<SystemTime>,
+    keys: Arc<Vec<ApiKey>>,
+}
+
+static STORE: Lazy<RwLock<Option<Store>>> = Lazy::new(|| RwLock::new(None));
+// Khóa ghi để hai thao tác admin không ghi đè lẫn nhau
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+// last_used_at mới nhất trong bộ nhớ (chưa chắc đã ghi xuống file)
+static LAST_USED: Lazy<RwLock<HashMap<String, u64>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+// Có last_used_at cần ghi xuống file ở lần flush() kế tiếp
+static DIRTY: AtomicBool = AtomicBool::new(false);
+
+fn file_mtime() -> Option<SystemTime> {
+    std::fs::metadata(KEYS_FILE).and_then(|m| m.modified()).ok()
+}
+
+// Danh sách key đã lưu; nạp lại khi file thay đổi
+fn stored_keys() -> Arc<Vec<ApiKey>> {
+    let mtime = file_mtime();
+    if let Some(s) = STORE.read().as_ref() {
+        if s.mtime == mtime { return s.keys.clone(); }
+    }
+    let keys: Vec<ApiKey> = std::fs::read_to_string(KEYS_FILE)
+        .ok()
+        .and_then(|t| serde_json::from_str(&t).ok())
+        .unwrap_or_default();
+    let keys = Arc::new(keys);
+    *STORE.write() = Some(Store { mtime, keys: keys.clone() });
+    keys
+}
+
+// Ghi nguyên tử, chỉ owner đọc được (unix)
+fn save_keys(keys: &[ApiKey]) -> std::io::Result<()> {
+    if let Some(dir) = std::path::Path::new(KEYS_FILE).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let text = serde_json::to_string_pretty(keys).map_err(std::io::Error::other)?;
+    let tmp = format!("{}.tmp", KEYS_FILE);
+    std::fs::write(&tmp, text)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::fs::rename(&tmp, KEYS_FILE)?;
+    *STORE.write() = None;
+    Ok(())
+}
+
+// Sửa danh sách key dưới khóa ghi; gộp last_used_at trong bộ nhớ trước khi lưu
+fn mutate<T>(f: impl FnOnce(&mut Vec<ApiKey>) -> Result<T, String>) -> Result<T, String> {
+    let _guard = WRITE_LOCK.lock();
+    let mut keys = with_last_used((*stored_keys()).clone());
+    let out = f(&mut keys)?;
+    save_keys(&keys).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+fn with_last_used(mut keys: Vec<ApiKey>) -> Vec<ApiKey> {
+    let used = LAST_USED.read();
+    for k in keys.iter_mut() {
+        if let Some(t) = used.get(&k.id) {
+            if k.last_used_at.is_none_or(|cur| cur < *t) { k.last_used_at = Some(*t); }
+        }
+    }
+    keys
+}
+
+pub fn list_keys() -> Vec<ApiKey> {
+    with_last_used((*stored_keys()).clone())
+}
+
+// Thông tin tạo/cập nhật key từ Admin
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct KeyPolicy {
+    pub name: Option<String>,
+    pub routes: Option<Vec<String>>,
+    pub scopes: Option<Vec<String>>,
+    // Unix timestamp; 0 = bỏ hạn
+    pub expires_at: Option<u64>,
+    // Tiện dụng khi tạo: hết hạn sau N ngày
+    pub expires_in_days: Option<u64>,
+    pub rate_limit_per_minute: Option<u32>,
+    pub disabled: Option<bool>,
+}
+
+impl KeyPolicy {
+    fn apply(self, k: &mut ApiKey) {
+        if let Some(v) = self.name { k.name = v.trim().to_string(); }
+        if let Some(v) = self.routes { k.routes = v.into_iter().filter(|s| !s.trim().is_empty()).collect(); }
+        if let Some(v) = self.scopes { k.scopes = v.into_iter().filter(|s| !s.trim().is_empty()).collect(); }
+        if let Some(days) = self.expires_in_days { k.expires_at = Some(now_secs().saturating_add(days.saturating_mul(86_400))); }
+        if let Some(t) = self.expires_at { k.expires_at = if t == 0 { None } else { Some(t) }; }
+        if let Some(v) = self.rate_limit_per_minute { k.rate_limit_per_minute = if v == 0 { None } else { Some(v) }; }
+        if let Some(v) = self.disabled { k.disabled = v; }
+    }
+}
+
+// Tạo key mới; trả về (bản ghi, key gốc) - key gốc không được lưu
+pub fn create_key(policy: KeyPolicy) -> Result<(ApiKey, String), String> {
+    let secret = format!("{}{}", KEY_PREFIX, random_hex(20));
+    let mut key = ApiKey {
+        id: random_hex(6),
+        prefix: secret[..KEY_PREFIX.len() + 6].to_string(),
+        hash: hash_key(&secret),
+        created_at: now_secs(),
+        ..Default::default()
+    };
+    policy.apply(&mut key);
+    let out = key.clone();
+    mutate(move |keys| {
+        keys.push(key);
+        Ok(())
+    })?;
+    Ok((out, secret))
+}
+
+pub fn update_key(id: &str, policy: KeyPolicy) -> Result<ApiKey, String> {
+    mutate(|keys| {
+        let k = keys.iter_mut().find(|k| k.id == id).ok_or_else(|| format!("api key {} not found", id))?;
+        policy.apply(k);
+        Ok(k.clone())
+    })
+}
+
+pub fn delete_key(id: &str) -> Result<(), String> {
+    mutate(|keys| {
+        let before = keys.len();
+        keys.retain(|k| k.id != id);
+        if keys.len() == before { return Err(format!("api key {} not found", id)); }
+        Ok(())
+    })?;
+    LAST_USED.write().remove(id);
+    Ok(())
+}
+
+// Ghi nhận lần dùng trong bộ nhớ; file chỉ được ghi bởi flush() định kỳ (không ghi trên luồng request)
+fn touch(key: &ApiKey, now: u64) {
+    LAST_USED.write().insert(key.id.clone(), now);
+    if key.last_used_at.is_none_or(|t| now.saturating_sub(t) >= LAST_USED_FLUSH_SECS) {
+        DIRTY.store(true, Ordering::Relaxed);
+    }
+}
+
+// Ghi last_used_at trong bộ nhớ xuống file nếu có thay đổi (gọi định kỳ từ app)
+pub fn flush() -> std::io::Result<()> {
+    if !DIRTY.swap(false, Ordering::Relaxed) { return Ok(()); }
+    mutate(|_| Ok(())).map_err(|e| {
+        // Giữ cờ để lần sau thử lại
+        DIRTY.store(true, Ordering::Relaxed);
+        std::io::Error::other(e)
+    })
+}
+
+// Rate limit riêng của key: sliding window 1 phút, dùng chung store (và cơ chế dọn dẹp) của rate_limit
+fn rate_policy(key: &ApiKey) -> Option<rate_limit::Policy> {
+    let limit = key.rate_limit_per_minute.filter(|l| *l > 0)?;
+    Some(rate_limit::Policy {
+        window: Duration::from_secs(60),
+        ..rate_limit::Policy::per_second(limit, rate_limit::Algorithm::SlidingWindow)
+    })
+}
+
+fn check_rate(key: &ApiKey) -> Result<(), KeyError> {
+    let Some(policy) = rate_policy(key) else { return Ok(()) };
+    let decision = rate_limit::check(&format!("api_key|id:{}", key.id), &policy);
+    if decision.allowed { return Ok(()); }
+    Err(KeyError::RateLimited(decision.retry_after_secs().unwrap_or(1)))
+}
+
+// Kiểm tra key trong danh sách cho path tại thời điểm now (không ghi nhận lần dùng)
+fn verify<'a>(keys: &'a [ApiKey], raw: &str, path: &str, now: u64) -> Result<&'a ApiKey, KeyError> {
+    let hash = hash_key(raw.trim());
+    let key = keys
+        .iter()
+        .find(|k| module_utils::constant_time_eq(k.hash.as_bytes(), hash.as_bytes()))
+        .ok_or(KeyError::Invalid)?;
+    if key.disabled { return Err(KeyError::Disabled); }
+    if key.is_expired(now) { return Err(KeyError::Expired); }
+    if !key.allows_route(path) { return Err(KeyError::RouteNotAllowed); }
+    check_rate(key)?;
+    Ok(key)
+}
+
+// Xác thực key cho path: hash khớp, còn hạn, chưa bị khóa, được gắn route, chưa vượt rate limit
+pub fn authenticate(raw: &str, path: &str) -> Result<ApiKey, KeyError> {
+    let keys = stored_keys();
+    let now = now_secs();
+    let key = verify(&keys, raw, path, now)?;
+    touch(key, now);
+    Ok(key.clone())
+}
+
+// Manifest để UI Admin tự động sinh cấu hình theo code của feature
+#[cfg(feature = "plugin")]
+#[no_mangle]
+pub extern "C" fn feature_manifest_api_key() -> *mut c_char {
+    let json = r#"{
+        "name": "api_key",
+        "description": "Xác thực bằng API key (chỉ lưu hash), giới hạn route/scope/hạn dùng/rate limit theo từng key",
+        "settings": [
+          {"key": "protected_routes", "type": "route_list", "label": "Routes yêu cầu API key", "default": []},
+          {"key": "header_name", "type": "string", "label": "Header Name", "default": "X-API-Key"},
+          {"key": "allow_authorization_header", "type": "boolean", "label": "Accept Authorization: ApiKey <key>", "default": true}
+        ]
+    }"#;
+    CString::new(json).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str, raw: &str) -> ApiKey {
+        ApiKey { id: id.to_string(), name: id.to_string(), hash: hash_key(raw), ..Default::default() }
+    }
+
+    #[test]
+    fn finds_key_by_hash() {
+        let keys = vec![key("k1", "ak_one"), key("k2", "ak_two")];
+        assert_eq!(verify(&keys, "ak_two", "/greet", 0).unwrap().id, "k2");
+        // Khoảng trắng thừa quanh key được bỏ qua
+        assert_eq!(verify(&keys, " ak_one ", "/greet", 0).unwrap().id, "k1");
+        assert_eq!(verify(&keys, "ak_three", "/greet", 0).unwrap_err(), KeyError::Invalid);
+        // Chuỗi hash không phải là key
+        assert_eq!(verify(&keys, &hash_key("ak_one"), "/greet", 0).unwrap_err(), KeyError::Invalid);
+    }
+
+    #[test]
+    fn expired_key_is_rejected() {
+        let mut k = key("exp", "ak_exp");
+        k.expires_at = Some(1_000);
+        let keys = vec![k];
+        assert!(verify(&keys, "ak_exp", "/greet", 999).is_ok());
+        assert_eq!(verify(&keys, "ak_exp", "/greet", 1_000).unwrap_err(), KeyError::Expired);
+        assert_eq!(KeyError::Expired.status(), 401);
+    }
+
+    #[test]
+    fn disabled_key_is_rejected() {
+        let mut k = key("off", "ak_off");
+        let mut policy = KeyPolicy { disabled: Some(true), ..Default::default() };
+        policy.clone().apply(&mut k);
+        assert_eq!(verify(&[k.clone()], "ak_off", "/greet", 0).unwrap_err(), KeyError::Disabled);
+        policy.disabled = Some(false);
+        policy.apply(&mut k);
+        assert!(verify(&[k], "ak_off", "/greet", 0).is_ok());
+    }
+
+    #[test]
+    fn routes_and_scopes_follow_policy() {
+        let mut k = key("scoped", "ak_scoped");
+        KeyPolicy {
+            routes: Some(vec!["/greet/*".into(), " ".into()]),
+            scopes: Some(vec!["read".into(), "".into()]),
+            ..Default::default()
+        }
+        .apply(&mut k);
+        assert_eq!(k.routes, vec!["/greet/*"]);
+        let keys = vec![k];
+        let ok = verify(&keys, "ak_scoped", "/greet/hello", 0).unwrap();
+        let p = ok.principal();
+        assert!(p.has_scope("read"));
+        assert!(!p.has_scope("write"));
+        assert_eq!(p.api_key_id.as_deref(), Some("scoped"));
+        let err = verify(&keys, "ak_scoped", "/admin", 0).unwrap_err();
+        assert_eq!(err, KeyError::RouteNotAllowed);
+        assert_eq!(err.status(), 403);
+    }
+
+    #[test]
+    fn expiry_in_days_saturates() {
+        let mut k = ApiKey::default();
+        KeyPolicy { expires_in_days: Some(u64::MAX), ..Default::default() }.apply(&mut k);
+        assert_eq!(k.expires_at, Some(u64::MAX));
+        KeyPolicy { expires_at: Some(0), ..Default::default() }.apply(&mut k);
+        assert_eq!(k.expires_at, None);
+    }
+
+    #[test]
+    fn per_key_rate_limit() {
+        let mut limited = key("rate-limited-test", "ak_limited");
+        limited.rate_limit_per_minute = Some(2);
+        let keys = vec![limited, key("rate-free-test", "ak_free")];
+        assert!(verify(&keys, "ak_limited", "/greet", 0).is_ok());
+        assert!(verify(&keys, "ak_limited", "/greet", 0).is_ok());
+        match verify(&keys, "ak_limited", "/greet", 0) {
+            Err(KeyError::RateLimited(secs)) => assert!((1..=60).contains(&secs)),
+            other => panic!("expected rate limit, got {:?}", other),
+        }
+        // Key không đặt giới hạn không bị ảnh hưởng
+        for _ in 0..5 {
+            assert!(verify(&keys, "ak_free", "/greet", 0).is_ok());
+        }
+    }
+}
@@ -20,17 +20,19 @@ pub mod plugin {
     // Manifest mô tả UI cấu hình cho Admin (chuẩn hóa theo các feature khác)
     // - Cho phép bật/tắt CORS theo từng route bằng danh sách enabled_routes
     // - Cấu hình mặc định đặt ở các khóa phẳng: origins, methods, headers, expose_headers, allow_credentials, max_age
+    // - routes: ghi đè từng khóa theo route (route có ghi đè được bật kể cả khi không nằm trong enabled_routes)
     fn manifest_json() -> String {
         r#"{
             "name": "cors",
             "settings": [
               {"key": "enabled_routes", "type": "route_list", "label": "Routes áp dụng CORS", "default": []},
-              {"key": "origins", "type": "string_list", "label": "Default Origins", "default": ["*"]},
+              {"key": "origins", "type": "string_list", "label": "Default Origins (*, exact, https://*.example.com, regex:<pattern>)", "default": ["*"]},
               {"key": "methods", "type": "string_list", "label": "Default Methods", "default": ["GET","POST","PUT","DELETE"]},
               {"key": "headers", "type": "string_list", "label": "Default Headers", "default": ["*"]},
               {"key": "expose_headers", "type": "string_list", "label": "Default Expose Headers", "default": []},
               {"key": "allow_credentials", "type": "number", "label": "Allow Credentials (0/1)", "default": 0},
-              {"key": "max_age", "type": "number", "label": "Max Age (seconds)", "default": 0}
+              {"key": "max_age", "type": "number", "label": "Max Age (seconds)", "default": 0},
+              {"key": "routes", "type": "json", "label": "Per-Route Overrides (take precedence over defaults)", "default": {}, "example": {"/greet/*": {"origins": ["https://*.example.com"], "allow_credentials": 1}, "/internal/*": {"enabled": false}}}
             ]
         }"#.to_string()
     }
@@ -48,4 +50,6 @@ pub mod plugin {
     }
 }
 
-// Không cần logic runtime trong crate này; phần áp dụng layer sẽ xử lý ở app/router.rs
\ No newline at end of file
+// Logic chính sách (pure Rust) dùng bởi middleware CORS của app (app/src/cors.rs)
+pub mod policy;
+pub use policy::{config_for, CorsConfig, CorsPolicy, OriginPattern};
\ No newline at end of file
@@ -0,0 +1,410 @@
+// Chính sách CORS: cấu hình mặc định + ghi đè theo route, khớp origin theo chuỗi / wildcard / regex,
+// kiểm tra các tổ hợp không hợp lệ (credentials với "*")
+use module_utils::{route_matches, route_specificity, ConfigCache};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+pub const DEFAULT_METHODS: &[&str] = &["GET", "POST", "PUT", "DELETE"];
+
+// Một mục trong danh sách origins:
+// "*" | "https://app.example.com" | "https://*.example.com" | "http://localhost:*" | "regex:^https://(a|b)\.example\.com$"
+#[derive(Debug, Clone)]
+pub enum OriginPattern {
+    Any,
+    Exact(String),
+    Pattern { source: String, re: Regex },
+}
+
+impl OriginPattern {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if s == "*" { return Ok(Self::Any); }
+        if let Some(re) = s.strip_prefix("regex:") {
+            let re = re.trim();
+            let anchored = format!("^(?:{})$", re.trim_start_matches('^').trim_end_matches('$'));
+            return Regex::new(&anchored)
+                .map(|re| Self::Pattern { source: s.to_string(), re })
+                .map_err(|e| format!("invalid origin regex {:?}: {}", s, e));
+        }
+        let Some((scheme, rest)) = s.split_once("://") else {
+            return Err(format!("invalid origin {:?}: expected scheme://host[:port]", s));
+        };
+        if scheme.is_empty() || rest.is_empty() || rest.contains('/') {
+            return Err(format!("invalid origin {:?}: expected scheme://host[:port]", s));
+        }
+        if !s.contains('*') { return Ok(Self::Exact(s.to_ascii_lowercase())); }
+        // "*" trong host khớp một hoặc nhiều nhãn tên miền; sau ":" khớp số port
+        let mut re = String::from("^");
+        let mut prev = ' ';
+        for c in s.to_ascii_lowercase().chars() {
+            if c == '*' {
+                re.push_str(if prev == ':' { "[0-9]+" } else { "[a-z0-9-]+(?:\\.[a-z0-9-]+)*" });
+            } else {
+                re.push_str(&regex::escape(&c.to_string()));
+            }
+            prev = c;
+        }
+        re.push('$');
+        Regex::new(&re)
+            .map(|re| Self::Pattern { source: s.to_string(), re })
+            .map_err(|e| format!("invalid origin pattern {:?}: {}", s, e))
+    }
+
+    pub fn matches(&self, origin: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Exact(o) => o.eq_ignore_ascii_case(origin),
+            Self::Pattern { re, .. } => re.is_match(&origin.to_ascii_lowercase()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Any => "*",
+            Self::Exact(o) => o,
+            Self::Pattern { source, .. } => source,
+        }
+    }
+}
+
+// Các khóa cấu hình; None = lấy từ mặc định (khi ghi đè theo route) hoặc giá trị mặc định
+#[derive(Debug, Clone, Default)]
+struct PolicySpec {
+    origins: Option<Vec<String>>,
+    methods: Option<Vec<String>>,
+    headers: Option<Vec<String>>,
+    expose_headers: Option<Vec<String>>,
+    allow_credentials: Option<bool>,
+    max_age: Option<u64>,
+}
+
+fn strings(v: Option<&Value>) -> Option<Vec<String>> {
+    v.and_then(|v| v.as_array()).map(|arr| arr.iter().filter_map(|x| x.as_str()).map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+}
+
+impl PolicySpec {
+    fn from_object(o: &Map<String, Value>) -> Self {
+        Self {
+            origins: strings(o.get("origins")),
+            methods: strings(o.get("methods")),
+            headers: strings(o.get("headers")),
+            expose_headers: strings(o.get("expose_headers")),
+            // Nhận bool hoặc 0/1
+            allow_credentials: o.get("allow_credentials").map(|v| v.as_bool().unwrap_or_else(|| v.as_u64().is_some_and(|n| n != 0))),
+            max_age: o.get("max_age").and_then(|v| v.as_u64()),
+        }
+    }
+
+    // Giá trị của route ghi đè từng khóa của mặc định
+    fn merged(&self, over: &PolicySpec) -> Self {
+        Self {
+            origins: over.origins.clone().or_else(|| self.origins.clone()),
+            methods: over.methods.clone().or_else(|| self.methods.clone()),
+            headers: over.headers.clone().or_else(|| self.headers.clone()),
+            expose_headers: over.expose_headers.clone().or_else(|| self.expose_headers.clone()),
+            allow_credentials: over.allow_credentials.or(self.allow_credentials),
+            max_age: over.max_age.or(self.max_age),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorsPolicy {
+    // Pattern route áp dụng ("*" = mặc định cho mọi route được bật)
+    pub route: String,
+    #[serde(serialize_with = "ser_origins")]
+    pub origins: Vec<OriginPattern>,
+    pub methods: Vec<String>,
+    // Viết thường; "*" = mọi header
+    pub headers: Vec<String>,
+    pub expose_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age: Option<u64>,
+}
+
+fn ser_origins<S: serde::Serializer>(origins: &[OriginPattern], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(origins.iter().map(|o| o.as_str()))
+}
+
+impl CorsPolicy {
+    fn compile(route: &str, spec: &PolicySpec) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+        let mut origins = Vec::new();
+        for o in spec.origins.iter().flatten() {
+            match OriginPattern::parse(o) {
+                Ok(p) => origins.push(p),
+                Err(e) => errors.push(e),
+            }
+        }
+        let methods: Vec<String> = match &spec.methods {
+            Some(m) => m.iter().map(|m| m.to_ascii_uppercase()).collect(),
+            None => DEFAULT_METHODS.iter().map(|m| m.to_string()).collect(),
+        };
+        for m in methods.iter().filter(|m| *m != "*") {
+            if !m.bytes().all(|b| b.is_ascii_alphabetic()) { errors.push(format!("invalid method {:?}", m)); }
+        }
+        let header_names = |list: &Option<Vec<String>>, errors: &mut Vec<String>| -> Vec<String> {
+            let list: Vec<String> = list.iter().flatten().map(|h| h.to_ascii_lowercase()).collect();
+            for h in list.iter().filter(|h| *h != "*") {
+                if !h.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_!#$%&'*+.^`|~".contains(&b)) {
+                    errors.push(format!("invalid header name {:?}", h));
+                }
+            }
+            list
+        };
+        let headers = header_names(&spec.headers, &mut errors);
+        let expose_headers = header_names(&spec.expose_headers, &mut errors);
+        let allow_credentials = spec.allow_credentials.unwrap_or(false);
+        // Trình duyệt không chấp nhận "*" khi gửi kèm credentials
+        if allow_credentials {
+            if origins.iter().any(|o| matches!(o, OriginPattern::Any)) {
+                errors.push("allow_credentials cannot be combined with origin \"*\" (list origins or use a wildcard pattern)".to_string());
+            }
+            for (name, list) in [("methods", &methods), ("headers", &headers), ("expose_headers", &expose_headers)] {
+                if list.iter().any(|x| x == "*") {
+                    errors.push(format!("allow_credentials cannot be combined with {} \"*\"", name));
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(|e| format!("{}: {}", route, e)).collect());
+        }
+        Ok(Self { route: route.to_string(), origins, methods, headers, expose_headers, allow_credentials, max_age: spec.max_age })
+    }
+
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.origins.iter().any(|o| o.matches(origin))
+    }
+
+    fn allows_any_origin(&self) -> bool {
+        self.origins.iter().any(|o| matches!(o, OriginPattern::Any))
+    }
+
+    // Header cho response thường; origin không được phép thì chỉ có Vary
+    pub fn response_headers(&self, origin: Option<&str>) -> Vec<(&'static str, String)> {
+        // "*" không phụ thuộc Origin nên không cần Vary
+        if self.allows_any_origin() {
+            return match origin {
+                Some(_) => self.allowed_headers("*".to_string()),
+                None => Vec::new(),
+            };
+        }
+        let mut out = vec![("vary", "Origin".to_string())];
+        if let Some(o) = origin.filter(|o| self.allows_origin(o)) {
+            out.extend(self.allowed_headers(o.to_string()));
+        }
+        out
+    }
+
+    fn allowed_headers(&self, allow_origin: String) -> Vec<(&'static str, String)> {
+        let mut out = vec![("access-control-allow-origin", allow_origin)];
+        if self.allow_credentials { out.push(("access-control-allow-credentials", "true".to_string())); }
+        if !self.expose_headers.is_empty() { out.push(("access-control-expose-headers", self.expose_headers.join(", "))); }
+        out
+    }
+
+    // Header cho preflight (OPTIONS + Access-Control-Request-Method)
+    pub fn preflight_headers(&self, origin: &str) -> Vec<(&'static str, String)> {
+        let mut out = vec![("vary", "Origin, Access-Control-Request-Method, Access-Control-Request-Headers".to_string())];
+        if !self.allows_origin(origin) { return out; }
+        let allow_origin = if self.allows_any_origin() { "*".to_string() } else { origin.to_string() };
+        out.push(("access-control-allow-origin", allow_origin));
+        if self.allow_credentials { out.push(("access-control-allow-credentials", "true".to_string())); }
+        out.push(("access-control-allow-methods", self.methods.join(", ")));
+        if !self.headers.is_empty() { out.push(("access-control-allow-headers", self.headers.join(", "))); }
+        if let Some(age) = self.max_age { out.push(("access-control-max-age", age.to_string())); }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorsConfig {
+    // None = mọi route; Some = chỉ các route trong enabled_routes (và các route có ghi đè)
+    pub enabled_routes: Option<Vec<String>>,
+    // None nếu cấu hình mặc định không hợp lệ
+    pub defaults: Option<CorsPolicy>,
+    // Ghi đè theo route, sắp từ cụ thể nhất
+    pub routes: Vec<CorsPolicy>,
+    // Route ghi đè với "enabled": false hoặc cấu hình không hợp lệ (không áp CORS, không quay về mặc định)
+    pub disabled_routes: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl CorsConfig {
+    // Đọc từ feature_extras.cors: các khóa phẳng là mặc định, "routes" ghi đè từng khóa theo route
+    // {"origins": ["https://*.example.com"], "routes": {"/public/*": {"origins": ["*"], "allow_credentials": false}}}
+    pub fn from_extras(extras: Option<&Value>) -> Self {
+        let empty = Map::new();
+        let obj = extras.and_then(|v| v.as_object()).unwrap_or(&empty);
+        let base = PolicySpec::from_object(obj);
+        let mut errors = Vec::new();
+        let defaults = match CorsPolicy::compile("*", &base) {
+            Ok(p) => Some(p),
+            Err(e) => { errors.extend(e); None }
+        };
+        let mut routes = Vec::new();
+        let mut disabled_routes = Vec::new();
+        if let Some(map) = obj.get("routes").and_then(|v| v.as_object()) {
+            for (route, over) in map {
+                let Some(over) = over.as_object() else {
+                    errors.push(format!("{}: route override must be an object", route));
+                    continue;
+                };
+                if over.get("enabled").and_then(|v| v.as_bool()) == Some(false) {
+                    disabled_routes.push(route.clone());
+                    continue;
+                }
+                match CorsPolicy::compile(route, &base.merged(&PolicySpec::from_object(over))) {
+                    Ok(p) => routes.push(p),
+                    Err(e) => {
+                        errors.extend(e);
+                        disabled_routes.push(route.clone());
+                    }
+                }
+            }
+        }
+        routes.sort_by_key(|p| std::cmp::Reverse(route_specificity(&p.route)));
+        Self { enabled_routes: strings(obj.get("enabled_routes")), defaults, routes, disabled_routes, errors }
+    }
+
+    // Chính sách áp cho path; None = không có CORS (route không bật hoặc cấu hình không hợp lệ)
+    pub fn policy_for(&self, path: &str) -> Option<&CorsPolicy> {
+        let disabled = self.disabled_routes.iter().filter(|r| route_matches(r, path)).max_by_key(|r| route_specificity(r));
+        let over = self.routes.iter().find(|p| route_matches(&p.route, path));
+        // Ghi đè cụ thể hơn thắng
+        match (over, disabled) {
+            (Some(p), Some(d)) if route_specificity(d) > route_specificity(&p.route) => return None,
+            (Some(p), _) => return Some(p),
+            (None, Some(_)) => return None,
+            (None, None) => {}
+        }
+        let enabled = match &self.enabled_routes {
+            Some(list) => list.iter().any(|r| route_matches(r, path)),
+            None => true,
+        };
+        if enabled { self.defaults.as_ref() } else { None }
+    }
+}
+
+// Cấu hình được cache; chỉ biên dịch lại regex origin khi feature_extras.cors thay đổi
+static CACHE: ConfigCache<CorsConfig> = ConfigCache::new();
+
+pub fn config_for(extras: Option<&Value>) -> Arc<CorsConfig> {
+    CACHE.get(extras, CorsConfig::from_extras)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pattern(s: &str) -> OriginPattern {
+        OriginPattern::parse(s).unwrap()
+    }
+
+    #[test]
+    fn wildcard_subdomain_does_not_match_lookalikes() {
+        let p = pattern("https://*.example.com");
+        assert!(p.matches("https://a.example.com"));
+        assert!(p.matches("https://a.b.example.com"));
+        assert!(p.matches("HTTPS://A.Example.COM"));
+        assert!(!p.matches("https://example.com"));
+        assert!(!p.matches("https://evil-example.com"));
+        assert!(!p.matches("https://a.example.com.evil.net"));
+        assert!(!p.matches("http://a.example.com"));
+        assert!(!p.matches("https://a.example.com:8443"));
+
+        let port = pattern("http://localhost:*");
+        assert!(port.matches("http://localhost:3000"));
+        assert!(!port.matches("http://localhost:3000.evil.net"));
+        assert!(!port.matches("http://localhost"));
+    }
+
+    #[test]
+    fn exact_origin_and_invalid_entries() {
+        let p = pattern("https://App.example.com");
+        assert!(p.matches("https://app.example.com"));
+        assert!(!p.matches("https://app.example.com.evil.net"));
+        assert!(OriginPattern::parse("app.example.com").is_err());
+        assert!(OriginPattern::parse("https://app.example.com/path").is_err());
+        assert!(OriginPattern::parse("regex:(").is_err());
+    }
+
+    #[test]
+    fn regex_is_anchored() {
+        // Không có ^...$ vẫn phải khớp toàn bộ origin
+        let p = pattern(r"regex:https://(a|b)\.example\.com");
+        assert!(p.matches("https://a.example.com"));
+        assert!(!p.matches("https://a.example.com.evil.net"));
+        assert!(!p.matches("https://evil.net/?https://b.example.com"));
+        let explicit = pattern(r"regex:^https://(a|b)\.example\.com$");
+        assert!(explicit.matches("https://b.example.com"));
+        assert!(!explicit.matches("xhttps://b.example.com"));
+    }
+
+    #[test]
+    fn credentials_with_any_origin_are_rejected() {
+        let cfg = CorsConfig::from_extras(Some(&json!({"origins": ["*"], "allow_credentials": true})));
+        assert!(cfg.defaults.is_none());
+        assert!(cfg.errors[0].contains("allow_credentials"), "{:?}", cfg.errors);
+        assert!(cfg.policy_for("/greet").is_none());
+
+        let headers = CorsConfig::from_extras(Some(&json!({"origins": ["https://a.example.com"], "headers": ["*"], "allow_credentials": 1})));
+        assert!(headers.defaults.is_none());
+
+        // Route ghi đè không hợp lệ bị tắt thay vì quay về mặc định
+        let route = CorsConfig::from_extras(Some(&json!({
+            "origins": ["https://a.example.com"],
+            "routes": {"/public/*": {"origins": ["*"], "allow_credentials": true}}
+        })));
+        assert!(route.defaults.is_some());
+        assert!(route.policy_for("/public/x").is_none());
+        assert_eq!(route.errors.len(), 1);
+    }
+
+    #[test]
+    fn route_override_takes_precedence() {
+        let cfg = CorsConfig::from_extras(Some(&json!({
+            "origins": ["https://app.example.com"],
+            "allow_credentials": true,
+            "enabled_routes": ["/api/*"],
+            "routes": {
+                "/public/*": {"origins": ["*"], "allow_credentials": false},
+                "/public/private/*": {"enabled": false},
+                "/api/admin/*": {"enabled": false},
+                "/api/admin/open": {"methods": ["get"]}
+            }
+        })));
+        assert!(cfg.errors.is_empty(), "{:?}", cfg.errors);
+        let api = cfg.policy_for("/api/items").unwrap();
+        assert_eq!(api.route, "*");
+        assert!(api.allow_credentials);
+        // Không nằm trong enabled_routes: không có CORS; /public/* có ghi đè nên vẫn được áp
+        assert!(cfg.policy_for("/other").is_none());
+        let public = cfg.policy_for("/public/file").unwrap();
+        assert!(public.allows_origin("https://anything.example"));
+        assert!(!public.allow_credentials);
+        assert!(cfg.policy_for("/public/private/x").is_none());
+        assert!(cfg.policy_for("/api/admin/users").is_none());
+        let open = cfg.policy_for("/api/admin/open").unwrap();
+        assert_eq!(open.methods, vec!["GET"]);
+        // Khóa không ghi đè lấy từ mặc định
+        assert!(open.allows_origin("https://app.example.com"));
+        assert!(open.allow_credentials);
+    }
+
+    #[test]
+    fn response_headers_reflect_only_allowed_origins() {
+        let cfg = CorsConfig::from_extras(Some(&json!({"origins": ["https://*.example.com"], "allow_credentials": true, "max_age": 600})));
+        let p = cfg.policy_for("/x").unwrap();
+        let allowed = p.response_headers(Some("https://a.example.com"));
+        assert!(allowed.contains(&("access-control-allow-origin", "https://a.example.com".to_string())));
+        assert!(allowed.contains(&("access-control-allow-credentials", "true".to_string())));
+        assert_eq!(p.response_headers(Some("https://evil-example.com")), vec![("vary", "Origin".to_string())]);
+        let pre = p.preflight_headers("https://a.example.com");
+        assert!(pre.contains(&("access-control-max-age", "600".to_string())));
+        assert_eq!(p.preflight_headers("https://evil.net").len(), 1);
+    }
+}
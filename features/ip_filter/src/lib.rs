@@ -0,0 +1,456 @@
+#![allow(non_snake_case)]
+#[cfg(feature = "plugin")] use libc::c_char;
+#[cfg(feature = "plugin")] use std::ffi::CString;
+use ipnet::IpNet;
+use module_utils::{route_matches, ConfigCache};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// C-ABI symbol used by the dynamic loader to identify the feature
+#[cfg(feature = "plugin")]
+#[no_mangle]
+pub extern "C" fn feature_name_ip_filter() -> *mut c_char {
+    CString::new("ip_filter").unwrap().into_raw()
+}
+
+// ---- Pure Rust logic below (used by the main app via rlib) ----
+
+// Danh sách IP bị ban được ghi định kỳ xuống file để không mất khi restart
+pub const BANS_FILE: &str = "./admin/config/ip_bans.json";
+
+pub const DEFAULT_BAN_WINDOW_SECS: u64 = 600;
+pub const DEFAULT_BAN_DURATION_SECS: u64 = 3600;
+
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn parse_net(s: &str) -> Option<IpNet> {
+    let s = s.trim();
+    s.parse::<IpNet>().ok().or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+// Đọc danh sách IP/CIDR; giá trị sai ghi vào errors thay vì bỏ qua im lặng
+fn net_list(v: Option<&Value>, field: &str, errors: &mut Vec<String>) -> Vec<IpNet> {
+    let mut out = Vec::new();
+    for s in v.and_then(|v| v.as_array()).into_iter().flatten().filter_map(|x| x.as_str()) {
+        match parse_net(s) {
+            Some(n) => out.push(n),
+            None => errors.push(format!("{}: invalid IP/CIDR {:?}", field, s)),
+        }
+    }
+    out
+}
+
+// ---- Cấu hình ----
+
+// Danh sách allow/deny riêng cho các route khớp glob
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteRule {
+    pub route: String,
+    #[serde(serialize_with = "ser_nets")]
+    pub allow: Vec<IpNet>,
+    #[serde(serialize_with = "ser_nets")]
+    pub deny: Vec<IpNet>,
+}
+
+fn ser_nets<S: serde::Serializer>(nets: &[IpNet], s: S) -> Result<S::Ok, S::Error> {
+    s.collect_seq(nets.iter().map(|n| n.to_string()))
+}
+
+// Loại vi phạm được đếm để tự động ban
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Offense {
+    // Request bị WAF chặn
+    Waf,
+    // 401 (sai mật khẩu / token / API key)
+    Unauthorized,
+    // 429 (rate limit, quota)
+    TooManyRequests,
+}
+
+impl Offense {
+    pub fn from_status(status: u16) -> Option<Self> {
+        match status {
+            401 => Some(Self::Unauthorized),
+            429 => Some(Self::TooManyRequests),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Waf => "WAF blocks",
+            Self::Unauthorized => "401 responses",
+            Self::TooManyRequests => "429 responses",
+        }
+    }
+}
+
+// Ngưỡng kiểu fail2ban: đủ N vi phạm cùng loại trong window_secs thì ban ban_secs giây; N = 0 là tắt
+#[derive(Debug, Clone, Serialize)]
+pub struct BanPolicy {
+    pub waf: u32,
+    pub unauthorized: u32,
+    pub too_many_requests: u32,
+    pub window_secs: u64,
+    pub ban_secs: u64,
+}
+
+impl BanPolicy {
+    pub fn threshold(&self, offense: Offense) -> u32 {
+        match offense {
+            Offense::Waf => self.waf,
+            Offense::Unauthorized => self.unauthorized,
+            Offense::TooManyRequests => self.too_many_requests,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IpFilterConfig {
+    // Áp cho mọi route
+    #[serde(serialize_with = "ser_nets")]
+    pub allow: Vec<IpNet>,
+    #[serde(serialize_with = "ser_nets")]
+    pub deny: Vec<IpNet>,
+    pub rules: Vec<RouteRule>,
+    // Không bao giờ tự động ban (mặc định loopback)
+    #[serde(serialize_with = "ser_nets")]
+    pub ban_exempt: Vec<IpNet>,
+    pub ban: BanPolicy,
+    pub errors: Vec<String>,
+}
+
+impl IpFilterConfig {
+    // Đọc từ feature_extras.ip_filter:
+    // {"allow": [..], "deny": [..], "rules": [{"route": "/admin/*", "allow": ["10.0.0.0/8"]}],
+    //  "ban_after_waf": 5, "ban_after_401": 10, "ban_after_429": 20, "ban_window_secs": 600, "ban_duration_secs": 3600}
+    pub fn from_extras(extras: Option<&Value>) -> Self {
+        let get = |k: &str| extras.and_then(|o| o.get(k));
+        let num = |k: &str, d: u64| get(k).and_then(|v| v.as_u64()).unwrap_or(d);
+        let mut errors = Vec::new();
+        let allow = net_list(get("allow"), "allow", &mut errors);
+        let deny = net_list(get("deny"), "deny", &mut errors);
+        let mut rules = Vec::new();
+        for (i, r) in get("rules").and_then(|v| v.as_array()).into_iter().flatten().enumerate() {
+            let routes: Vec<String> = match r.get("routes").and_then(|v| v.as_array()) {
+                Some(arr) => arr.iter().filter_map(|x| x.as_str()).map(|s| s.to_string()).collect(),
+                None => r.get("route").and_then(|v| v.as_str()).map(|s| vec![s.to_string()]).unwrap_or_default(),
+            };
+            if routes.is_empty() {
+                errors.push(format!("rules[{}]: missing route", i));
+                continue;
+            }
+            let allow = net_list(r.get("allow"), &format!("rules[{}].allow", i), &mut errors);
+            let deny = net_list(r.get("deny"), &format!("rules[{}].deny", i), &mut errors);
+            for route in routes {
+                rules.push(RouteRule { route, allow: allow.clone(), deny: deny.clone() });
+            }
+        }
+        let ban_exempt = match get("ban_exempt") {
+            Some(v) => net_list(Some(v), "ban_exempt", &mut errors),
+            None => ["127.0.0.0/8", "::1/128"].iter().filter_map(|s| parse_net(s)).collect(),
+        };
+        let ban = BanPolicy {
+            waf: num("ban_after_waf", 5) as u32,
+            unauthorized: num("ban_after_401", 10) as u32,
+            too_many_requests: num("ban_after_429", 20) as u32,
+            window_secs: num("ban_window_secs", DEFAULT_BAN_WINDOW_SECS).max(1),
+            ban_secs: num("ban_duration_secs", DEFAULT_BAN_DURATION_SECS).max(1),
+        };
+        Self { allow, deny, rules, ban_exempt, ban, errors }
+    }
+
+    fn is_exempt(&self, ip: &IpAddr) -> bool {
+        self.ban_exempt.iter().any(|n| n.contains(ip))
+    }
+}
+
+// Cấu hình được cache; chỉ phân tích lại (CIDR) khi feature_extras.ip_filter thay đổi
+static CACHE: ConfigCache<IpFilterConfig> = ConfigCache::new();
+
+pub fn config_for(extras: Option<&Value>) -> Arc<IpFilterConfig> {
+    CACHE.get(extras, IpFilterConfig::from_extras)
+}
+
+// ---- Ban ----
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ban {
+    pub ip: String,
+    pub reason: String,
+    pub banned_at: u64,
+    // None = ban vĩnh viễn (chỉ đặt thủ công từ Admin)
+    pub expires_at: Option<u64>,
+}
+
+impl Ban {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|t| t > now)
+    }
+
+    pub fn retry_after_secs(&self, now: u64) -> Option<u64> {
+        self.expires_at.map(|t| t.saturating_sub(now).max(1))
+    }
+}
+
+static BANS: Lazy<Mutex<HashMap<IpAddr, Ban>>> = Lazy::new(|| {
+    let list: Vec<Ban> = std::fs::read_to_string(BANS_FILE)
+        .ok()
+        .and_then(|t| serde_json::from_str(&t).ok())
+        .unwrap_or_default();
+    Mutex::new(list.into_iter().filter_map(|b| Some((b.ip.parse().ok()?, b))).collect())
+});
+// Thời điểm các vi phạm gần đây theo (IP, loại)
+type Offenses = HashMap<(IpAddr, Offense), VecDeque<u64>>;
+
+static OFFENSES: Lazy<Mutex<Offenses>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static DIRTY: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone)]
+pub enum Access {
+    Allowed,
+    // Lý do, vd "deny list" hoặc "route /admin/* allowlist"
+    Denied(String),
+    Banned(Ban),
+}
+
+// Thứ tự: ban đang hiệu lực -> deny (chung, theo route) -> allow (nếu có allowlist áp dụng thì IP phải thuộc một trong số đó)
+pub fn check(ip: IpAddr, path: &str, cfg: &IpFilterConfig, now: u64) -> Access {
+    if let Some(b) = BANS.lock().get(&ip).filter(|b| b.is_active(now)) {
+        return Access::Banned(b.clone());
+    }
+    let rules: Vec<&RouteRule> = cfg.rules.iter().filter(|r| route_matches(&r.route, path)).collect();
+    if cfg.deny.iter().any(|n| n.contains(&ip)) {
+        return Access::Denied("deny list".to_string());
+    }
+    if let Some(r) = rules.iter().find(|r| r.deny.iter().any(|n| n.contains(&ip))) {
+        return Access::Denied(format!("route {} deny list", r.route));
+    }
+    let mut allowlists = std::iter::once(&cfg.allow).chain(rules.iter().map(|r| &r.allow)).filter(|l| !l.is_empty()).peekable();
+    if allowlists.peek().is_some() && !allowlists.any(|l| l.iter().any(|n| n.contains(&ip))) {
+        return Access::Denied("not in allowlist".to_string());
+    }
+    Access::Allowed
+}
+
+// Ghi nhận một vi phạm; trả về Ban mới nếu IP vừa chạm ngưỡng
+pub fn record_offense(ip: IpAddr, offense: Offense, cfg: &IpFilterConfig, now: u64) -> Option<Ban> {
+    let threshold = cfg.ban.threshold(offense);
+    if threshold == 0 || cfg.is_exempt(&ip) { return None; }
+    if BANS.lock().get(&ip).is_some_and(|b| b.is_active(now)) { return None; }
+    {
+        let mut offenses = OFFENSES.lock();
+        let hits = offenses.entry((ip, offense)).or_default();
+        hits.push_back(now);
+        while hits.front().is_some_and(|t| t.saturating_add(cfg.ban.window_secs) <= now) { hits.pop_front(); }
+        if (hits.len() as u32) < threshold { return None; }
+        offenses.remove(&(ip, offense));
+    }
+    let reason = format!("{} {} within {}s", threshold, offense.label(), cfg.ban.window_secs);
+    Some(ban(ip, Some(cfg.ban.ban_secs), &reason, now))
+}
+
+// Ban thủ công hoặc tự động; duration None = vĩnh viễn
+pub fn ban(ip: IpAddr, duration_secs: Option<u64>, reason: &str, now: u64) -> Ban {
+    let b = Ban {
+        ip: ip.to_string(),
+        reason: reason.to_string(),
+        banned_at: now,
+        expires_at: duration_secs.map(|d| now.saturating_add(d)),
+    };
+    BANS.lock().insert(ip, b.clone());
+    DIRTY.store(true, Ordering::Relaxed);
+    b
+}
+
+// Gỡ ban (và xóa bộ đếm vi phạm); false nếu IP không bị ban
+pub fn unban(ip: IpAddr) -> bool {
+    OFFENSES.lock().retain(|(addr, _), _| *addr != ip);
+    let removed = BANS.lock().remove(&ip).is_some();
+    if removed { DIRTY.store(true, Ordering::Relaxed); }
+    removed
+}
+
+// Các ban còn hiệu lực, mới nhất trước
+pub fn bans(now: u64) -> Vec<Ban> {
+    let mut list: Vec<Ban> = BANS.lock().values().filter(|b| b.is_active(now)).cloned().collect();
+    list.sort_by(|a, b| b.banned_at.cmp(&a.banned_at).then_with(|| a.ip.cmp(&b.ip)));
+    list
+}
+
+// Số vi phạm đang đếm trong cửa sổ theo IP (để Admin xem IP sắp bị ban)
+pub fn offenses(cfg: &IpFilterConfig, now: u64) -> Vec<Value> {
+    let offenses = OFFENSES.lock();
+    let mut out: Vec<Value> = offenses
+        .iter()
+        .map(|((ip, kind), hits)| {
+            let count = hits.iter().filter(|t| t.saturating_add(cfg.ban.window_secs) > now).count();
+            serde_json::json!({"ip": ip.to_string(), "offense": kind, "count": count, "threshold": cfg.ban.threshold(*kind)})
+        })
+        .filter(|v| v["count"].as_u64().unwrap_or(0) > 0)
+        .collect();
+    out.sort_by(|a, b| b["count"].as_u64().cmp(&a["count"].as_u64()));
+    out
+}
+
+// Bỏ ban hết hạn, bộ đếm cũ rồi ghi file nếu có thay đổi (ghi nguyên tử)
+pub fn flush(cfg: &IpFilterConfig, now: u64) -> std::io::Result<bool> {
+    OFFENSES.lock().retain(|_, hits| hits.back().is_some_and(|t| t.saturating_add(cfg.ban.window_secs) > now));
+    let text = {
+        let mut bans = BANS.lock();
+        let before = bans.len();
+        bans.retain(|_, b| b.is_active(now));
+        if !DIRTY.swap(false, Ordering::Relaxed) && before == bans.len() { return Ok(false); }
+        let mut list: Vec<&Ban> = bans.values().collect();
+        list.sort_by(|a, b| a.ip.cmp(&b.ip));
+        serde_json::to_string_pretty(&list).map_err(std::io::Error::other)?
+    };
+    if let Some(dir) = std::path::Path::new(BANS_FILE).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = format!("{}.tmp", BANS_FILE);
+    let res = std::fs::write(&tmp, text).and_then(|_| std::fs::rename(&tmp, BANS_FILE));
+    // Ghi lỗi thì để lần sau ghi lại
+    if res.is_err() { DIRTY.store(true, Ordering::Relaxed); }
+    res.map(|_| true)
+}
+
+// Manifest để UI Admin tự động sinh cấu hình theo code của feature
+#[cfg(feature = "plugin")]
+#[no_mangle]
+pub extern "C" fn feature_manifest_ip_filter() -> *mut c_char {
+    let json = r#"{
+        "name": "ip_filter",
+        "description": "Allowlist/denylist IP theo CIDR (chung và theo route), tự động ban IP kiểu fail2ban khi bị WAF chặn, nhận 401 hoặc 429 nhiều lần",
+        "settings": [
+          {"key": "allow", "type": "string_list", "label": "Allowlist (IP/CIDR, empty = everyone)", "default": []},
+          {"key": "deny", "type": "string_list", "label": "Denylist (IP/CIDR)", "default": []},
+          {"key": "rules", "type": "json", "label": "Per-Route Rules (route glob + allow/deny)", "default": [], "example": [{"route": "/admin/*", "allow": ["10.0.0.0/8", "192.168.1.10"]}, {"route": "/greet/*", "deny": ["203.0.113.0/24"]}]},
+          {"key": "ban_after_waf", "type": "number", "label": "Ban after N WAF blocks (0 = off)", "default": 5},
+          {"key": "ban_after_401", "type": "number", "label": "Ban after N 401 responses (0 = off)", "default": 10},
+          {"key": "ban_after_429", "type": "number", "label": "Ban after N 429 responses (0 = off)", "default": 20},
+          {"key": "ban_window_secs", "type": "number", "label": "Offense Window (seconds)", "default": 600},
+          {"key": "ban_duration_secs", "type": "number", "label": "Ban Duration (seconds)", "default": 3600},
+          {"key": "ban_exempt", "type": "string_list", "label": "Never Auto-Ban (IP/CIDR)", "default": ["127.0.0.0/8", "::1/128"]}
+        ]
+    }"#;
+    CString::new(json).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn huge_durations_and_windows_do_not_overflow() {
+        let ip: IpAddr = "198.51.100.7".parse().unwrap();
+        let b = ban(ip, Some(u64::MAX), "test", 1_000);
+        assert_eq!(b.expires_at, Some(u64::MAX));
+        assert!(b.is_active(u64::MAX - 1));
+        assert!(unban(ip));
+        let cfg = IpFilterConfig::from_extras(Some(&json!({"ban_exempt": [], "ban_after_401": 2, "ban_window_secs": u64::MAX})));
+        assert!(record_offense(ip, Offense::Unauthorized, &cfg, u64::MAX - 5).is_none());
+        assert!(record_offense(ip, Offense::Unauthorized, &cfg, u64::MAX - 4).is_some());
+        assert!(unban(ip));
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn denied(a: Access) -> Option<String> {
+        match a {
+            Access::Denied(reason) => Some(reason),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn deny_beats_allow() {
+        let cfg = IpFilterConfig::from_extras(Some(&json!({
+            "allow": ["10.0.0.0/8"],
+            "deny": ["10.0.0.5"],
+            "rules": [{"route": "/hooks/*", "allow": ["10.1.0.0/16"], "deny": ["10.1.2.0/24"]}]
+        })));
+        assert!(cfg.errors.is_empty(), "{:?}", cfg.errors);
+        assert!(matches!(check(ip("10.0.0.4"), "/greet", &cfg, 0), Access::Allowed));
+        assert_eq!(denied(check(ip("10.0.0.5"), "/greet", &cfg, 0)).as_deref(), Some("deny list"));
+        assert_eq!(denied(check(ip("10.1.2.3"), "/hooks/x", &cfg, 0)).as_deref(), Some("route /hooks/* deny list"));
+        // deny theo route chỉ áp cho route đó
+        assert!(matches!(check(ip("10.1.2.3"), "/greet", &cfg, 0), Access::Allowed));
+        assert_eq!(denied(check(ip("192.0.2.1"), "/greet", &cfg, 0)).as_deref(), Some("not in allowlist"));
+    }
+
+    #[test]
+    fn route_allowlist_only_applies_to_its_routes() {
+        let cfg = IpFilterConfig::from_extras(Some(&json!({
+            "rules": [{"routes": ["/admin/*", "/metrics"], "allow": ["192.168.1.10", "10.0.0.0/8"]}, {"allow": ["1.2.3.4"]}]
+        })));
+        assert_eq!(cfg.errors, vec!["rules[1]: missing route"]);
+        assert!(matches!(check(ip("192.168.1.10"), "/admin/users", &cfg, 0), Access::Allowed));
+        assert!(matches!(check(ip("10.9.9.9"), "/metrics", &cfg, 0), Access::Allowed));
+        assert!(denied(check(ip("192.168.1.11"), "/admin/users", &cfg, 0)).is_some());
+        assert!(denied(check(ip("192.168.1.11"), "/metrics", &cfg, 0)).is_some());
+        // Route khác không có allowlist: ai cũng vào được
+        assert!(matches!(check(ip("192.168.1.11"), "/greet", &cfg, 0), Access::Allowed));
+    }
+
+    #[test]
+    fn ban_exempt_is_never_banned() {
+        let defaults = IpFilterConfig::from_extras(Some(&json!({"ban_after_401": 1})));
+        assert!(record_offense(ip("127.0.0.1"), Offense::Unauthorized, &defaults, 100).is_none());
+        assert!(record_offense(ip("::1"), Offense::Unauthorized, &defaults, 100).is_none());
+        let custom = IpFilterConfig::from_extras(Some(&json!({"ban_after_waf": 1, "ban_exempt": ["203.0.113.0/24"]})));
+        assert!(record_offense(ip("203.0.113.9"), Offense::Waf, &custom, 100).is_none());
+        assert!(matches!(check(ip("203.0.113.9"), "/", &custom, 100), Access::Allowed));
+        // Danh sách rỗng: loopback cũng có thể bị ban
+        let none = IpFilterConfig::from_extras(Some(&json!({"ban_after_waf": 1, "ban_exempt": []})));
+        assert!(record_offense(ip("127.0.0.9"), Offense::Waf, &none, 100).is_some());
+        assert!(unban(ip("127.0.0.9")));
+    }
+
+    #[test]
+    fn threshold_within_window_bans() {
+        let cfg = IpFilterConfig::from_extras(Some(&json!({"ban_after_401": 3, "ban_window_secs": 60, "ban_duration_secs": 300})));
+        let a = ip("198.51.100.20");
+        assert!(record_offense(a, Offense::Unauthorized, &cfg, 1_000).is_none());
+        assert!(record_offense(a, Offense::Unauthorized, &cfg, 1_030).is_none());
+        // Loại vi phạm khác được đếm riêng
+        assert!(record_offense(a, Offense::TooManyRequests, &cfg, 1_040).is_none());
+        let b = record_offense(a, Offense::Unauthorized, &cfg, 1_059).expect("third 401 within 60s bans");
+        assert_eq!(b.expires_at, Some(1_359));
+        assert_eq!(b.reason, "3 401 responses within 60s");
+        match check(a, "/greet", &cfg, 1_100) {
+            Access::Banned(b) => assert_eq!(b.retry_after_secs(1_100), Some(259)),
+            other => panic!("expected ban, got {:?}", other),
+        }
+        // Ban hết hạn
+        assert!(matches!(check(a, "/greet", &cfg, 1_359), Access::Allowed));
+        assert!(unban(a));
+    }
+
+    #[test]
+    fn offenses_outside_window_do_not_count() {
+        let cfg = IpFilterConfig::from_extras(Some(&json!({"ban_after_waf": 3, "ban_window_secs": 60})));
+        let a = ip("198.51.100.30");
+        assert!(record_offense(a, Offense::Waf, &cfg, 1_000).is_none());
+        assert!(record_offense(a, Offense::Waf, &cfg, 1_010).is_none());
+        // Vi phạm đầu tiên đúng 60s trước đã rơi khỏi cửa sổ
+        assert!(record_offense(a, Offense::Waf, &cfg, 1_060).is_none());
+        assert_eq!(offenses(&cfg, 1_060).iter().find(|o| o["ip"] == "198.51.100.30").unwrap()["count"], 2);
+        assert!(record_offense(a, Offense::Waf, &cfg, 1_065).is_some());
+        assert!(unban(a));
+    }
+}
+
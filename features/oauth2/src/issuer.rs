@@ -0,0 +1,401 @@
+// Authorization server tối giản (tùy chọn): cấp token client_credentials và dev token,
+// ký ES256 bằng khóa sinh cục bộ, công bố JWKS để chính guard oauth2 xác thực lại.
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::pkcs8::{EncodePrivateKey, LineEnding};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+pub const ISSUER_DIR: &str = "./admin/config/oauth2";
+const KEYS_FILE: &str = "./admin/config/oauth2/keys.json";
+const CLIENTS_FILE: &str = "./admin/config/oauth2/clients.json";
+// Số khóa giữ lại trong JWKS sau khi xoay (để token cũ còn hạn vẫn xác thực được)
+const KEEP_KEYS: usize = 3;
+
+// Khóa ghi file để hai request admin không ghi đè lẫn nhau
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+// Khóa ký đã nạp kèm JWKS đã dựng sẵn; chỉ đọc file lần đầu, được làm mới khi xoay khóa
+static KEYS_CACHE: RwLock<Option<Arc<KeySet>>> = RwLock::new(None);
+
+struct KeySet {
+    keys: Vec<SigningKey>,
+    jwks: Option<crate::JwkSet>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningKey {
+    pub kid: String,
+    pub created_at: u64,
+    pub active: bool,
+    pub x: String,
+    pub y: String,
+    private_pem: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Client {
+    pub client_id: String,
+    pub name: String,
+    // sha256(secret) dạng hex; secret chỉ hiển thị một lần khi tạo/xoay
+    pub secret_hash: String,
+    pub scopes: Vec<String>,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct IssuerConfig {
+    pub enabled: bool,
+    pub issuer: String,
+    pub audience: String,
+    pub token_ttl_secs: u64,
+    pub key_rotation_days: u64,
+    // URL công khai (vd "https://api.example.com") cho openid-configuration; rỗng = suy ra từ Host
+    pub public_base_url: String,
+}
+
+impl IssuerConfig {
+    pub fn from_extras(extras: Option<&Value>) -> Self {
+        let get = |k: &str| extras.and_then(|o| o.get(k));
+        let get_str = |k: &str, d: &str| {
+            get(k).and_then(|v| v.as_str()).map(|s| s.trim()).filter(|s| !s.is_empty()).unwrap_or(d).to_string()
+        };
+        Self {
+            enabled: get("local_issuer_enabled").and_then(|v| v.as_bool()).unwrap_or(false),
+            issuer: get_str("local_issuer", "rust-fastapi"),
+            audience: get_str("token_audience", ""),
+            token_ttl_secs: get("token_ttl_secs").and_then(|v| v.as_u64()).unwrap_or(3600).max(60),
+            key_rotation_days: get("key_rotation_days").and_then(|v| v.as_u64()).unwrap_or(30),
+            public_base_url: get_str("public_base_url", "").trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+// Lỗi theo RFC 6749 §5.2
+#[derive(Debug, Clone)]
+pub struct TokenError {
+    pub error: &'static str,
+    pub description: String,
+}
+
+impl TokenError {
+    fn new(error: &'static str, description: impl Into<String>) -> Self {
+        Self { error, description: description.into() }
+    }
+
+    // invalid_client -> 401, còn lại -> 400
+    pub fn status(&self) -> u16 {
+        if self.error == "invalid_client" { 401 } else { 400 }
+    }
+
+    pub fn body(&self) -> Value {
+        json!({ "error": self.error, "error_description": self.description })
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn random_hex(n: usize) -> String {
+    let mut buf = vec![0u8; n];
+    OsRng.fill_bytes(&mut buf);
+    hex::encode(buf)
+}
+
+fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+fn read_list<T: for<'de> Deserialize<'de>>(path: &str) -> Vec<T> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|t| serde_json::from_str(&t).ok())
+        .unwrap_or_default()
+}
+
+// Ghi nguyên tử; file chứa khóa bí mật nên chỉ owner đọc được (unix)
+fn write_private(path: &str, value: &impl Serialize) -> std::io::Result<()> {
+    std::fs::create_dir_all(ISSUER_DIR)?;
+    let text = serde_json::to_string_pretty(value).map_err(std::io::Error::other)?;
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, text)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::fs::rename(&tmp, Path::new(path))
+}
+
+// ---- Signing keys ----
+
+fn key_set() -> Arc<KeySet> {
+    if let Some(set) = KEYS_CACHE.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return set.clone();
+    }
+    let keys: Vec<SigningKey> = read_list(KEYS_FILE);
+    let jwks = serde_json::from_value(jwks_of(&keys)).ok();
+    let set = Arc::new(KeySet { keys, jwks });
+    *KEYS_CACHE.write().unwrap_or_else(|e| e.into_inner()) = Some(set.clone());
+    set
+}
+
+fn load_keys() -> Vec<SigningKey> {
+    key_set().keys.clone()
+}
+
+fn key_expired(k: &SigningKey, cfg: &IssuerConfig) -> bool {
+    let max_age = cfg.key_rotation_days.saturating_mul(86_400);
+    max_age > 0 && now_secs().saturating_sub(k.created_at) >= max_age
+}
+
+fn generate_key() -> std::io::Result<SigningKey> {
+    let secret = p256::SecretKey::random(&mut OsRng);
+    let pem = secret.to_pkcs8_pem(LineEnding::LF).map_err(std::io::Error::other)?;
+    let point = secret.public_key().to_encoded_point(false);
+    let (Some(x), Some(y)) = (point.x(), point.y()) else {
+        return Err(std::io::Error::other("invalid public key"));
+    };
+    Ok(SigningKey {
+        kid: random_hex(8),
+        created_at: now_secs(),
+        active: true,
+        x: URL_SAFE_NO_PAD.encode(x),
+        y: URL_SAFE_NO_PAD.encode(y),
+        private_pem: pem.to_string(),
+    })
+}
+
+// Sinh khóa mới làm khóa ký; các khóa cũ chỉ còn dùng để xác thực
+pub fn rotate_keys() -> std::io::Result<SigningKey> {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    rotate_locked()
+}
+
+// Gọi khi đang giữ STORE_LOCK
+fn rotate_locked() -> std::io::Result<SigningKey> {
+    let mut keys: Vec<SigningKey> = read_list(KEYS_FILE);
+    for k in keys.iter_mut() { k.active = false; }
+    let key = generate_key()?;
+    keys.insert(0, key.clone());
+    keys.truncate(KEEP_KEYS);
+    let res = write_private(KEYS_FILE, &keys);
+    *KEYS_CACHE.write().unwrap_or_else(|e| e.into_inner()) = None;
+    res.map(|_| key)
+}
+
+// Khóa ký hiện tại; tự xoay khi chưa có hoặc đã quá key_rotation_days (0 = không tự xoay).
+// Kiểm tra lại dưới STORE_LOCK để các request đồng thời chỉ xoay một lần (xoay nhiều lần sẽ đẩy
+// khóa vừa ký token ra khỏi KEEP_KEYS)
+pub fn active_key(cfg: &IssuerConfig) -> std::io::Result<SigningKey> {
+    let usable = |keys: &[SigningKey]| keys.iter().find(|k| k.active && !key_expired(k, cfg)).cloned();
+    if let Some(k) = usable(&key_set().keys) { return Ok(k); }
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    *KEYS_CACHE.write().unwrap_or_else(|e| e.into_inner()) = None;
+    match usable(&key_set().keys) {
+        Some(k) => Ok(k),
+        None => rotate_locked(),
+    }
+}
+
+fn jwks_of(keys: &[SigningKey]) -> Value {
+    let keys: Vec<Value> = keys
+        .iter()
+        .map(|k| json!({"kty": "EC", "crv": "P-256", "alg": "ES256", "use": "sig", "kid": k.kid, "x": k.x, "y": k.y}))
+        .collect();
+    json!({ "keys": keys })
+}
+
+// JWKS công khai (không chứa khóa bí mật)
+pub fn public_jwks() -> Value {
+    jwks_of(&key_set().keys)
+}
+
+// Dùng trên mỗi lần xác thực JWT: lấy từ cache, không đọc lại file
+pub fn local_jwks() -> Option<crate::JwkSet> {
+    key_set().jwks.clone()
+}
+
+pub fn key_summaries() -> Vec<Value> {
+    load_keys()
+        .iter()
+        .map(|k| json!({"kid": k.kid, "created_at": k.created_at, "active": k.active}))
+        .collect()
+}
+
+// ---- Clients ----
+
+pub fn list_clients() -> Vec<Client> {
+    read_list(CLIENTS_FILE)
+}
+
+// Tạo client mới; trả về (client, secret) - secret không được lưu dạng rõ
+pub fn create_client(client_id: Option<&str>, name: &str, scopes: Vec<String>) -> Result<(Client, String), String> {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut clients = list_clients();
+    let client_id = client_id.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).unwrap_or_else(|| random_hex(8));
+    if clients.iter().any(|c| c.client_id == client_id) {
+        return Err(format!("client {} already exists", client_id));
+    }
+    let secret = random_hex(24);
+    let client = Client {
+        client_id,
+        name: name.to_string(),
+        secret_hash: hash_secret(&secret),
+        scopes,
+        created_at: now_secs(),
+    };
+    clients.push(client.clone());
+    write_private(CLIENTS_FILE, &clients).map_err(|e| e.to_string())?;
+    Ok((client, secret))
+}
+
+pub fn delete_client(client_id: &str) -> Result<(), String> {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut clients = list_clients();
+    let before = clients.len();
+    clients.retain(|c| c.client_id != client_id);
+    if clients.len() == before { return Err(format!("client {} not found", client_id)); }
+    write_private(CLIENTS_FILE, &clients).map_err(|e| e.to_string())
+}
+
+pub fn rotate_client_secret(client_id: &str) -> Result<String, String> {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut clients = list_clients();
+    let Some(c) = clients.iter_mut().find(|c| c.client_id == client_id) else {
+        return Err(format!("client {} not found", client_id));
+    };
+    let secret = random_hex(24);
+    c.secret_hash = hash_secret(&secret);
+    write_private(CLIENTS_FILE, &clients).map_err(|e| e.to_string())?;
+    Ok(secret)
+}
+
+pub fn authenticate_client(client_id: &str, secret: &str) -> Option<Client> {
+    let expected = hash_secret(secret);
+    list_clients().into_iter().find(|c| {
+        c.client_id == client_id && module_utils::constant_time_eq(c.secret_hash.as_bytes(), expected.as_bytes())
+    })
+}
+
+// ---- Token issuing ----
+
+pub fn issue_token(cfg: &IssuerConfig, subject: &str, scopes: &[String], extra: Map<String, Value>, ttl_secs: u64) -> std::io::Result<String> {
+    let key = active_key(cfg)?;
+    let now = now_secs();
+    let mut claims = extra;
+    claims.insert("iss".into(), json!(cfg.issuer));
+    claims.insert("sub".into(), json!(subject));
+    claims.insert("iat".into(), json!(now));
+    claims.insert("nbf".into(), json!(now));
+    claims.insert("exp".into(), json!(now + ttl_secs));
+    claims.insert("jti".into(), json!(random_hex(12)));
+    if !scopes.is_empty() { claims.insert("scope".into(), json!(scopes.join(" "))); }
+    if !cfg.audience.is_empty() { claims.insert("aud".into(), json!(cfg.audience)); }
+
+    let mut header = Header::new(Algorithm::ES256);
+    header.kid = Some(key.kid.clone());
+    let enc = EncodingKey::from_ec_pem(key.private_pem.as_bytes()).map_err(std::io::Error::other)?;
+    encode(&header, &Value::Object(claims), &enc).map_err(std::io::Error::other)
+}
+
+// grant_type=client_credentials (RFC 6749 §4.4); scope yêu cầu phải nằm trong scope được cấp cho client
+pub fn client_credentials(cfg: &IssuerConfig, client_id: &str, secret: &str, scope: Option<&str>) -> Result<Value, TokenError> {
+    let client = authenticate_client(client_id, secret)
+        .ok_or_else(|| TokenError::new("invalid_client", "client authentication failed"))?;
+    let requested: Vec<String> = scope
+        .map(|s| s.split_whitespace().map(|x| x.to_string()).collect())
+        .unwrap_or_default();
+    let granted = if requested.is_empty() {
+        client.scopes.clone()
+    } else {
+        if let Some(bad) = requested.iter().find(|s| !client.scopes.contains(s)) {
+            return Err(TokenError::new("invalid_scope", format!("scope {} not allowed for client", bad)));
+        }
+        requested
+    };
+    let mut extra = Map::new();
+    extra.insert("client_id".into(), json!(client.client_id));
+    let token = issue_token(cfg, &client.client_id, &granted, extra, cfg.token_ttl_secs)
+        .map_err(|e| TokenError::new("server_error", e.to_string()))?;
+    Ok(json!({
+        "access_token": token,
+        "token_type": "Bearer",
+        "expires_in": cfg.token_ttl_secs,
+        "scope": granted.join(" "),
+    }))
+}
+
+// Authorization: Basic base64(client_id:client_secret) (client_secret_basic)
+pub fn parse_basic_auth(header: Option<&str>) -> Option<(String, String)> {
+    let v = header?.trim();
+    let (scheme, data) = v.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") { return None; }
+    let decoded = base64::engine::general_purpose::STANDARD.decode(data.trim()).ok()?;
+    let text = String::from_utf8(decoded).ok()?;
+    let (id, secret) = text.split_once(':')?;
+    Some((id.to_string(), secret.to_string()))
+}
+
+pub fn unsupported_grant(grant_type: &str) -> TokenError {
+    TokenError::new("unsupported_grant_type", format!("grant_type {} is not supported", grant_type))
+}
+
+pub fn invalid_request(msg: &str) -> TokenError {
+    TokenError::new("invalid_request", msg)
+}
+
+// base_url: gốc URL công khai của server (vd: http://localhost:3000)
+pub fn openid_configuration(cfg: &IssuerConfig, base_url: &str) -> Value {
+    let base = base_url.trim_end_matches('/');
+    json!({
+        "issuer": cfg.issuer,
+        "token_endpoint": format!("{}/oauth2/token", base),
+        "jwks_uri": format!("{}/.well-known/jwks.json", base),
+        "grant_types_supported": ["client_credentials"],
+        "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post"],
+        "id_token_signing_alg_values_supported": ["ES256"],
+        "response_types_supported": ["token"],
+        "scopes_supported": list_clients().into_iter().flat_map(|c| c.scopes).collect::<std::collections::BTreeSet<String>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_requests_rotate_once_and_huge_rotation_days_do_not_overflow() {
+        let _ = std::fs::remove_dir_all(ISSUER_DIR);
+        *KEYS_CACHE.write().unwrap() = None;
+        let cfg = IssuerConfig::from_extras(Some(&json!({"key_rotation_days": u64::MAX})));
+        let kids: Vec<String> = (0..8)
+            .map(|_| std::thread::spawn({
+                let cfg = cfg.clone();
+                move || active_key(&cfg).unwrap().kid
+            }))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect();
+        assert!(kids.iter().all(|k| *k == kids[0]));
+        assert_eq!(key_summaries().len(), 1);
+        // JWKS được cache và cập nhật sau khi xoay
+        assert_eq!(local_jwks().unwrap().keys.len(), 1);
+        let next = rotate_keys().unwrap();
+        assert_eq!(local_jwks().unwrap().keys.len(), 2);
+        assert_eq!(active_key(&cfg).unwrap().kid, next.kid);
+        let _ = std::fs::remove_dir_all(ISSUER_DIR);
+        // Chỉ xóa thư mục cha nếu rỗng (test chạy trong thư mục crate)
+        let _ = std::fs::remove_dir("./admin/config");
+        let _ = std::fs::remove_dir("./admin");
+    }
+}
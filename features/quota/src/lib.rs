@@ -0,0 +1,432 @@
+#![allow(non_snake_case)]
+#[cfg(feature = "plugin")] use libc::c_char;
+#[cfg(feature = "plugin")] use std::ffi::CString;
+use module_utils::{best_route_match, route_matches, ConfigCache};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// C-ABI symbol used by the dynamic loader to identify the feature
+#[cfg(feature = "plugin")]
+#[no_mangle]
+pub extern "C" fn feature_name_quota() -> *mut c_char {
+    CString::new("quota").unwrap().into_raw()
+}
+
+// ---- Pure Rust logic below (used by the main app via rlib) ----
+
+// Bộ đếm được ghi định kỳ xuống file để không mất khi restart
+pub const USAGE_FILE: &str = "./admin/config/quota_usage.json";
+// Số client tối đa có bộ đếm cùng lúc (client mới khi đầy bị từ chối, không đẩy bộ đếm đang có ra)
+pub const DEFAULT_MAX_CLIENTS: usize = 100_000;
+
+pub const HEADER_LIMIT: &str = "x-quota-limit";
+pub const HEADER_REMAINING: &str = "x-quota-remaining";
+pub const HEADER_RESET: &str = "x-quota-reset";
+pub const HEADER_PERIOD: &str = "x-quota-period";
+
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// ---- Kỳ tính quota theo lịch (đầu ngày / đầu tháng theo UTC + utc_offset_minutes) ----
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Day,
+    Month,
+}
+
+// Số ngày kể từ 1970-01-01 <-> (năm, tháng, ngày) theo lịch Gregory
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = if m > 2 { m - 3 } else { m + 9 } as i64;
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + if m <= 2 { 1 } else { 0 }, m, d)
+}
+
+impl Period {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "day" | "daily" => Some(Self::Day),
+            "month" | "monthly" => Some(Self::Month),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Month => "month",
+        }
+    }
+
+    // (nhãn kỳ hiện tại vd "2026-10-18" / "2026-10", unix time bắt đầu kỳ kế tiếp)
+    pub fn bounds(&self, now: u64, utc_offset_secs: i64) -> (String, u64) {
+        let day = (now as i64 + utc_offset_secs).div_euclid(86_400);
+        let (y, m, d) = civil_from_days(day);
+        let (label, next_day) = match self {
+            Self::Day => (format!("{:04}-{:02}-{:02}", y, m, d), day + 1),
+            Self::Month => {
+                let (ny, nm) = if m == 12 { (y + 1, 1) } else { (y, m + 1) };
+                (format!("{:04}-{:02}", y, m), days_from_civil(ny, nm, 1))
+            }
+        };
+        (label, (next_day * 86_400 - utc_offset_secs).max(0) as u64)
+    }
+}
+
+// ---- Cấu hình (feature_extras.quota) ----
+
+pub type Limits = Vec<(Period, u64)>;
+
+// {"day": 10000, "month": 200000}; 0 = không giới hạn kỳ đó
+fn parse_limits(v: Option<&Value>) -> Limits {
+    let mut out: Limits = v
+        .and_then(|v| v.as_object())
+        .map(|o| {
+            o.iter()
+                .filter_map(|(k, n)| Some((Period::parse(k)?, n.as_u64()?)))
+                .collect()
+        })
+        .unwrap_or_default();
+    out.sort();
+    out
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct QuotaConfig {
+    // Quota mặc định cho mọi client
+    pub limits: Limits,
+    // Quota riêng theo client ("key:<api key id>", "sub:<user>", "ip:<addr>"); ghi đè từng kỳ của mặc định
+    pub client_limits: HashMap<String, Limits>,
+    // Chi phí mỗi request theo route ("/greet/*": 5); mặc định 1, 0 = miễn phí
+    pub route_costs: HashMap<String, u64>,
+    // Chỉ tính quota cho các route này; rỗng = mọi route
+    pub routes: Vec<String>,
+    // Đếm theo ai (cú pháp như rate_limit: api_key, user, ip, header:<Name>)
+    pub key: String,
+    pub utc_offset_secs: i64,
+    pub max_clients: usize,
+}
+
+impl QuotaConfig {
+    pub fn from_extras(extras: Option<&Value>) -> Self {
+        let get = |k: &str| extras.and_then(|o| o.get(k));
+        let client_limits = get("client_limits")
+            .and_then(|v| v.as_object())
+            .map(|o| o.iter().map(|(c, l)| (c.clone(), parse_limits(Some(l)))).collect())
+            .unwrap_or_default();
+        let route_costs = get("route_costs")
+            .and_then(|v| v.as_object())
+            .map(|o| o.iter().filter_map(|(r, n)| Some((r.clone(), n.as_u64()?))).collect())
+            .unwrap_or_default();
+        let routes = get("routes")
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().filter_map(|x| x.as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default();
+        Self {
+            limits: parse_limits(get("limits")),
+            client_limits,
+            route_costs,
+            routes,
+            key: get("key").and_then(|v| v.as_str()).unwrap_or("api_key").to_string(),
+            utc_offset_secs: get("utc_offset_minutes").and_then(|v| v.as_i64()).unwrap_or(0).clamp(-14 * 60, 14 * 60) * 60,
+            max_clients: get("max_clients").and_then(|v| v.as_u64()).filter(|n| *n > 0).map(|n| n as usize).unwrap_or(DEFAULT_MAX_CLIENTS),
+        }
+    }
+
+    pub fn applies_to(&self, path: &str) -> bool {
+        self.routes.is_empty() || self.routes.iter().any(|r| route_matches(r, path))
+    }
+
+    // Pattern cụ thể nhất thắng (chính xác trước, rồi prefix "/x/*" dài nhất)
+    pub fn cost(&self, path: &str) -> u64 {
+        best_route_match(self.route_costs.iter().map(|(r, c)| (r.as_str(), *c)), path).unwrap_or(1)
+    }
+
+    // Quota hiệu lực của client (bỏ các kỳ có limit = 0)
+    pub fn limits_for(&self, client: &str) -> Limits {
+        let mut out = self.limits.clone();
+        if let Some(own) = self.client_limits.get(client) {
+            for (p, n) in own {
+                match out.iter_mut().find(|(q, _)| q == p) {
+                    Some(slot) => slot.1 = *n,
+                    None => out.push((*p, *n)),
+                }
+            }
+        }
+        out.retain(|(_, n)| *n > 0);
+        out.sort();
+        out
+    }
+}
+
+// Cấu hình được cache; chỉ phân tích lại (giới hạn và chi phí route) khi feature_extras.quota thay đổi
+static CACHE: ConfigCache<QuotaConfig> = ConfigCache::new();
+
+pub fn config_for(extras: Option<&Value>) -> Arc<QuotaConfig> {
+    CACHE.get(extras, QuotaConfig::from_extras)
+}
+
+// ---- Kết quả ----
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PeriodStatus {
+    pub period: Period,
+    // Nhãn kỳ, vd "2026-10-18"
+    pub label: String,
+    pub limit: u64,
+    pub used: u64,
+    pub remaining: u64,
+    // Unix time lúc quota được đặt lại
+    pub reset_at: u64,
+}
+
+impl PeriodStatus {
+    // Còn đủ quota cho request có chi phí `cost` (phép cộng tràn coi như vượt)
+    fn fits(&self, cost: u64) -> bool {
+        self.used.checked_add(cost).is_some_and(|n| n <= self.limit)
+    }
+
+    pub fn reset_after(&self, now: u64) -> u64 {
+        self.reset_at.saturating_sub(now).max(1)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaDecision {
+    pub allowed: bool,
+    pub cost: u64,
+    pub periods: Vec<PeriodStatus>,
+}
+
+impl QuotaDecision {
+    // Kỳ quyết định: kỳ bị vượt (khi từ chối) hoặc kỳ còn ít nhất
+    fn binding(&self) -> Option<&PeriodStatus> {
+        if !self.allowed {
+            if let Some(p) = self.periods.iter().find(|p| !p.fits(self.cost)) { return Some(p); }
+        }
+        self.periods.iter().min_by_key(|p| (p.remaining, p.reset_at))
+    }
+
+    pub fn headers(&self, now: u64) -> Vec<(&'static str, String)> {
+        let Some(p) = self.binding() else { return Vec::new() };
+        vec![
+            (HEADER_LIMIT, p.limit.to_string()),
+            (HEADER_REMAINING, p.remaining.to_string()),
+            (HEADER_RESET, p.reset_after(now).to_string()),
+            (HEADER_PERIOD, p.period.as_str().to_string()),
+        ]
+    }
+
+    pub fn retry_after_secs(&self, now: u64) -> Option<u64> {
+        if self.allowed { return None; }
+        self.binding().map(|p| p.reset_after(now))
+    }
+
+    // Body application/problem+json (RFC 9457) khi hết quota
+    pub fn problem(&self, path: &str, now: u64) -> Value {
+        let detail = match self.binding() {
+            Some(p) => format!("{} quota of {} exhausted ({} used, request cost {})", p.period.as_str(), p.limit, p.used, self.cost),
+            None => "Quota exhausted".to_string(),
+        };
+        serde_json::json!({
+            "type": "about:blank",
+            "title": "Quota Exceeded",
+            "status": 429,
+            "detail": detail,
+            "instance": path,
+            "retry_after": self.retry_after_secs(now),
+        })
+    }
+}
+
+// Đã đủ max_clients client có bộ đếm: client mới bị từ chối (503) cho tới khi bộ đếm kỳ cũ được dọn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooManyClients;
+
+impl TooManyClients {
+    pub fn problem(&self, path: &str) -> Value {
+        serde_json::json!({
+            "type": "about:blank",
+            "title": "Quota Unavailable",
+            "status": 503,
+            "detail": "Quota is tracking the maximum number of clients (max_clients)",
+            "instance": path,
+        })
+    }
+}
+
+// ---- Bộ đếm ----
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Counter {
+    label: String,
+    used: u64,
+}
+
+type Usage = HashMap<String, HashMap<Period, Counter>>;
+
+static USAGE: Lazy<Mutex<Usage>> = Lazy::new(|| {
+    let usage = std::fs::read_to_string(USAGE_FILE)
+        .ok()
+        .and_then(|t| serde_json::from_str(&t).ok())
+        .unwrap_or_default();
+    Mutex::new(usage)
+});
+static DIRTY: AtomicBool = AtomicBool::new(false);
+
+fn status_of(counters: Option<&HashMap<Period, Counter>>, limits: &Limits, now: u64, offset: i64) -> Vec<PeriodStatus> {
+    limits
+        .iter()
+        .map(|(period, limit)| {
+            let (label, reset_at) = period.bounds(now, offset);
+            // Bộ đếm của kỳ trước coi như 0
+            let used = counters
+                .and_then(|c| c.get(period))
+                .filter(|c| c.label == label)
+                .map(|c| c.used)
+                .unwrap_or(0);
+            PeriodStatus { period: *period, label, limit: *limit, used, remaining: limit.saturating_sub(used), reset_at }
+        })
+        .collect()
+}
+
+// Trừ quota cho một request; Ok(None) nếu client không bị giới hạn.
+// Bị từ chối thì không trừ (request vượt quota không tiêu tốn gì thêm).
+// Client chưa có bộ đếm khi đã đủ max_clients -> Err (bộ đếm kỳ cũ được dọn ở flush định kỳ)
+pub fn consume(client: &str, path: &str, cfg: &QuotaConfig, now: u64) -> Result<Option<QuotaDecision>, TooManyClients> {
+    let limits = cfg.limits_for(client);
+    if limits.is_empty() { return Ok(None); }
+    let cost = cfg.cost(path);
+    let mut usage = USAGE.lock();
+    let mut periods = status_of(usage.get(client), &limits, now, cfg.utc_offset_secs);
+    let allowed = periods.iter().all(|p| p.fits(cost));
+    if allowed && cost > 0 {
+        if !usage.contains_key(client) && usage.len() >= cfg.max_clients { return Err(TooManyClients); }
+        let counters = usage.entry(client.to_string()).or_default();
+        for p in periods.iter_mut() {
+            let c = counters.entry(p.period).or_default();
+            if c.label != p.label { *c = Counter { label: p.label.clone(), used: 0 }; }
+            c.used = c.used.saturating_add(cost);
+            p.used = c.used;
+            p.remaining = p.limit.saturating_sub(c.used);
+        }
+        DIRTY.store(true, Ordering::Relaxed);
+    }
+    Ok(Some(QuotaDecision { allowed, cost, periods }))
+}
+
+// Trạng thái quota của client (không trừ)
+pub fn status(client: &str, cfg: &QuotaConfig, now: u64) -> Vec<PeriodStatus> {
+    status_of(USAGE.lock().get(client), &cfg.limits_for(client), now, cfg.utc_offset_secs)
+}
+
+// Tất cả client đang có bộ đếm trong kỳ hiện tại, sắp theo tên
+pub fn report(cfg: &QuotaConfig, now: u64) -> Vec<Value> {
+    let usage = USAGE.lock();
+    let mut clients: Vec<&String> = usage.keys().collect();
+    clients.sort();
+    clients
+        .into_iter()
+        .map(|c| serde_json::json!({ "client": c, "periods": status_of(usage.get(c), &cfg.limits_for(c), now, cfg.utc_offset_secs) }))
+        .collect()
+}
+
+// Xóa bộ đếm của client; trả về false nếu không có
+pub fn reset(client: &str) -> bool {
+    let removed = USAGE.lock().remove(client).is_some();
+    if removed { DIRTY.store(true, Ordering::Relaxed); }
+    removed
+}
+
+// Bỏ bộ đếm của kỳ đã qua rồi ghi file nếu có thay đổi (ghi nguyên tử)
+pub fn flush(cfg: &QuotaConfig, now: u64) -> std::io::Result<bool> {
+    let text = {
+        let mut usage = USAGE.lock();
+        let before: usize = usage.values().map(|c| c.len()).sum();
+        for counters in usage.values_mut() {
+            counters.retain(|p, c| p.bounds(now, cfg.utc_offset_secs).0 == c.label);
+        }
+        usage.retain(|_, c| !c.is_empty());
+        let after: usize = usage.values().map(|c| c.len()).sum();
+        if !DIRTY.swap(false, Ordering::Relaxed) && before == after { return Ok(false); }
+        serde_json::to_string_pretty(&*usage).map_err(std::io::Error::other)?
+    };
+    if let Some(dir) = std::path::Path::new(USAGE_FILE).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp = format!("{}.tmp", USAGE_FILE);
+    let res = std::fs::write(&tmp, text).and_then(|_| std::fs::rename(&tmp, USAGE_FILE));
+    // Ghi lỗi thì để lần sau ghi lại
+    if res.is_err() { DIRTY.store(true, Ordering::Relaxed); }
+    res.map(|_| true)
+}
+
+// Manifest để UI Admin tự động sinh cấu hình theo code của feature
+#[cfg(feature = "plugin")]
+#[no_mangle]
+pub extern "C" fn feature_manifest_quota() -> *mut c_char {
+    let json = r#"{
+        "name": "quota",
+        "description": "Quota theo ngày/tháng cho từng API key hoặc client, bộ đếm lưu file, chi phí theo route",
+        "settings": [
+          {"key": "limits", "type": "json", "label": "Default Quota (day, month; 0 = unlimited)", "default": {}, "example": {"day": 10000, "month": 200000}},
+          {"key": "client_limits", "type": "json", "label": "Per-Client Quota (key:<id>, sub:<user>, ip:<addr>)", "default": {}, "example": {"key:3f9a1c2b4d5e": {"day": 50000}}},
+          {"key": "route_costs", "type": "json", "label": "Per-Route Cost (default 1, 0 = free)", "default": {}, "example": {"/greet/*": 5}},
+          {"key": "routes", "type": "route_list", "label": "Routes counted (empty = all)", "default": []},
+          {"key": "key", "type": "string", "label": "Client Key (api_key, user, ip, header:<Name>)", "default": "api_key"},
+          {"key": "utc_offset_minutes", "type": "number", "label": "Reset Time Zone (UTC offset, minutes)", "default": 0},
+          {"key": "max_clients", "type": "number", "label": "Max Tracked Clients (new clients get 503 when full)", "default": 100000}
+        ]
+    }"#;
+    CString::new(json).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn new_clients_are_rejected_when_full_and_counters_saturate() {
+        let cfg = QuotaConfig::from_extras(Some(&json!({
+            "limits": {"day": u64::MAX},
+            "route_costs": {"/big": u64::MAX},
+            "max_clients": 2
+        })));
+        let now = 1_760_000_000;
+        assert!(consume("test:a", "/big", &cfg, now).unwrap().unwrap().allowed);
+        // used + cost tràn u64 được coi là vượt quota, không bị quay vòng về số nhỏ
+        let d = consume("test:a", "/big", &cfg, now).unwrap().unwrap();
+        assert!(!d.allowed && d.periods[0].used == u64::MAX);
+        assert!(consume("test:b", "/x", &cfg, now).unwrap().unwrap().allowed);
+        assert_eq!(consume("test:c", "/x", &cfg, now).unwrap_err(), TooManyClients);
+        // Client đã có bộ đếm vẫn được tính bình thường
+        assert!(consume("test:b", "/x", &cfg, now).unwrap().unwrap().allowed);
+        assert!(reset("test:b"));
+        assert!(consume("test:c", "/x", &cfg, now).unwrap().unwrap().allowed);
+    }
+}
@@ -0,0 +1,131 @@
+// Nơi lưu trạng thái rate limit:
+// - memory: ShardedStore trong tiến trình (mỗi instance đếm riêng)
+// - redis: dùng chung giữa các instance qua máy chủ nói giao thức Redis (Redis, Valkey, KeyDB, Dragonfly...)
+// Khi backend lỗi, failure_mode quyết định cho qua (open) hay từ chối (closed)
+use crate::redis::{RedisBackend, RedisConfig};
+use crate::store::ShardedStore;
+use crate::{Decision, Policy};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum BackendError {
+    // Lỗi vừa xảy ra khi gọi backend (nên ghi log)
+    Failed(String),
+    // Backend đang trong thời gian chờ thử lại sau lỗi trước đó (đã ghi log)
+    Unavailable(String),
+    // Cấu hình backend sai
+    Config(String),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Failed(m) => write!(f, "{}", m),
+            BackendError::Unavailable(m) => write!(f, "{} (backing off)", m),
+            BackendError::Config(m) => write!(f, "invalid config: {}", m),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+pub trait Backend: Send + Sync {
+    fn name(&self) -> &'static str;
+    // true nếu check() có thể chặn (I/O mạng) -> app nên gọi ngoài luồng async
+    fn is_remote(&self) -> bool { false }
+    // Kiểm tra và ghi nhận một request cho key theo chính sách
+    fn check(&self, key: &str, policy: &Policy) -> Result<Decision, BackendError>;
+    // Trạng thái để hiển thị trên admin
+    fn status(&self) -> Value { serde_json::json!({ "backend": self.name() }) }
+}
+
+impl Backend for ShardedStore {
+    fn name(&self) -> &'static str { "memory" }
+
+    fn check(&self, key: &str, policy: &Policy) -> Result<Decision, BackendError> {
+        Ok(ShardedStore::check(self, key, policy))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FailureMode {
+    // Backend lỗi thì cho request đi qua (ưu tiên tính sẵn sàng)
+    #[default]
+    Open,
+    // Backend lỗi thì trả 503 (ưu tiên bảo vệ upstream)
+    Closed,
+}
+
+impl FailureMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "open" | "allow" | "fail_open" => Some(Self::Open),
+            "closed" | "deny" | "fail_closed" => Some(Self::Closed),
+            _ => None,
+        }
+    }
+
+    pub fn from_extras(extras: Option<&Value>) -> Self {
+        extras
+            .and_then(|o| o.get("failure_mode"))
+            .and_then(|v| v.as_str())
+            .and_then(Self::parse)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendKind {
+    Memory,
+    Redis(RedisConfig),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackendConfig {
+    pub kind: BackendKind,
+    pub failure_mode: FailureMode,
+}
+
+impl BackendConfig {
+    // Đọc từ feature_extras.rate_limit: backend, redis_url, redis_prefix, redis_timeout_ms, failure_mode
+    pub fn from_extras(extras: Option<&Value>) -> Result<Self, BackendError> {
+        let get = |k: &str| extras.and_then(|o| o.get(k));
+        let failure_mode = FailureMode::from_extras(extras);
+        let kind = match get("backend").and_then(|v| v.as_str()).unwrap_or("memory").trim() {
+            "" | "memory" => BackendKind::Memory,
+            "redis" => {
+                let url = get("redis_url").and_then(|v| v.as_str()).unwrap_or("redis://127.0.0.1:6379");
+                let mut cfg = RedisConfig::parse(url)?;
+                if let Some(p) = get("redis_prefix").and_then(|v| v.as_str()) { cfg.prefix = p.to_string(); }
+                if let Some(ms) = get("redis_timeout_ms").and_then(|v| v.as_u64()).filter(|n| *n > 0) { cfg.timeout_ms = ms; }
+                BackendKind::Redis(cfg)
+            }
+            other => return Err(BackendError::Config(format!("unknown rate limit backend '{}'", other))),
+        };
+        Ok(Self { kind, failure_mode })
+    }
+}
+
+// Backend đang dùng, tạo lại khi cấu hình đổi (giữ pool kết nối giữa các request)
+type Active = Option<(BackendKind, Arc<dyn Backend>)>;
+static ACTIVE: Lazy<Mutex<Active>> = Lazy::new(|| Mutex::new(None));
+
+pub fn backend_for(cfg: &BackendConfig) -> Arc<dyn Backend> {
+    let memory = || -> Arc<dyn Backend> { Arc::new(crate::MemoryBackend) };
+    if cfg.kind == BackendKind::Memory { return memory(); }
+    let mut active = ACTIVE.lock();
+    if let Some((kind, b)) = active.as_ref() {
+        if *kind == cfg.kind { return b.clone(); }
+    }
+    let b: Arc<dyn Backend> = match &cfg.kind {
+        BackendKind::Memory => memory(),
+        BackendKind::Redis(rc) => Arc::new(RedisBackend::new(rc.clone())),
+    };
+    *active = Some((cfg.kind.clone(), b.clone()));
+    b
+}
+
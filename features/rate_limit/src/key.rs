@@ -0,0 +1,59 @@
+// Chiến lược xác định "ai" đang gọi để đếm rate limit
+use std::net::IpAddr;
+
+// Giá trị header dài hơn mức này bị cắt để key không phình bộ nhớ
+const MAX_HEADER_KEY_LEN: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum KeyStrategy {
+    // IP client (ClientIp do app giải quyết; chỉ đọc header proxy khi kết nối từ trusted_proxies)
+    #[default]
+    Ip,
+    // id của API key đã xác thực (feature api_key)
+    ApiKey,
+    // subject của Principal đã xác thực (JWT sub hoặc API key)
+    User,
+    // Giá trị một header tùy ý, vd "header:X-Tenant-Id"
+    Header(String),
+}
+
+impl KeyStrategy {
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some(name) = s.strip_prefix("header:").map(|n| n.trim()).filter(|n| !n.is_empty()) {
+            return Some(Self::Header(name.to_ascii_lowercase()));
+        }
+        match s.to_ascii_lowercase().replace('-', "_").as_str() {
+            // forwarded_ip: tên cũ, giờ mọi chiến lược IP đều qua cùng bộ giải quyết ClientIp
+            "ip" | "forwarded_ip" | "xff" => Some(Self::Ip),
+            "api_key" => Some(Self::ApiKey),
+            "user" | "subject" | "sub" => Some(Self::User),
+            _ => None,
+        }
+    }
+}
+
+// Thông tin của request dùng để dựng key
+pub struct KeyInput<'a> {
+    // IP client đã giải quyết (module_utils::ClientIp)
+    pub client_ip: Option<IpAddr>,
+    pub api_key_id: Option<&'a str>,
+    pub subject: Option<&'a str>,
+}
+
+// Key đếm rate limit, có tiền tố theo loại; thiếu thông tin (chưa xác thực, thiếu header) thì quay về IP client
+pub fn client_key<'a>(strategy: &KeyStrategy, input: &KeyInput<'a>, header: impl Fn(&str) -> Option<&'a str>) -> String {
+    let ip_key = |ip: Option<IpAddr>| format!("ip:{}", ip.map(|i| i.to_string()).unwrap_or_else(|| "unknown".into()));
+    match strategy {
+        KeyStrategy::Ip => ip_key(input.client_ip),
+        KeyStrategy::ApiKey => input.api_key_id.map(|id| format!("key:{}", id)).unwrap_or_else(|| ip_key(input.client_ip)),
+        KeyStrategy::User => input.subject.map(|s| format!("sub:{}", s)).unwrap_or_else(|| ip_key(input.client_ip)),
+        KeyStrategy::Header(name) => match header(name).map(|v| v.trim()).filter(|v| !v.is_empty()) {
+            Some(v) => {
+                let end = v.char_indices().map(|(i, c)| i + c.len_utf8()).take_while(|n| *n <= MAX_HEADER_KEY_LEN).last().unwrap_or(0);
+                format!("hdr:{}:{}", name, &v[..end])
+            }
+            None => ip_key(input.client_ip),
+        },
+    }
+}
@@ -1,11 +1,19 @@
 #![allow(non_snake_case)]
 #[cfg(feature = "plugin")] use libc::c_char;
 use once_cell::sync::Lazy;
-use parking_lot::RwLock;
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 #[cfg(feature = "plugin")] use std::ffi::CString;
 
+pub mod backend;
+pub mod key;
+pub mod redis;
+pub mod store;
+pub use backend::{Backend, BackendConfig, BackendError, FailureMode};
+pub use key::{client_key, KeyInput, KeyStrategy};
+pub use store::{ShardedStore, StoreStats};
+
 // C-ABI symbol used by the dynamic loader to identify the feature
 #[cfg(feature = "plugin")]
 #[no_mangle]
@@ -15,27 +23,369 @@ pub extern "C" fn feature_name_rate_limit() -> *mut c_char {
 
 // ---- Pure Rust logic below (used by the main app via rlib) ----
 
-static RATE_LIMIT_MAP: Lazy<RwLock<HashMap<String, (Instant, usize)>>> =
-    Lazy::new(|| RwLock::new(HashMap::new()));
-
 pub fn normalize_path(p: &str) -> String {
     if p == "/" { return "/".to_string(); }
     p.trim_end_matches('/').to_string()
 }
 
-// Returns true if request is allowed under the provided limit for the ip+path key
-pub fn check_allow(ip: &str, path: &str, limit: usize) -> bool {
-    let now = Instant::now();
-    let key = format!("{}|{}", ip, normalize_path(path));
-    let mut map = RATE_LIMIT_MAP.write();
-    let entry = map.entry(key).or_insert((now, 0));
-    let elapsed = now.duration_since(entry.0);
-    if elapsed >= Duration::from_secs(1) {
-        entry.0 = now;
-        entry.1 = 0;
+// fixed_window: đếm theo cửa sổ cố định (cho phép burst gấp đôi ở ranh giới cửa sổ)
+// sliding_window: ước lượng theo trọng số cửa sổ trước + cửa sổ hiện tại (bộ nhớ O(1))
+// sliding_log: lưu thời điểm từng request trong cửa sổ (chính xác, bộ nhớ O(limit))
+// token_bucket: nạp limit token mỗi window, cho phép dồn tối đa burst token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    FixedWindow,
+    #[default]
+    SlidingWindow,
+    SlidingLog,
+    TokenBucket,
+}
+
+impl Algorithm {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "fixed_window" | "fixed" => Some(Self::FixedWindow),
+            "sliding_window" | "sliding" => Some(Self::SlidingWindow),
+            "sliding_log" => Some(Self::SlidingLog),
+            "token_bucket" | "bucket" => Some(Self::TokenBucket),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::FixedWindow => "fixed_window",
+            Self::SlidingWindow => "sliding_window",
+            Self::SlidingLog => "sliding_log",
+            Self::TokenBucket => "token_bucket",
+        }
+    }
+}
+
+// Cửa sổ dài nhất được chấp nhận; giá trị lớn hơn bị kẹp lại (tránh tràn khi cộng vào Instant)
+pub const MAX_WINDOW: Duration = Duration::from_secs(365 * 86_400);
+
+// Số giây ("30") hoặc chuỗi có đơn vị: "500ms", "10s", "5m", "1h", "1d".
+// Số âm hoặc NaN -> None; quá lớn -> MAX_WINDOW (không panic).
+pub fn parse_window(v: &Value) -> Option<Duration> {
+    let secs = match v {
+        Value::Number(n) => n.as_f64()?,
+        Value::String(s) => {
+            let s = s.trim().to_ascii_lowercase();
+            let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(s.len());
+            let (num, unit) = s.split_at(split);
+            let n: f64 = num.parse().ok()?;
+            match unit.trim() {
+                "ms" => n / 1000.0,
+                "" | "s" | "sec" => n,
+                "m" | "min" => n * 60.0,
+                "h" => n * 3600.0,
+                "d" => n * 86_400.0,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    if secs.is_nan() || secs <= 0.0 { return None; }
+    let d = Duration::try_from_secs_f64(secs).unwrap_or(MAX_WINDOW);
+    if d.is_zero() { None } else { Some(d.min(MAX_WINDOW)) }
+}
+
+// Chính sách áp dụng cho một route: tối đa `limit` request mỗi `window`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    pub algorithm: Algorithm,
+    pub limit: u32,
+    pub window: Duration,
+    // Chỉ dùng cho token_bucket: dung lượng tối đa của bucket (mặc định = limit)
+    pub burst: u32,
+    // Đếm theo ai: IP, API key, user, header...
+    pub key: KeyStrategy,
+}
+
+impl Policy {
+    pub fn per_second(limit: u32, algorithm: Algorithm) -> Self {
+        Self { algorithm, limit, window: Duration::from_secs(1), burst: limit, key: KeyStrategy::default() }
+    }
+
+    // Đọc từ object {"algorithm", "limit", "window" | "window_secs", "burst", "key"}; thiếu trường thì lấy theo `base`
+    pub fn from_value(v: &Value, base: &Policy) -> Option<Self> {
+        let obj = v.as_object()?;
+        let algorithm = obj.get("algorithm").and_then(|x| x.as_str()).and_then(Algorithm::parse).unwrap_or(base.algorithm);
+        let limit = obj.get("limit").and_then(|x| x.as_u64()).map(|n| n.min(u32::MAX as u64) as u32).unwrap_or(base.limit);
+        let window = obj.get("window").or_else(|| obj.get("window_secs")).and_then(parse_window).unwrap_or(base.window);
+        let burst = obj.get("burst").and_then(|x| x.as_u64()).filter(|n| *n > 0).map(|n| n.min(u32::MAX as u64) as u32).unwrap_or(limit);
+        let key = obj.get("key").and_then(|x| x.as_str()).and_then(KeyStrategy::parse).unwrap_or_else(|| base.key.clone());
+        Some(Self { algorithm, limit, window, burst: burst.max(1), key })
+    }
+
+    // Sau khoảng không hoạt động này trạng thái của key tương đương trạng thái mới -> có thể bỏ
+    pub fn idle_ttl(&self) -> Duration {
+        match self.algorithm {
+            Algorithm::FixedWindow | Algorithm::SlidingLog => self.window,
+            Algorithm::SlidingWindow => self.window * 2,
+            // burst/limit lớn có thể vượt Duration: kẹp về MAX_WINDOW thay vì panic
+            Algorithm::TokenBucket if self.limit > 0 => {
+                Duration::try_from_secs_f64(self.window.as_secs_f64() * self.burst as f64 / self.limit as f64).unwrap_or(MAX_WINDOW)
+            }
+            Algorithm::TokenBucket => self.window,
+        }
+    }
+
+    // Giá trị RateLimit-Policy, vd "100;w=60" (token bucket kèm burst)
+    pub fn header_value(&self) -> String {
+        let w = ceil_secs(self.window).max(1);
+        match self.algorithm {
+            Algorithm::TokenBucket => format!("{};w={};burst={}", self.limit, w, self.burst),
+            _ => format!("{};w={}", self.limit, w),
+        }
+    }
+
+    // Mô tả ngắn cho người đọc (problem detail, OpenAPI)
+    pub fn describe(&self) -> String {
+        let mut text = format!("{} requests per {:?} ({})", self.limit, self.window, self.algorithm.as_str());
+        if self.algorithm == Algorithm::TokenBucket { text.push_str(&format!(", burst {}", self.burst)); }
+        text
+    }
+
+    // Định danh chính sách; đổi cấu hình thì trạng thái cũ không còn áp dụng
+    fn fingerprint(&self) -> String {
+        format!("{}:{}:{}:{}", self.algorithm.as_str(), self.limit, self.window.as_millis(), self.burst)
     }
-    entry.1 += 1;
-    entry.1 <= limit
+}
+
+// Chọn chính sách cho path theo thứ tự:
+// route_policies (khớp chính xác, rồi prefix "/x/*" dài nhất) -> route_limits (req/s) -> legacy route limit
+// -> chính sách chung (limit + window) -> rps -> legacy rps
+pub fn resolve_policy(extras: Option<&Value>, path: &str, legacy_route: Option<u32>, legacy_rps: u32) -> Policy {
+    let get = |k: &str| extras.and_then(|o| o.get(k));
+    let algorithm = get("algorithm").and_then(|v| v.as_str()).and_then(Algorithm::parse).unwrap_or_default();
+    let rps = get("rps").and_then(|v| v.as_u64()).map(|n| n as u32).unwrap_or(legacy_rps);
+    let mut global = Policy::per_second(rps, algorithm);
+    global.key = get("key").and_then(|v| v.as_str()).and_then(KeyStrategy::parse).unwrap_or_default();
+    // limit/burst = 0 nghĩa là chưa cấu hình
+    if let Some(limit) = get("limit").and_then(|v| v.as_u64()).filter(|n| *n > 0) {
+        global.limit = limit.min(u32::MAX as u64) as u32;
+        global.window = get("window").or_else(|| get("window_secs")).and_then(parse_window).unwrap_or(global.window);
+        global.burst = global.limit;
+    }
+    if let Some(b) = get("burst").and_then(|v| v.as_u64()).filter(|n| *n > 0) { global.burst = (b.min(u32::MAX as u64) as u32).max(1); }
+
+    let path_norm = normalize_path(path);
+    if let Some(map) = get("route_policies").and_then(|v| v.as_object()) {
+        let best = module_utils::best_route_match(map.iter().map(|(pattern, v)| (pattern.as_str(), v)), path);
+        if let Some(p) = best.and_then(|v| Policy::from_value(v, &global)) { return p; }
+    }
+    let route_rps = get("route_limits")
+        .and_then(|v| v.as_object())
+        .and_then(|m| m.get(&path_norm))
+        .and_then(|v| v.as_u64())
+        .map(|n| n as u32)
+        .or(legacy_route);
+    match route_rps {
+        Some(n) => Policy { key: global.key, ..Policy::per_second(n, algorithm) },
+        None => global,
+    }
+}
+
+// Kết quả kiểm tra một request
+#[derive(Debug, Clone, Copy)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    // Thời gian đến khi quota được khôi phục hoàn toàn
+    pub reset_after: Duration,
+    // Khi bị từ chối: thời gian tối thiểu nên chờ trước khi thử lại
+    pub retry_after: Option<Duration>,
+}
+
+// Header theo IETF draft "RateLimit header fields for HTTP"
+pub const HEADER_LIMIT: &str = "ratelimit-limit";
+pub const HEADER_REMAINING: &str = "ratelimit-remaining";
+pub const HEADER_RESET: &str = "ratelimit-reset";
+pub const HEADER_POLICY: &str = "ratelimit-policy";
+
+fn ceil_secs(d: Duration) -> u64 {
+    d.as_secs_f64().ceil() as u64
+}
+
+impl Decision {
+    // Số giây client nên chờ (Retry-After), tối thiểu 1
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        self.retry_after.map(|d| ceil_secs(d).max(1))
+    }
+
+    // RateLimit-Limit / Remaining / Reset / Policy cho cả response được phép và bị từ chối
+    pub fn headers(&self, policy: &Policy) -> Vec<(&'static str, String)> {
+        vec![
+            (HEADER_LIMIT, self.limit.to_string()),
+            (HEADER_REMAINING, self.remaining.to_string()),
+            (HEADER_RESET, ceil_secs(self.reset_after).to_string()),
+            (HEADER_POLICY, policy.header_value()),
+        ]
+    }
+
+    // Body application/problem+json (RFC 9457) khi bị từ chối
+    pub fn problem(&self, policy: &Policy, path: &str) -> Value {
+        serde_json::json!({
+            "type": "about:blank",
+            "title": "Too Many Requests",
+            "status": 429,
+            "detail": format!("Rate limit exceeded: {}", policy.describe()),
+            "instance": path,
+            "retry_after": self.retry_after_secs(),
+        })
+    }
+
+    // Body khi backend lỗi và failure_mode = closed
+    pub fn unavailable_problem(path: &str) -> Value {
+        serde_json::json!({
+            "type": "about:blank",
+            "title": "Service Unavailable",
+            "status": 503,
+            "detail": "Rate limit backend unavailable",
+            "instance": path,
+        })
+    }
+}
+
+pub(crate) enum State {
+    Window { start: Instant, count: u32 },
+    Sliding { start: Instant, prev: u32, count: u32 },
+    Log(VecDeque<Instant>),
+    Bucket { tokens: f64, last: Instant },
+}
+
+impl State {
+    pub(crate) fn new(p: &Policy, now: Instant) -> Self {
+        match p.algorithm {
+            Algorithm::FixedWindow => State::Window { start: now, count: 0 },
+            Algorithm::SlidingWindow => State::Sliding { start: now, prev: 0, count: 0 },
+            Algorithm::SlidingLog => State::Log(VecDeque::new()),
+            Algorithm::TokenBucket => State::Bucket { tokens: p.burst as f64, last: now },
+        }
+    }
+
+    pub(crate) fn check(&mut self, p: &Policy, now: Instant) -> Decision {
+        let w = p.window;
+        let deny = |retry: Duration, reset: Duration| Decision {
+            allowed: false, limit: p.limit, remaining: 0, reset_after: reset, retry_after: Some(retry.max(Duration::from_millis(1))),
+        };
+        match self {
+            State::Window { start, count } => {
+                if now.duration_since(*start) >= w { *start = now; *count = 0; }
+                let reset = w.saturating_sub(now.duration_since(*start));
+                if *count >= p.limit { return deny(reset, reset); }
+                *count += 1;
+                Decision { allowed: true, limit: p.limit, remaining: p.limit - *count, reset_after: reset, retry_after: None }
+            }
+            State::Sliding { start, prev, count } => {
+                let elapsed = now.duration_since(*start);
+                if elapsed >= w {
+                    // Sang cửa sổ mới: cửa sổ liền trước giữ lại số đếm, xa hơn thì bỏ
+                    let windows = (elapsed.as_secs_f64() / w.as_secs_f64()).floor() as u32;
+                    *prev = if windows == 1 { *count } else { 0 };
+                    *count = 0;
+                    *start += w * windows;
+                }
+                let into = now.duration_since(*start).as_secs_f64() / w.as_secs_f64();
+                let estimate = *prev as f64 * (1.0 - into) + *count as f64;
+                let reset = w.saturating_sub(now.duration_since(*start)) + if *prev > 0 { w } else { Duration::ZERO };
+                if estimate + 1.0 > p.limit as f64 {
+                    // Chờ đến khi phần trọng số của cửa sổ trước giảm đủ để có thêm 1 chỗ
+                    let retry = if *prev > 0 && (*count as f64) + 1.0 <= p.limit as f64 {
+                        let target = 1.0 - (p.limit as f64 - *count as f64 - 1.0) / *prev as f64;
+                        Duration::from_secs_f64(((target - into).max(0.0)) * w.as_secs_f64())
+                    } else {
+                        w.saturating_sub(now.duration_since(*start))
+                    };
+                    return deny(retry, reset);
+                }
+                *count += 1;
+                let remaining = (p.limit as f64 - estimate - 1.0).floor().max(0.0) as u32;
+                Decision { allowed: true, limit: p.limit, remaining, reset_after: reset, retry_after: None }
+            }
+            State::Log(log) => {
+                while log.front().is_some_and(|t| now.duration_since(*t) >= w) { log.pop_front(); }
+                let reset = log.front().map(|t| w.saturating_sub(now.duration_since(*t))).unwrap_or(Duration::ZERO);
+                if log.len() as u32 >= p.limit {
+                    return deny(reset, reset);
+                }
+                log.push_back(now);
+                let reset = log.front().map(|t| w.saturating_sub(now.duration_since(*t))).unwrap_or(w);
+                Decision { allowed: true, limit: p.limit, remaining: p.limit - log.len() as u32, reset_after: reset, retry_after: None }
+            }
+            State::Bucket { tokens, last } => {
+                let rate = p.limit as f64 / w.as_secs_f64();
+                let cap = p.burst as f64;
+                *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * rate).min(cap);
+                *last = now;
+                let until_full = |t: f64| if rate > 0.0 { Duration::from_secs_f64((cap - t).max(0.0) / rate) } else { Duration::MAX };
+                if *tokens < 1.0 {
+                    let retry = if rate > 0.0 { Duration::from_secs_f64((1.0 - *tokens) / rate) } else { Duration::MAX };
+                    return Decision { limit: p.burst, ..deny(retry, until_full(*tokens)) };
+                }
+                *tokens -= 1.0;
+                Decision { allowed: true, limit: p.burst, remaining: tokens.floor() as u32, reset_after: until_full(*tokens), retry_after: None }
+            }
+        }
+    }
+}
+
+static STORE: Lazy<ShardedStore> = Lazy::new(|| ShardedStore::new(store::DEFAULT_SHARDS, store::DEFAULT_MAX_ENTRIES));
+
+// Backend mặc định: kho trong bộ nhớ của tiến trình
+pub struct MemoryBackend;
+
+impl Backend for MemoryBackend {
+    fn name(&self) -> &'static str { "memory" }
+
+    fn check(&self, key: &str, policy: &Policy) -> Result<Decision, BackendError> {
+        Ok(STORE.check(key, policy))
+    }
+}
+
+fn state_key(key: &str, policy: &Policy) -> String {
+    format!("{}|{}", key, policy.fingerprint())
+}
+
+// Kiểm tra và ghi nhận một request cho key (vd: ip + path) theo chính sách
+pub fn check(key: &str, policy: &Policy) -> Decision {
+    STORE.check(&state_key(key, policy), policy)
+}
+
+// Như check() nhưng trên backend được chọn (memory/redis); lỗi backend do caller xử lý theo failure_mode
+pub fn check_with(backend: &dyn Backend, key: &str, policy: &Policy) -> Result<Decision, BackendError> {
+    backend.check(&state_key(key, policy), policy)
+}
+
+// Giới hạn số key được theo dõi (feature_extras.rate_limit.max_entries)
+pub fn set_max_entries(n: usize) {
+    STORE.set_max_entries(n);
+}
+
+pub fn max_entries_from_extras(extras: Option<&Value>) -> usize {
+    extras
+        .and_then(|o| o.get("max_entries"))
+        .and_then(|v| v.as_u64())
+        .filter(|n| *n > 0)
+        .map(|n| n as usize)
+        .unwrap_or(store::DEFAULT_MAX_ENTRIES)
+}
+
+pub fn stats() -> StoreStats {
+    STORE.stats()
+}
+
+pub fn sweep() -> usize {
+    STORE.sweep()
+}
+
+// Giữ API cũ: giới hạn `limit` request/giây cho ip+path
+pub fn check_allow(ip: &str, path: &str, limit: usize) -> bool {
+    let policy = Policy::per_second(limit.min(u32::MAX as usize) as u32, Algorithm::default());
+    check(&format!("{}|{}", ip, normalize_path(path)), &policy).allowed
 }
 
 // Manifest mô tả UI và schema cấu hình cho plugin, phục vụ Admin UI động
@@ -46,8 +396,181 @@ pub extern "C" fn feature_manifest_rate_limit() -> *mut c_char {
         "name": "rate_limit",
         "settings": [
           {"key": "rps", "type": "number", "label": "Rate Limit (req/s)", "default": 1, "scope": "global"},
-          {"key": "route_limits", "type": "route_number_map", "label": "Per-Route Rate Limits", "default": {}}
+          {"key": "algorithm", "type": "string", "label": "Algorithm (sliding_window, sliding_log, token_bucket, fixed_window)", "default": "sliding_window"},
+          {"key": "limit", "type": "number", "label": "Global Limit per Window (overrides rps)", "default": 0},
+          {"key": "window", "type": "string", "label": "Global Window (500ms, 10s, 1m, 1h)", "default": "1s"},
+          {"key": "burst", "type": "number", "label": "Token Bucket Burst (0 = limit)", "default": 0},
+          {"key": "key", "type": "string", "label": "Client Key (ip, api_key, user, header:<Name>)", "default": "ip"},
+          {"key": "max_entries", "type": "number", "label": "Max Tracked Clients (memory cap)", "default": 100000},
+          {"key": "backend", "type": "string", "label": "State Backend (memory, redis)", "default": "memory"},
+          {"key": "redis_url", "type": "string", "label": "Redis URL (redis://[:password@]host:port/db)", "default": "redis://127.0.0.1:6379", "secret": "userinfo"},
+          {"key": "redis_prefix", "type": "string", "label": "Redis Key Prefix", "default": "rl:"},
+          {"key": "redis_timeout_ms", "type": "number", "label": "Redis Timeout (ms)", "default": 200},
+          {"key": "failure_mode", "type": "string", "label": "When Backend Is Down (open = allow, closed = reject 503)", "default": "open"},
+          {"key": "route_limits", "type": "route_number_map", "label": "Per-Route Rate Limits", "default": {}},
+          {"key": "route_policies", "type": "json", "label": "Per-Route Policies (algorithm, limit, window, burst, key)", "default": {}, "example": {"/greet/*": {"algorithm": "token_bucket", "limit": 100, "window": "1m", "burst": 20, "key": "api_key"}, "/greet/hi": {"algorithm": "sliding_log", "limit": 5, "window": "10s"}}}
         ]
     }"#;
     CString::new(json).unwrap().into_raw()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_window_units_and_out_of_range_values() {
+        assert_eq!(parse_window(&json!(30)), Some(Duration::from_secs(30)));
+        assert_eq!(parse_window(&json!("500ms")), Some(Duration::from_millis(500)));
+        assert_eq!(parse_window(&json!("5m")), Some(Duration::from_secs(300)));
+        assert_eq!(parse_window(&json!(0)), None);
+        assert_eq!(parse_window(&json!(-5)), None);
+        assert_eq!(parse_window(&json!("1x")), None);
+        // Không panic với giá trị quá lớn, bị kẹp về MAX_WINDOW
+        assert_eq!(parse_window(&json!(1e300)), Some(MAX_WINDOW));
+        assert_eq!(parse_window(&json!("99999999999999999999999d")), Some(MAX_WINDOW));
+        assert_eq!(parse_window(&json!(u64::MAX)), Some(MAX_WINDOW));
+    }
+
+    fn secs(n: f64) -> Duration {
+        Duration::from_secs_f64(n)
+    }
+
+    // So sánh thời lượng có dung sai (phép tính số thực trong sliding window / token bucket)
+    fn assert_near(actual: Duration, expected: f64) {
+        assert!((actual.as_secs_f64() - expected).abs() < 1e-3, "{:?} != {}s", actual, expected);
+    }
+
+    fn run(p: &Policy, state: &mut State, now: Instant, n: usize) -> Vec<Decision> {
+        (0..n).map(|_| state.check(p, now)).collect()
+    }
+
+    #[test]
+    fn fixed_window_counts_and_resets() {
+        let p = Policy { window: secs(10.0), ..Policy::per_second(3, Algorithm::FixedWindow) };
+        let t0 = Instant::now();
+        let mut st = State::new(&p, t0);
+        let first = st.check(&p, t0);
+        assert!(first.allowed);
+        assert_eq!((first.limit, first.remaining), (3, 2));
+        assert_eq!(first.reset_after, secs(10.0));
+        assert_eq!(st.check(&p, t0 + secs(1.0)).remaining, 1);
+        assert_eq!(st.check(&p, t0 + secs(2.0)).remaining, 0);
+
+        let denied = st.check(&p, t0 + secs(3.0));
+        assert!(!denied.allowed);
+        assert_eq!(denied.remaining, 0);
+        assert_eq!(denied.retry_after, Some(secs(7.0)));
+        assert_eq!(denied.retry_after_secs(), Some(7));
+        assert_eq!(denied.headers(&p)[2], (HEADER_RESET, "7".to_string()));
+        assert_eq!(denied.problem(&p, "/x")["retry_after"], 7);
+
+        // Cửa sổ mới: đếm lại từ đầu
+        let next = st.check(&p, t0 + secs(10.0));
+        assert!(next.allowed);
+        assert_eq!(next.remaining, 2);
+    }
+
+    #[test]
+    fn fixed_window_allows_a_burst_at_the_edge() {
+        let p = Policy { window: secs(10.0), ..Policy::per_second(3, Algorithm::FixedWindow) };
+        let t0 = Instant::now();
+        let mut st = State::new(&p, t0);
+        // Cuối cửa sổ này và đầu cửa sổ kế tiếp: 2 * limit trong 1 giây
+        assert!(run(&p, &mut st, t0 + secs(9.0), 3).iter().all(|d| d.allowed));
+        assert!(run(&p, &mut st, t0 + secs(10.0), 3).iter().all(|d| d.allowed));
+        assert!(!st.check(&p, t0 + secs(10.0)).allowed);
+    }
+
+    #[test]
+    fn sliding_window_weights_the_previous_window() {
+        let p = Policy { window: secs(10.0), ..Policy::per_second(10, Algorithm::SlidingWindow) };
+        let t0 = Instant::now();
+        let mut st = State::new(&p, t0);
+        let burst = run(&p, &mut st, t0 + secs(9.0), 10);
+        assert!(burst.iter().all(|d| d.allowed));
+        assert_eq!(burst[9].remaining, 0);
+        let full = st.check(&p, t0 + secs(9.0));
+        assert!(!full.allowed);
+        assert_near(full.retry_after.unwrap(), 1.0);
+
+        // Ngay sau ranh giới cửa sổ, burst trước đó vẫn được tính (khác fixed window)
+        let edge = st.check(&p, t0 + secs(10.0));
+        assert!(!edge.allowed);
+        assert_near(edge.retry_after.unwrap(), 1.0);
+        assert_near(edge.reset_after, 20.0);
+
+        // Giữa cửa sổ: cửa sổ trước còn nặng 50% -> còn 5 chỗ
+        let mid = run(&p, &mut st, t0 + secs(15.0), 6);
+        assert!(mid[..5].iter().all(|d| d.allowed));
+        assert_eq!(mid[0].remaining, 4);
+        assert!(!mid[5].allowed);
+        // Chờ đến khi trọng số cửa sổ trước giảm đủ cho thêm 1 request
+        assert_near(mid[5].retry_after.unwrap(), 1.0);
+    }
+
+    #[test]
+    fn sliding_window_forgets_older_windows() {
+        let p = Policy { window: secs(10.0), ..Policy::per_second(10, Algorithm::SlidingWindow) };
+        let t0 = Instant::now();
+        let mut st = State::new(&p, t0);
+        run(&p, &mut st, t0, 10);
+        let later = st.check(&p, t0 + secs(25.0));
+        assert!(later.allowed);
+        assert_eq!(later.remaining, 9);
+        assert_near(later.reset_after, 5.0);
+    }
+
+    #[test]
+    fn sliding_log_tracks_each_request() {
+        let p = Policy { window: secs(10.0), ..Policy::per_second(3, Algorithm::SlidingLog) };
+        let t0 = Instant::now();
+        let mut st = State::new(&p, t0);
+        assert_eq!(st.check(&p, t0).reset_after, secs(10.0));
+        assert_eq!(st.check(&p, t0 + secs(2.0)).remaining, 1);
+        let last = st.check(&p, t0 + secs(4.0));
+        assert_eq!((last.remaining, last.reset_after), (0, secs(6.0)));
+
+        let denied = st.check(&p, t0 + secs(5.0));
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, Some(secs(5.0)));
+
+        // Request đầu tiên rời cửa sổ -> có thêm đúng một chỗ
+        let freed = st.check(&p, t0 + secs(10.0));
+        assert!(freed.allowed);
+        assert_eq!((freed.remaining, freed.reset_after), (0, secs(2.0)));
+        let again = st.check(&p, t0 + secs(11.0));
+        assert!(!again.allowed);
+        assert_eq!(again.retry_after_secs(), Some(1));
+    }
+
+    #[test]
+    fn token_bucket_bursts_then_refills() {
+        let p = Policy { burst: 3, ..Policy::per_second(1, Algorithm::TokenBucket) };
+        let t0 = Instant::now();
+        let mut st = State::new(&p, t0);
+        let burst = run(&p, &mut st, t0, 3);
+        assert!(burst.iter().all(|d| d.allowed && d.limit == 3));
+        assert_eq!(burst.iter().map(|d| d.remaining).collect::<Vec<_>>(), vec![2, 1, 0]);
+        assert_near(burst[2].reset_after, 3.0);
+
+        let empty = st.check(&p, t0);
+        assert!(!empty.allowed);
+        assert_eq!(empty.limit, 3);
+        assert_near(empty.retry_after.unwrap(), 1.0);
+        assert_eq!(empty.retry_after_secs(), Some(1));
+
+        // Nạp lại 1.5 token sau 1.5 giây
+        assert!(st.check(&p, t0 + secs(1.5)).allowed);
+        let partial = st.check(&p, t0 + secs(1.5));
+        assert!(!partial.allowed);
+        assert_near(partial.retry_after.unwrap(), 0.5);
+
+        // Không vượt quá burst dù chờ lâu
+        let full = st.check(&p, t0 + secs(100.0));
+        assert_eq!(full.remaining, 2);
+        assert_near(full.reset_after, 1.0);
+    }
+}
+
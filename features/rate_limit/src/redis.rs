@@ -0,0 +1,605 @@
+// Backend Redis cho rate limit: client RESP tối giản (đồng bộ, có timeout) + một Lua script
+// để kiểm tra và ghi nhận trong một bước nguyên tử trên server. Thời gian lấy từ TIME của server
+// nên các instance lệch đồng hồ vẫn đếm chung một cửa sổ.
+use crate::backend::{Backend, BackendError};
+use crate::{Algorithm, Decision, Policy};
+use parking_lot::Mutex;
+use serde_json::Value;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// Số kết nối rảnh giữ lại trong pool
+const MAX_IDLE: usize = 16;
+// Sau khi không kết nối được, chờ khoảng này mới thử lại (tránh mỗi request đều chờ timeout)
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+// Reply của script rất nhỏ; chặn bulk lớn bất thường
+const MAX_BULK: usize = 1 << 20;
+
+// KEYS[1] = key; ARGV = algorithm, limit, window_ms, burst
+// Trả về {allowed (0/1), remaining, reset_ms, retry_ms}
+const SCRIPT: &str = r#"
+local t = redis.call('TIME')
+local now = tonumber(t[1]) * 1000 + math.floor(tonumber(t[2]) / 1000)
+local key = KEYS[1]
+local algo = ARGV[1]
+local limit = tonumber(ARGV[2])
+local window = tonumber(ARGV[3])
+local burst = tonumber(ARGV[4])
+
+if algo == 'fixed_window' then
+  local count = tonumber(redis.call('GET', key) or '0')
+  local ttl = redis.call('PTTL', key)
+  if ttl < 0 then ttl = window end
+  if count >= limit then return {0, 0, ttl, ttl} end
+  count = redis.call('INCR', key)
+  if count == 1 then redis.call('PEXPIRE', key, window); ttl = window end
+  return {1, limit - count, ttl, 0}
+elseif algo == 'sliding_window' then
+  local start = now - (now % window)
+  local cur_key = key .. ':' .. start
+  local prev = tonumber(redis.call('GET', key .. ':' .. (start - window)) or '0')
+  local cur = tonumber(redis.call('GET', cur_key) or '0')
+  local elapsed = now - start
+  local estimate = prev * (1 - elapsed / window) + cur
+  local reset = window - elapsed
+  if prev > 0 then reset = reset + window end
+  if estimate + 1 > limit then
+    local retry = window - elapsed
+    if prev > 0 and cur + 1 <= limit then
+      local target = 1 - (limit - cur - 1) / prev
+      retry = math.max(1, math.ceil(target * window - elapsed))
+    end
+    return {0, 0, reset, retry}
+  end
+  redis.call('INCR', cur_key)
+  redis.call('PEXPIRE', cur_key, window * 2)
+  return {1, math.max(0, math.floor(limit - estimate - 1)), reset, 0}
+elseif algo == 'sliding_log' then
+  redis.call('ZREMRANGEBYSCORE', key, '-inf', now - window)
+  local count = redis.call('ZCARD', key)
+  if count >= limit then
+    local oldest = redis.call('ZRANGE', key, 0, 0, 'WITHSCORES')
+    local reset = 0
+    if oldest[2] then reset = tonumber(oldest[2]) + window - now end
+    return {0, 0, reset, math.max(1, reset)}
+  end
+  local seq = redis.call('INCR', key .. ':seq')
+  redis.call('PEXPIRE', key .. ':seq', window)
+  redis.call('ZADD', key, now, now .. '-' .. seq)
+  redis.call('PEXPIRE', key, window)
+  local oldest = redis.call('ZRANGE', key, 0, 0, 'WITHSCORES')
+  return {1, limit - count - 1, tonumber(oldest[2]) + window - now, 0}
+else
+  local rate = limit / window
+  local data = redis.call('HMGET', key, 'tokens', 'ts')
+  local tokens = tonumber(data[1]) or burst
+  local ts = tonumber(data[2]) or now
+  tokens = math.min(burst, tokens + math.max(0, now - ts) * rate)
+  local allowed, retry = 0, 0
+  if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+  elseif rate > 0 then
+    retry = math.ceil((1 - tokens) / rate)
+  else
+    retry = window
+  end
+  redis.call('HMSET', key, 'tokens', tostring(tokens), 'ts', now)
+  local full = window
+  if rate > 0 then full = math.ceil((burst - tokens) / rate) end
+  redis.call('PEXPIRE', key, math.max(full, 1000))
+  return {allowed, math.floor(tokens), full, retry}
+end
+"#;
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct RedisConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub db: u32,
+    pub prefix: String,
+    pub timeout_ms: u64,
+}
+
+// Debug không in mật khẩu (cấu hình có thể lọt vào log qua {:?})
+impl fmt::Debug for RedisConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisConfig")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "••••"))
+            .field("db", &self.db)
+            .field("prefix", &self.prefix)
+            .field("timeout_ms", &self.timeout_ms)
+            .finish()
+    }
+}
+
+impl RedisConfig {
+    // redis://[[user]:password@]host[:port][/db]
+    pub fn parse(url: &str) -> Result<Self, BackendError> {
+        // Không đưa URL vào thông báo lỗi: phần userinfo chứa mật khẩu
+        let bad = || BackendError::Config("invalid redis_url (expected redis://[[user]:password@]host[:port][/db])".to_string());
+        let rest = url.trim().strip_prefix("redis://").ok_or_else(bad)?;
+        let (auth, rest) = match rest.rsplit_once('@') {
+            Some((a, r)) => (Some(a), r),
+            None => (None, rest),
+        };
+        let (hostport, db) = match rest.split_once('/') {
+            Some((h, d)) if !d.is_empty() => (h, d.parse::<u32>().map_err(|_| bad())?),
+            Some((h, _)) => (h, 0),
+            None => (rest, 0),
+        };
+        // IPv6 viết trong ngoặc vuông: [::1]:6379
+        let (host, port) = match hostport.strip_prefix('[') {
+            Some(v6) => {
+                let (h, p) = v6.split_once(']').ok_or_else(bad)?;
+                (h, p.strip_prefix(':'))
+            }
+            None => match hostport.rsplit_once(':') {
+                Some((h, p)) => (h, Some(p)),
+                None => (hostport, None),
+            },
+        };
+        let port = match port {
+            Some(p) => p.parse::<u16>().map_err(|_| bad())?,
+            None => 6379,
+        };
+        if host.is_empty() { return Err(bad()); }
+        let (username, password) = match auth {
+            Some(a) => match a.split_once(':') {
+                Some((u, p)) => ((!u.is_empty()).then(|| u.to_string()), Some(p.to_string())),
+                None => (None, Some(a.to_string())),
+            },
+            None => (None, None),
+        };
+        Ok(Self { host: host.to_string(), port, username, password, db, prefix: "rl:".to_string(), timeout_ms: 200 })
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+#[derive(Debug)]
+enum Reply {
+    Status,
+    Error(String),
+    Int(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<Reply>>),
+}
+
+fn protocol(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn read_line(r: &mut impl BufRead) -> io::Result<Vec<u8>> {
+    let mut line = Vec::new();
+    r.read_until(b'\n', &mut line)?;
+    if !line.ends_with(b"\r\n") { return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed")); }
+    line.truncate(line.len() - 2);
+    Ok(line)
+}
+
+fn read_reply(r: &mut impl BufRead) -> io::Result<Reply> {
+    let line = read_line(r)?;
+    let (kind, rest) = line.split_first().ok_or_else(|| protocol("empty reply"))?;
+    let text = String::from_utf8_lossy(rest).into_owned();
+    let int = || text.parse::<i64>().map_err(|_| protocol(format!("bad integer '{}'", text)));
+    match kind {
+        b'+' => Ok(Reply::Status),
+        b'-' => Ok(Reply::Error(text)),
+        b':' => Ok(Reply::Int(int()?)),
+        b'$' => {
+            let n = int()?;
+            if n < 0 { return Ok(Reply::Bulk(None)); }
+            let n = n as usize;
+            if n > MAX_BULK { return Err(protocol("bulk reply too large")); }
+            let mut buf = vec![0u8; n + 2];
+            r.read_exact(&mut buf)?;
+            buf.truncate(n);
+            Ok(Reply::Bulk(Some(buf)))
+        }
+        b'*' => {
+            let n = int()?;
+            if n < 0 { return Ok(Reply::Array(None)); }
+            (0..n).map(|_| read_reply(r)).collect::<io::Result<Vec<_>>>().map(|v| Reply::Array(Some(v)))
+        }
+        _ => Err(protocol(format!("unexpected reply type '{}'", *kind as char))),
+    }
+}
+
+struct Conn {
+    stream: BufReader<TcpStream>,
+    script_sha: Option<String>,
+    // EVALSHA đã gửi đi trọn vẹn: server có thể đã ghi nhận hit nên không được gửi lại
+    eval_sent: bool,
+}
+
+impl Conn {
+    fn connect(cfg: &RedisConfig) -> io::Result<Self> {
+        let addr = (cfg.host.as_str(), cfg.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", cfg.host)))?;
+        let stream = TcpStream::connect_timeout(&addr, cfg.timeout())?;
+        stream.set_read_timeout(Some(cfg.timeout()))?;
+        stream.set_write_timeout(Some(cfg.timeout()))?;
+        let _ = stream.set_nodelay(true);
+        let mut conn = Self { stream: BufReader::new(stream), script_sha: None, eval_sent: false };
+        if let Some(pass) = &cfg.password {
+            let reply = match &cfg.username {
+                Some(user) => conn.call(&[b"AUTH", user.as_bytes(), pass.as_bytes()])?,
+                None => conn.call(&[b"AUTH", pass.as_bytes()])?,
+            };
+            expect_ok(reply, "AUTH")?;
+        }
+        if cfg.db != 0 {
+            let reply = conn.call(&[b"SELECT", cfg.db.to_string().as_bytes()])?;
+            expect_ok(reply, "SELECT")?;
+        }
+        Ok(conn)
+    }
+
+    // Kết nối rảnh bị server đóng (timeout, restart) hoặc còn dữ liệu thừa thì không dùng lại
+    fn is_stale(&self) -> bool {
+        if !self.stream.buffer().is_empty() { return true; }
+        let sock = self.stream.get_ref();
+        if sock.set_nonblocking(true).is_err() { return true; }
+        let mut b = [0u8; 1];
+        let stale = !matches!(sock.peek(&mut b), Err(e) if e.kind() == io::ErrorKind::WouldBlock);
+        stale || sock.set_nonblocking(false).is_err()
+    }
+
+    fn call(&mut self, args: &[&[u8]]) -> io::Result<Reply> {
+        self.send(args)?;
+        read_reply(&mut self.stream)
+    }
+
+    fn send(&mut self, args: &[&[u8]]) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+        for a in args {
+            buf.extend_from_slice(format!("${}\r\n", a.len()).as_bytes());
+            buf.extend_from_slice(a);
+            buf.extend_from_slice(b"\r\n");
+        }
+        self.stream.get_mut().write_all(&buf)
+    }
+
+    fn load_script(&mut self) -> io::Result<String> {
+        match self.call(&[b"SCRIPT", b"LOAD", SCRIPT.as_bytes()])? {
+            Reply::Bulk(Some(sha)) => {
+                let sha = String::from_utf8_lossy(&sha).into_owned();
+                self.script_sha = Some(sha.clone());
+                Ok(sha)
+            }
+            Reply::Error(e) => Err(protocol(format!("SCRIPT LOAD: {}", e))),
+            other => Err(protocol(format!("SCRIPT LOAD: unexpected reply {:?}", other))),
+        }
+    }
+
+    fn eval(&mut self, key: &str, p: &Policy) -> io::Result<Vec<i64>> {
+        let sha = match &self.script_sha {
+            Some(s) => s.clone(),
+            None => self.load_script()?,
+        };
+        let limit = p.limit.to_string();
+        let window = (p.window.as_millis().max(1) as u64).to_string();
+        let burst = p.burst.to_string();
+        let run = |conn: &mut Self, sha: &str| {
+            conn.send(&[b"EVALSHA", sha.as_bytes(), b"1", key.as_bytes(), p.algorithm.as_str().as_bytes(), limit.as_bytes(), window.as_bytes(), burst.as_bytes()])?;
+            conn.eval_sent = true;
+            read_reply(&mut conn.stream)
+        };
+        self.eval_sent = false;
+        let mut reply = run(self, &sha)?;
+        // Server đã SCRIPT FLUSH hoặc restart: nạp lại script rồi thử lại một lần
+        if matches!(&reply, Reply::Error(e) if e.starts_with("NOSCRIPT")) {
+            let sha = self.load_script()?;
+            reply = run(self, &sha)?;
+        }
+        match reply {
+            Reply::Array(Some(items)) if items.len() == 4 => items
+                .into_iter()
+                .map(|i| match i {
+                    Reply::Int(n) => Ok(n),
+                    other => Err(protocol(format!("unexpected script item {:?}", other))),
+                })
+                .collect(),
+            Reply::Error(e) => Err(protocol(format!("EVALSHA: {}", e))),
+            other => Err(protocol(format!("EVALSHA: unexpected reply {:?}", other))),
+        }
+    }
+}
+
+fn expect_ok(reply: Reply, cmd: &str) -> io::Result<()> {
+    match reply {
+        Reply::Status => Ok(()),
+        Reply::Error(e) => Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{}: {}", cmd, e))),
+        other => Err(protocol(format!("{}: unexpected reply {:?}", cmd, other))),
+    }
+}
+
+pub struct RedisBackend {
+    cfg: RedisConfig,
+    pool: Mutex<Vec<Conn>>,
+    down_until: Mutex<Option<Instant>>,
+    last_error: Mutex<Option<String>>,
+    calls: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl RedisBackend {
+    pub fn new(cfg: RedisConfig) -> Self {
+        Self {
+            cfg,
+            pool: Mutex::new(Vec::new()),
+            down_until: Mutex::new(None),
+            last_error: Mutex::new(None),
+            calls: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    fn fail(&self, e: io::Error) -> BackendError {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        let msg = format!("redis {}:{}: {}", self.cfg.host, self.cfg.port, e);
+        *self.down_until.lock() = Some(Instant::now() + RETRY_BACKOFF);
+        *self.last_error.lock() = Some(msg.clone());
+        BackendError::Failed(msg)
+    }
+
+    fn run(&self, key: &str, policy: &Policy) -> io::Result<Vec<i64>> {
+        // Kết nối lấy từ pool có thể đã bị server đóng khi rảnh: lỗi khi ghi thì thử lại bằng kết nối mới.
+        // Lỗi sau khi EVALSHA đã gửi (đọc reply hỏng/timeout) không thử lại vì hit có thể đã được đếm.
+        let pooled = {
+            let mut pool = self.pool.lock();
+            std::iter::from_fn(|| pool.pop()).find(|c| !c.is_stale())
+        };
+        if let Some(mut conn) = pooled {
+            match conn.eval(key, policy) {
+                Ok(v) => {
+                    self.release(conn);
+                    return Ok(v);
+                }
+                Err(e) if conn.eval_sent => return Err(e),
+                Err(_) => {}
+            }
+        }
+        let mut conn = Conn::connect(&self.cfg)?;
+        let v = conn.eval(key, policy)?;
+        self.release(conn);
+        Ok(v)
+    }
+
+    fn release(&self, conn: Conn) {
+        let mut pool = self.pool.lock();
+        if pool.len() < MAX_IDLE { pool.push(conn); }
+    }
+}
+
+impl Backend for RedisBackend {
+    fn name(&self) -> &'static str { "redis" }
+
+    fn is_remote(&self) -> bool { true }
+
+    fn check(&self, key: &str, policy: &Policy) -> Result<Decision, BackendError> {
+        if self.down_until.lock().is_some_and(|t| Instant::now() < t) {
+            return Err(BackendError::Unavailable(format!("redis {}:{} unavailable", self.cfg.host, self.cfg.port)));
+        }
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        // Hash tag {..} giữ mọi key phụ của script (":seq", cửa sổ trước/sau) trên cùng slot khi chạy cluster
+        let full_key = format!("{}{{{}}}", self.cfg.prefix, key);
+        let v = self.run(&full_key, policy).map_err(|e| self.fail(e))?;
+        *self.down_until.lock() = None;
+        let ms = |n: i64| Duration::from_millis(n.max(0) as u64);
+        let limit = if policy.algorithm == Algorithm::TokenBucket { policy.burst } else { policy.limit };
+        Ok(Decision {
+            allowed: v[0] == 1,
+            limit,
+            remaining: v[1].clamp(0, u32::MAX as i64) as u32,
+            reset_after: ms(v[2]),
+            retry_after: (v[0] != 1).then(|| ms(v[3]).max(Duration::from_millis(1))),
+        })
+    }
+
+    fn status(&self) -> Value {
+        serde_json::json!({
+            "backend": self.name(),
+            "addr": format!("{}:{}", self.cfg.host, self.cfg.port),
+            "db": self.cfg.db,
+            "prefix": self.cfg.prefix,
+            "timeout_ms": self.cfg.timeout_ms,
+            "healthy": !self.down_until.lock().is_some_and(|t| Instant::now() < t),
+            "idle_connections": self.pool.lock().len(),
+            "calls": self.calls.load(Ordering::Relaxed),
+            "errors": self.errors.load(Ordering::Relaxed),
+            "last_error": self.last_error.lock().clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    // Máy chủ RESP giả: ghi lại lệnh nhận được, trả lời theo `reply` (None = đóng kết nối không trả lời)
+    type Handler = dyn Fn(&[String], usize) -> Option<Vec<u8>> + Send + Sync;
+
+    fn fake_server(reply: Arc<Handler>) -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let log2 = log.clone();
+        std::thread::spawn(move || {
+            for (conn_no, stream) in listener.incoming().flatten().enumerate() {
+                let reply = reply.clone();
+                let log = log2.clone();
+                std::thread::spawn(move || {
+                    let mut w = stream.try_clone().unwrap();
+                    let mut r = BufReader::new(stream);
+                    while let Ok(Reply::Array(Some(items))) = read_reply(&mut r) {
+                        let args: Vec<String> = items
+                            .into_iter()
+                            .map(|i| match i {
+                                Reply::Bulk(Some(b)) => String::from_utf8_lossy(&b).into_owned(),
+                                _ => String::new(),
+                            })
+                            .collect();
+                        log.lock().push(args.join(" "));
+                        match reply(&args, conn_no) {
+                            Some(bytes) => { if w.write_all(&bytes).is_err() { return; } }
+                            None => return,
+                        }
+                    }
+                });
+            }
+        });
+        (port, log)
+    }
+
+    fn backend(port: u16, url_extra: &str) -> RedisBackend {
+        let mut cfg = RedisConfig::parse(&format!("redis://{}127.0.0.1:{}", url_extra, port)).unwrap();
+        cfg.timeout_ms = 500;
+        RedisBackend::new(cfg)
+    }
+
+    fn policy() -> Policy {
+        Policy::per_second(10, Algorithm::FixedWindow)
+    }
+
+    fn count(log: &Mutex<Vec<String>>, cmd: &str) -> usize {
+        log.lock().iter().filter(|l| l.starts_with(cmd)).count()
+    }
+
+    const ALLOWED: &[u8] = b"*4\r\n:1\r\n:9\r\n:1000\r\n:0\r\n";
+
+    #[test]
+    fn parse_url_and_hide_password() {
+        let cfg = RedisConfig::parse("redis://app:hunter2@[::1]:6380/3").unwrap();
+        assert_eq!((cfg.host.as_str(), cfg.port, cfg.db), ("::1", 6380, 3));
+        assert_eq!((cfg.username.as_deref(), cfg.password.as_deref()), (Some("app"), Some("hunter2")));
+        assert!(!format!("{:?}", cfg).contains("hunter2"));
+
+        let cfg = RedisConfig::parse("redis://:pw@cache").unwrap();
+        assert_eq!((cfg.host.as_str(), cfg.port, cfg.username, cfg.password.as_deref()), ("cache", 6379, None, Some("pw")));
+
+        let err = RedisConfig::parse("redis://:hunter2@cache:notaport").unwrap_err().to_string();
+        assert!(!err.contains("hunter2"), "{}", err);
+        assert!(RedisConfig::parse("http://cache").is_err());
+    }
+
+    #[test]
+    fn read_reply_types() {
+        let mut r = io::Cursor::new(b"+OK\r\n-ERR nope\r\n:42\r\n$3\r\nabc\r\n$-1\r\n*2\r\n:1\r\n$0\r\n\r\n".to_vec());
+        assert!(matches!(read_reply(&mut r).unwrap(), Reply::Status));
+        assert!(matches!(read_reply(&mut r).unwrap(), Reply::Error(e) if e == "ERR nope"));
+        assert!(matches!(read_reply(&mut r).unwrap(), Reply::Int(42)));
+        assert!(matches!(read_reply(&mut r).unwrap(), Reply::Bulk(Some(b)) if b == b"abc"));
+        assert!(matches!(read_reply(&mut r).unwrap(), Reply::Bulk(None)));
+        assert!(matches!(read_reply(&mut r).unwrap(), Reply::Array(Some(v)) if v.len() == 2));
+        // Kết nối bị đóng giữa chừng, kiểu reply lạ, bulk quá lớn
+        assert!(read_reply(&mut io::Cursor::new(b":1".to_vec())).is_err());
+        assert!(read_reply(&mut io::Cursor::new(b"?x\r\n".to_vec())).is_err());
+        assert!(read_reply(&mut io::Cursor::new(format!("${}\r\n", MAX_BULK + 1).into_bytes())).is_err());
+    }
+
+    #[test]
+    fn auth_select_and_eval() {
+        let (port, log) = fake_server(Arc::new(|args: &[String], _| {
+            Some(match args[0].as_str() {
+                "AUTH" | "SELECT" => b"+OK\r\n".to_vec(),
+                "SCRIPT" => b"$3\r\nsha\r\n".to_vec(),
+                _ => ALLOWED.to_vec(),
+            })
+        }));
+        let mut cfg = RedisConfig::parse(&format!("redis://u:p@127.0.0.1:{}/2", port)).unwrap();
+        cfg.prefix = "t:".to_string();
+        let b = RedisBackend::new(cfg);
+        let d = b.check("k", &policy()).unwrap();
+        assert!(d.allowed);
+        assert_eq!((d.remaining, d.reset_after), (9, Duration::from_secs(1)));
+        b.check("k", &policy()).unwrap();
+        let log = log.lock().clone();
+        assert_eq!(&log[..3], ["AUTH u p", "SELECT 2", &format!("SCRIPT LOAD {}", SCRIPT)]);
+        // Kết nối được dùng lại: chỉ AUTH và nạp script một lần
+        assert_eq!(log[3], "EVALSHA sha 1 t:{k} fixed_window 10 1000 10");
+        assert_eq!(log.len(), 5);
+    }
+
+    #[test]
+    fn denied_decision_has_retry_after() {
+        let (port, _) = fake_server(Arc::new(|args: &[String], _| {
+            Some(match args[0].as_str() {
+                "SCRIPT" => b"$3\r\nsha\r\n".to_vec(),
+                _ => b"*4\r\n:0\r\n:0\r\n:800\r\n:250\r\n".to_vec(),
+            })
+        }));
+        let d = backend(port, "").check("k", &policy()).unwrap();
+        assert!(!d.allowed);
+        assert_eq!(d.retry_after, Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn reloads_script_after_noscript() {
+        let (port, log) = fake_server(Arc::new(|args: &[String], _| {
+            Some(match args[0].as_str() {
+                "SCRIPT" => b"$3\r\nsha\r\n".to_vec(),
+                _ => b"-NOSCRIPT No matching script\r\n".to_vec(),
+            })
+        }));
+        assert!(backend(port, "").check("k", &policy()).is_err());
+        assert_eq!(count(&log, "SCRIPT LOAD"), 2);
+        assert_eq!(count(&log, "EVALSHA"), 2);
+    }
+
+    #[test]
+    fn auth_error_is_reported() {
+        let (port, _) = fake_server(Arc::new(|_: &[String], _| Some(b"-WRONGPASS invalid password\r\n".to_vec())));
+        let b = backend(port, ":bad@");
+        let err = b.check("k", &policy()).unwrap_err().to_string();
+        assert!(err.contains("WRONGPASS") && !err.contains("bad@"), "{}", err);
+        // Đang trong thời gian chờ thử lại -> không gọi server
+        assert!(matches!(b.check("k", &policy()), Err(BackendError::Unavailable(_))));
+    }
+
+    #[test]
+    fn no_retry_after_eval_was_sent() {
+        // Server nhận EVALSHA rồi đóng kết nối không trả lời: hit có thể đã được đếm, không gửi lại
+        let (port, log) = fake_server(Arc::new(|args: &[String], _| match args[0].as_str() {
+            "SCRIPT" => Some(b"$3\r\nsha\r\n".to_vec()),
+            _ => None,
+        }));
+        assert!(backend(port, "").check("k", &policy()).is_err());
+        assert_eq!(count(&log, "EVALSHA"), 1);
+    }
+
+    #[test]
+    fn stale_pooled_connection_is_replaced() {
+        let (port, log) = fake_server(Arc::new(|args: &[String], _| {
+            Some(match args[0].as_str() {
+                "SCRIPT" => b"$3\r\nsha\r\n".to_vec(),
+                _ => ALLOWED.to_vec(),
+            })
+        }));
+        let b = backend(port, "");
+        b.check("k", &policy()).unwrap();
+        // Giả lập server đã đóng kết nối rảnh: đọc trên kết nối trong pool trả về EOF
+        {
+            let pool = b.pool.lock();
+            pool[0].stream.get_ref().shutdown(std::net::Shutdown::Read).unwrap();
+        }
+        assert!(b.check("k", &policy()).unwrap().allowed);
+        assert_eq!(count(&log, "EVALSHA"), 2);
+    }
+}
@@ -0,0 +1,218 @@
+// Kho trạng thái rate limit trong bộ nhớ:
+// - chia shard theo hash của key để các request khác key không tranh chung một khóa
+// - entry không được dùng quá idle TTL của chính sách (lúc đó trạng thái đã như mới) sẽ bị dọn
+// - giới hạn tổng số entry; khi đầy thì bỏ entry có hạn (last_seen + idle TTL) sớm nhất,
+//   tức entry đã hết hạn trước, rồi đến entry sắp hết hạn nhất
+// - mỗi shard giữ thêm chỉ mục theo hạn (BTreeMap) nên bỏ entry / dọn dẹp là O(log n), không quét cả shard
+use crate::{Decision, Policy, State};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_SHARDS: usize = 16;
+pub const DEFAULT_MAX_ENTRIES: usize = 100_000;
+// Cứ mỗi SWEEP_EVERY lần check thì dọn một shard (xoay vòng)
+const SWEEP_EVERY: u64 = 1024;
+
+struct Entry {
+    state: State,
+    ttl: Duration,
+    // Khóa của entry trong Shard::by_deadline
+    slot: (Instant, u64),
+}
+
+#[derive(Default)]
+struct Shard {
+    map: HashMap<String, Entry>,
+    // (hạn, số thứ tự) -> key; số thứ tự phân biệt các entry cùng hạn
+    by_deadline: BTreeMap<(Instant, u64), String>,
+    seq: u64,
+}
+
+// Hạn của entry; ttl rất lớn (không cộng được vào Instant) thì coi như không hết hạn trong ~100 năm
+fn deadline(now: Instant, ttl: Duration) -> Instant {
+    now.checked_add(ttl)
+        .or_else(|| now.checked_add(Duration::from_secs(100 * 365 * 86_400)))
+        .unwrap_or(now)
+}
+
+impl Shard {
+    fn next_slot(&mut self, now: Instant, ttl: Duration) -> (Instant, u64) {
+        self.seq += 1;
+        (deadline(now, ttl), self.seq)
+    }
+
+    // Dời hạn của entry đang có (không cấp phát lại key)
+    fn touch(&mut self, key: &str, now: Instant) -> Option<&mut Entry> {
+        let (old, ttl) = self.map.get(key).map(|e| (e.slot, e.ttl))?;
+        let slot = self.next_slot(now, ttl);
+        if let Some(k) = self.by_deadline.remove(&old) {
+            self.by_deadline.insert(slot, k);
+        }
+        let e = self.map.get_mut(key)?;
+        e.slot = slot;
+        Some(e)
+    }
+
+    fn insert(&mut self, key: &str, state: State, ttl: Duration, now: Instant) {
+        let slot = self.next_slot(now, ttl);
+        self.by_deadline.insert(slot, key.to_string());
+        self.map.insert(key.to_string(), Entry { state, ttl, slot });
+    }
+
+    // Bỏ entry có hạn sớm nhất nếu hạn đó <= `until` (None: bỏ bất kể hạn)
+    fn pop_earliest(&mut self, until: Option<Instant>) -> bool {
+        let Some(entry) = self.by_deadline.first_entry() else { return false };
+        if until.is_some_and(|t| entry.key().0 > t) { return false; }
+        let key = entry.remove();
+        self.map.remove(&key);
+        true
+    }
+
+    fn remove_expired(&mut self, now: Instant) -> usize {
+        let mut removed = 0;
+        while self.pop_earliest(Some(now)) { removed += 1; }
+        removed
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StoreStats {
+    pub entries: usize,
+    pub shards: usize,
+    pub max_entries: usize,
+    pub shard_sizes: Vec<usize>,
+    pub checks: u64,
+    pub evicted_expired: u64,
+    pub evicted_capacity: u64,
+}
+
+pub struct ShardedStore {
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+    max_entries: AtomicUsize,
+    checks: AtomicU64,
+    evicted_expired: AtomicU64,
+    evicted_capacity: AtomicU64,
+}
+
+impl ShardedStore {
+    pub fn new(shards: usize, max_entries: usize) -> Self {
+        let shards = shards.max(1);
+        Self {
+            shards: (0..shards).map(|_| Mutex::new(Shard::default())).collect(),
+            hasher: RandomState::new(),
+            max_entries: AtomicUsize::new(max_entries.max(shards)),
+            checks: AtomicU64::new(0),
+            evicted_expired: AtomicU64::new(0),
+            evicted_capacity: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_max_entries(&self, n: usize) {
+        self.max_entries.store(n.max(self.shards.len()), Ordering::Relaxed);
+    }
+
+    fn shard_cap(&self) -> usize {
+        (self.max_entries.load(Ordering::Relaxed) / self.shards.len()).max(1)
+    }
+
+    fn shard_index(&self, key: &str) -> usize {
+        (self.hasher.hash_one(key) as usize) % self.shards.len()
+    }
+
+    pub fn check(&self, key: &str, policy: &Policy) -> Decision {
+        let now = Instant::now();
+        let n = self.checks.fetch_add(1, Ordering::Relaxed) + 1;
+        if n.is_multiple_of(SWEEP_EVERY) {
+            self.sweep_shard(((n / SWEEP_EVERY) as usize) % self.shards.len(), now);
+        }
+        let mut shard = self.shards[self.shard_index(key)].lock();
+        if let Some(e) = shard.touch(key, now) {
+            return e.state.check(policy, now);
+        }
+        let cap = self.shard_cap();
+        if shard.len() >= cap {
+            let removed = shard.remove_expired(now);
+            self.evicted_expired.fetch_add(removed as u64, Ordering::Relaxed);
+            while shard.len() >= cap && shard.pop_earliest(None) {
+                self.evicted_capacity.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let mut state = State::new(policy, now);
+        let decision = state.check(policy, now);
+        shard.insert(key, state, policy.idle_ttl(), now);
+        decision
+    }
+
+    fn sweep_shard(&self, idx: usize, now: Instant) -> usize {
+        let removed = self.shards[idx].lock().remove_expired(now);
+        self.evicted_expired.fetch_add(removed as u64, Ordering::Relaxed);
+        removed
+    }
+
+    // Dọn toàn bộ entry hết hạn, trả về số entry đã bỏ
+    pub fn sweep(&self) -> usize {
+        let now = Instant::now();
+        (0..self.shards.len()).map(|i| self.sweep_shard(i, now)).sum()
+    }
+
+    pub fn clear(&self) {
+        for s in &self.shards { *s.lock() = Shard::default(); }
+    }
+
+    pub fn stats(&self) -> StoreStats {
+        let shard_sizes: Vec<usize> = self.shards.iter().map(|s| s.lock().len()).collect();
+        StoreStats {
+            entries: shard_sizes.iter().sum(),
+            shards: self.shards.len(),
+            max_entries: self.max_entries.load(Ordering::Relaxed),
+            shard_sizes,
+            checks: self.checks.load(Ordering::Relaxed),
+            evicted_expired: self.evicted_expired.load(Ordering::Relaxed),
+            evicted_capacity: self.evicted_capacity.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Algorithm;
+
+    #[test]
+    fn full_shard_evicts_earliest_deadline_first() {
+        let store = ShardedStore::new(1, 2);
+        let short = Policy::per_second(1, Algorithm::FixedWindow);
+        let long = Policy { window: Duration::from_secs(60), ..short.clone() };
+        assert!(store.check("a", &long).allowed);
+        assert!(store.check("b", &short).allowed);
+        // "b" hết hạn sớm hơn nên bị bỏ khi thêm "c"
+        assert!(store.check("c", &long).allowed);
+        let stats = store.stats();
+        assert_eq!((stats.entries, stats.evicted_capacity), (2, 1));
+        assert!(!store.check("a", &long).allowed);
+        assert!(store.check("b", &short).allowed);
+    }
+
+    #[test]
+    fn touch_moves_entry_and_sweep_removes_expired() {
+        let store = ShardedStore::new(1, 10);
+        let p = Policy { window: Duration::from_millis(20), ..Policy::per_second(5, Algorithm::FixedWindow) };
+        store.check("a", &p);
+        store.check("a", &p);
+        assert_eq!(store.shards[0].lock().by_deadline.len(), 1);
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(store.sweep(), 1);
+        assert_eq!(store.stats().entries, 0);
+        assert!(store.shards[0].lock().by_deadline.is_empty());
+    }
+}
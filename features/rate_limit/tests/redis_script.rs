@@ -0,0 +1,68 @@
+// Kiểm tra Lua script trên máy chủ nói giao thức Redis thật (Redis, Valkey, KeyDB...).
+// Chỉ chạy khi có RATE_LIMIT_TEST_REDIS_URL, vd:
+//   RATE_LIMIT_TEST_REDIS_URL=redis://127.0.0.1:6379/15 cargo test -p rate_limit --test redis_script
+use rate_limit::redis::{RedisBackend, RedisConfig};
+use rate_limit::{Algorithm, Backend, Policy};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn backend() -> Option<RedisBackend> {
+    let url = std::env::var("RATE_LIMIT_TEST_REDIS_URL").ok()?;
+    let mut cfg = RedisConfig::parse(&url).expect("RATE_LIMIT_TEST_REDIS_URL");
+    // Prefix riêng cho mỗi lần chạy để không đụng key của lần trước
+    let nonce = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    cfg.prefix = format!("rl-test:{}:", nonce);
+    cfg.timeout_ms = 1000;
+    Some(RedisBackend::new(cfg))
+}
+
+fn policy(algorithm: Algorithm, limit: u32, window: Duration, burst: u32) -> Policy {
+    Policy { algorithm, limit, window, burst, ..Policy::per_second(limit, algorithm) }
+}
+
+// Gửi n request, trả về số request được cho qua
+fn allowed(b: &RedisBackend, key: &str, p: &Policy, n: usize) -> usize {
+    (0..n).filter(|_| b.check(key, p).unwrap().allowed).count()
+}
+
+#[test]
+fn fixed_window_limits_and_resets() {
+    let Some(b) = backend() else { return };
+    let p = policy(Algorithm::FixedWindow, 3, Duration::from_millis(300), 3);
+    assert_eq!(allowed(&b, "fw", &p, 5), 3);
+    let d = b.check("fw", &p).unwrap();
+    assert!(!d.allowed && d.remaining == 0);
+    assert!(d.retry_after.unwrap() <= Duration::from_millis(300));
+    std::thread::sleep(Duration::from_millis(350));
+    assert!(b.check("fw", &p).unwrap().allowed);
+}
+
+#[test]
+fn sliding_log_counts_exactly() {
+    let Some(b) = backend() else { return };
+    let p = policy(Algorithm::SlidingLog, 4, Duration::from_secs(10), 4);
+    let first = b.check("sl", &p).unwrap();
+    assert!(first.allowed && first.remaining == 3);
+    assert_eq!(allowed(&b, "sl", &p, 5), 3);
+    // Key khác đếm riêng
+    assert!(b.check("sl-other", &p).unwrap().allowed);
+}
+
+#[test]
+fn sliding_window_rejects_over_limit() {
+    let Some(b) = backend() else { return };
+    let p = policy(Algorithm::SlidingWindow, 5, Duration::from_secs(10), 5);
+    assert_eq!(allowed(&b, "sw", &p, 8), 5);
+    assert!(b.check("sw", &p).unwrap().retry_after.is_some());
+}
+
+#[test]
+fn token_bucket_allows_burst_then_refills() {
+    let Some(b) = backend() else { return };
+    // 10 token/giây, bucket 3
+    let p = policy(Algorithm::TokenBucket, 10, Duration::from_secs(1), 3);
+    let first = b.check("tb", &p).unwrap();
+    assert_eq!((first.limit, first.remaining), (3, 2));
+    assert_eq!(allowed(&b, "tb", &p, 4), 2);
+    std::thread::sleep(Duration::from_millis(250));
+    assert!(b.check("tb", &p).unwrap().allowed);
+}
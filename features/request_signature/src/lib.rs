@@ -0,0 +1,37 @@
+#![allow(non_snake_case)]
+#[cfg(feature = "plugin")]
+pub mod plugin {
+    use libc::c_char;
+    use std::ffi::CString;
+
+    // C-ABI symbol used by the dynamic loader to identify the feature
+    #[no_mangle]
+    pub extern "C" fn feature_name_request_signature() -> *mut c_char {
+        CString::new("request_signature").unwrap().into_raw()
+    }
+
+    // Manifest mô tả UI cấu hình cho Admin
+    // - Khóa phẳng là mặc định cho mọi route; routes: route nào cần chữ ký và secret của route đó
+    fn manifest_json() -> String {
+        r#"{
+            "name": "request_signature",
+            "description": "Xác thực chữ ký HMAC cho route webhook (hmac, GitHub, Stripe): secret theo route (xoay vòng, env:NAME), kiểm tra timestamp, chống replay",
+            "settings": [
+              {"key": "tolerance_secs", "type": "number", "label": "Timestamp Tolerance (seconds)", "default": 300},
+              {"key": "replay_window_secs", "type": "number", "label": "Replay Window Without Timestamp (seconds)", "default": 86400},
+              {"key": "replay_cache_size", "type": "number", "label": "Replay Cache Size (entries)", "default": 10000},
+              {"key": "max_body_bytes", "type": "number", "label": "Max Signed Body Size (bytes, 413 above)", "default": 1048576},
+              {"key": "routes", "type": "json", "label": "Signed Routes (scheme: hmac, github, stripe; secrets: env:NAME only)", "default": {}, "example": {"/hooks/github": {"scheme": "github", "secrets": ["env:GITHUB_WEBHOOK_SECRET"]}, "/hooks/stripe": {"scheme": "stripe", "secrets": ["env:STRIPE_WEBHOOK_SECRET"]}, "/hooks/custom/*": {"scheme": "hmac", "algorithm": "sha256", "header": "x-signature", "timestamp_header": "x-signature-timestamp", "nonce_header": "x-signature-nonce", "secrets": ["env:CUSTOM_WEBHOOK_SECRET", "env:CUSTOM_WEBHOOK_SECRET_PREVIOUS"]}}}
+            ]
+        }"#.to_string()
+    }
+
+    #[no_mangle]
+    pub extern "C" fn feature_manifest_request_signature() -> *mut c_char {
+        CString::new(manifest_json()).unwrap().into_raw()
+    }
+}
+
+// Logic xác thực (pure Rust) dùng bởi middleware của app (app/src/request_signature.rs)
+pub mod verify;
+pub use verify::{config_for, now_secs, sign, Algorithm, RoutePolicy, Scheme, SignatureConfig, SignatureError};
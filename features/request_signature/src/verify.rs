@@ -0,0 +1,578 @@
+// Xác thực chữ ký HMAC của request kiểu webhook theo route:
+// - scheme "hmac":   X-Signature: sha256=<hex> trên "<timestamp>.<body>" (X-Signature-Timestamp)
+// - scheme "github": X-Hub-Signature-256: sha256=<hex> trên body thô, X-GitHub-Delivery làm nonce
+// - scheme "stripe": Stripe-Signature: t=<ts>,v1=<hex>[,v1=...] trên "<t>.<body>"
+// Chống replay: chữ ký (và nonce nếu có) đã dùng được nhớ tới khi hết hạn
+use hmac::{Hmac, Mac};
+use module_utils::{route_matches, route_specificity, ConfigCache};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::{Sha256, Sha512};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_TOLERANCE_SECS: u64 = 300;
+// Route không có timestamp (github): thời gian nhớ chữ ký / nonce đã dùng
+pub const DEFAULT_REPLAY_WINDOW_SECS: u64 = 86_400;
+pub const DEFAULT_REPLAY_CACHE_SIZE: usize = 10_000;
+pub const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    Hmac,
+    Github,
+    Stripe,
+}
+
+impl Scheme {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "hmac" | "generic" => Some(Self::Hmac),
+            "github" => Some(Self::Github),
+            "stripe" => Some(Self::Stripe),
+            _ => None,
+        }
+    }
+
+    fn default_header(self) -> &'static str {
+        match self {
+            Self::Hmac => "x-signature",
+            Self::Github => "x-hub-signature-256",
+            Self::Stripe => "stripe-signature",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" => Some(Self::Sha256),
+            "sha512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha512 => "sha512",
+        }
+    }
+}
+
+// So sánh HMAC trong thời gian hằng (verify_slice)
+fn hmac_matches(algorithm: Algorithm, secret: &[u8], payload: &[&[u8]], expected: &[u8]) -> bool {
+    macro_rules! check {
+        ($h:ty) => {{
+            let Ok(mut mac) = Hmac::<$h>::new_from_slice(secret) else { return false };
+            for p in payload { mac.update(p); }
+            mac.verify_slice(expected).is_ok()
+        }};
+    }
+    match algorithm {
+        Algorithm::Sha256 => check!(Sha256),
+        Algorithm::Sha512 => check!(Sha512),
+    }
+}
+
+// Chữ ký hex của payload (dùng cho client / công cụ gửi thử)
+pub fn sign(algorithm: Algorithm, secret: &[u8], payload: &[u8]) -> String {
+    macro_rules! sign {
+        ($h:ty) => {{
+            let mut mac = Hmac::<$h>::new_from_slice(secret).expect("HMAC accepts any key length");
+            mac.update(payload);
+            hex::encode(mac.finalize().into_bytes())
+        }};
+    }
+    match algorithm {
+        Algorithm::Sha256 => sign!(Sha256),
+        Algorithm::Sha512 => sign!(Sha512),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    Missing,
+    Malformed,
+    Invalid,
+    MissingTimestamp,
+    // Timestamp lệch quá tolerance_secs so với đồng hồ server
+    TimestampOutOfTolerance,
+    Replayed,
+    BodyTooLarge,
+    // Route có cấu hình nhưng không có secret dùng được: từ chối mọi request
+    NotConfigured,
+    // Bộ nhớ chống replay đầy mục còn hiệu lực: không thể ghi nhận thêm mà vẫn đảm bảo chống replay
+    ReplayCacheFull,
+}
+
+impl SignatureError {
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::Malformed => 400,
+            Self::Replayed => 409,
+            Self::BodyTooLarge => 413,
+            Self::NotConfigured => 500,
+            Self::ReplayCacheFull => 503,
+            _ => 401,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Missing => "missing_signature",
+            Self::Malformed => "malformed_signature",
+            Self::Invalid => "invalid_signature",
+            Self::MissingTimestamp => "missing_timestamp",
+            Self::TimestampOutOfTolerance => "timestamp_out_of_tolerance",
+            Self::Replayed => "replayed_request",
+            Self::BodyTooLarge => "body_too_large",
+            Self::NotConfigured => "signature_not_configured",
+            Self::ReplayCacheFull => "replay_cache_full",
+        }
+    }
+
+    pub fn detail(&self) -> &'static str {
+        match self {
+            Self::Missing => "Request signature header is missing",
+            Self::Malformed => "Request signature or timestamp is malformed",
+            Self::Invalid => "Request signature does not match",
+            Self::MissingTimestamp => "Request timestamp is missing",
+            Self::TimestampOutOfTolerance => "Request timestamp is outside the allowed tolerance",
+            Self::Replayed => "Request has already been received",
+            Self::BodyTooLarge => "Request body is too large to verify",
+            Self::NotConfigured => "Signature verification is not configured for this route",
+            Self::ReplayCacheFull => "Too many recent signed requests, try again later",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoutePolicy {
+    pub route: String,
+    pub scheme: Scheme,
+    pub algorithm: Algorithm,
+    // Tên header viết thường
+    pub header: String,
+    pub timestamp_header: Option<String>,
+    pub nonce_header: Option<String>,
+    pub require_timestamp: bool,
+    pub tolerance_secs: u64,
+    pub replay_window_secs: u64,
+    // Nhiều secret để xoay vòng: khớp một trong số đó là hợp lệ; không bao giờ trả ra API
+    #[serde(skip)]
+    secrets: Vec<Vec<u8>>,
+    pub secret_count: usize,
+}
+
+// Header chữ ký sau khi tách: timestamp (nếu scheme mang theo) và các chữ ký ứng viên
+struct ParsedSignature {
+    timestamp: Option<String>,
+    signatures: Vec<Vec<u8>>,
+}
+
+impl RoutePolicy {
+    fn compile(route: &str, o: &Map<String, Value>, defaults: &Map<String, Value>) -> (Self, Vec<String>) {
+        let mut errors = Vec::new();
+        let get = |k: &str| o.get(k).or_else(|| defaults.get(k));
+        let text = |k: &str| get(k).and_then(|v| v.as_str()).map(|s| s.trim().to_ascii_lowercase()).filter(|s| !s.is_empty());
+
+        let scheme = match text("scheme") {
+            None => Scheme::Hmac,
+            Some(s) => Scheme::parse(&s).unwrap_or_else(|| {
+                errors.push(format!("{}: unknown scheme {:?} (hmac, github, stripe)", route, s));
+                Scheme::Hmac
+            }),
+        };
+        // github / stripe cố định sha256
+        let algorithm = match (scheme, text("algorithm")) {
+            (Scheme::Hmac, Some(a)) => Algorithm::parse(&a).unwrap_or_else(|| {
+                errors.push(format!("{}: unknown algorithm {:?} (sha256, sha512)", route, a));
+                Algorithm::Sha256
+            }),
+            _ => Algorithm::Sha256,
+        };
+        let header = text("header").unwrap_or_else(|| scheme.default_header().to_string());
+        let timestamp_header = match scheme {
+            Scheme::Hmac => Some(text("timestamp_header").unwrap_or_else(|| "x-signature-timestamp".to_string())),
+            // stripe mang timestamp trong header chữ ký, github không có
+            _ => None,
+        };
+        let nonce_header = text("nonce_header").or_else(|| match scheme {
+            Scheme::Hmac => Some("x-signature-nonce".to_string()),
+            Scheme::Github => Some("x-github-delivery".to_string()),
+            Scheme::Stripe => None,
+        });
+        let require_timestamp = match scheme {
+            Scheme::Hmac => get("require_timestamp").and_then(|v| v.as_bool()).unwrap_or(true),
+            Scheme::Github => false,
+            Scheme::Stripe => true,
+        };
+        let num = |k: &str, d: u64| get(k).and_then(|v| v.as_u64()).filter(|n| *n > 0).unwrap_or(d);
+
+        // "secret" hoặc "secrets", chỉ nhận "env:NAME": secret không nằm trong features.json, lịch sử hay API admin
+        let mut raw: Vec<String> = Vec::new();
+        if let Some(s) = o.get("secret").and_then(|v| v.as_str()) { raw.push(s.to_string()); }
+        if let Some(arr) = o.get("secrets").and_then(|v| v.as_array()) {
+            raw.extend(arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()));
+        }
+        let mut secrets = Vec::new();
+        for s in raw.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            if !module_utils::is_env_ref(s) {
+                errors.push(format!("{}: literal secrets are not accepted, use env:NAME", route));
+                continue;
+            }
+            match module_utils::resolve_secret(s) {
+                Ok(v) => secrets.push(v.into_bytes()),
+                Err(e) => errors.push(format!("{}: {}", route, e)),
+            }
+        }
+        if secrets.is_empty() {
+            errors.push(format!("{}: no usable secret, all requests will be rejected", route));
+        }
+
+        let policy = Self {
+            route: route.to_string(),
+            scheme,
+            algorithm,
+            header,
+            timestamp_header,
+            nonce_header,
+            require_timestamp,
+            tolerance_secs: num("tolerance_secs", DEFAULT_TOLERANCE_SECS),
+            replay_window_secs: num("replay_window_secs", DEFAULT_REPLAY_WINDOW_SECS),
+            secret_count: secrets.len(),
+            secrets,
+        };
+        (policy, errors)
+    }
+
+    fn decode_hex(s: &str) -> Result<Vec<u8>, SignatureError> {
+        hex::decode(s.trim()).map_err(|_| SignatureError::Malformed)
+    }
+
+    fn parse_signature(&self, value: &str, header: &impl Fn(&str) -> Option<String>) -> Result<ParsedSignature, SignatureError> {
+        match self.scheme {
+            // "t=1700000000,v1=abc,v1=def,v0=..." (v0 là chữ ký test của Stripe, bỏ qua)
+            Scheme::Stripe => {
+                let mut timestamp = None;
+                let mut signatures = Vec::new();
+                for part in value.split(',') {
+                    match part.trim().split_once('=') {
+                        Some(("t", t)) => timestamp = Some(t.trim().to_string()),
+                        Some(("v1", s)) => signatures.push(Self::decode_hex(s)?),
+                        Some(_) => {}
+                        None => return Err(SignatureError::Malformed),
+                    }
+                }
+                if signatures.is_empty() { return Err(SignatureError::Malformed); }
+                Ok(ParsedSignature { timestamp, signatures })
+            }
+            // "sha256=<hex>"; hmac chấp nhận cả hex không có tiền tố
+            Scheme::Hmac | Scheme::Github => {
+                let hex_part = match value.split_once('=') {
+                    Some((algo, sig)) if algo.trim().eq_ignore_ascii_case(self.algorithm.label()) => sig,
+                    Some(_) => return Err(SignatureError::Malformed),
+                    None if self.scheme == Scheme::Hmac => value,
+                    None => return Err(SignatureError::Malformed),
+                };
+                let timestamp = self.timestamp_header.as_deref().and_then(header);
+                Ok(ParsedSignature { timestamp, signatures: vec![Self::decode_hex(hex_part)?] })
+            }
+        }
+    }
+
+    // Kiểm tra chữ ký, timestamp và replay; chỉ request có chữ ký hợp lệ mới được ghi vào bộ nhớ chống replay
+    pub fn verify(&self, header: impl Fn(&str) -> Option<String>, body: &[u8], now: u64, cache_size: usize) -> Result<(), SignatureError> {
+        if self.secrets.is_empty() { return Err(SignatureError::NotConfigured); }
+        let value = header(&self.header).filter(|v| !v.trim().is_empty()).ok_or(SignatureError::Missing)?;
+        let parsed = self.parse_signature(value.trim(), &header)?;
+
+        let timestamp = match parsed.timestamp.as_deref().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            Some(t) => Some(t.parse::<u64>().map_err(|_| SignatureError::Malformed)?),
+            None if self.require_timestamp => return Err(SignatureError::MissingTimestamp),
+            None => None,
+        };
+        if let Some(ts) = timestamp {
+            if ts.abs_diff(now) > self.tolerance_secs { return Err(SignatureError::TimestampOutOfTolerance); }
+        }
+
+        let ts_prefix = timestamp.map(|ts| format!("{}.", ts)).unwrap_or_default();
+        let payload: [&[u8]; 2] = [ts_prefix.as_bytes(), body];
+        let matched = parsed.signatures.iter().find(|sig| {
+            self.secrets.iter().any(|secret| hmac_matches(self.algorithm, secret, &payload, sig))
+        });
+        let Some(sig) = matched else { return Err(SignatureError::Invalid) };
+
+        // Có timestamp: sau ts + tolerance request cũ đã bị từ chối nên không cần nhớ lâu hơn
+        let expires_at = match timestamp {
+            Some(ts) => ts.saturating_add(self.tolerance_secs),
+            None => now.saturating_add(self.replay_window_secs),
+        };
+        let mut keys = vec![format!("{}|sig:{}", self.route, hex::encode(sig))];
+        if let Some(n) = self.nonce_header.as_deref().and_then(&header).filter(|n| !n.trim().is_empty()) {
+            keys.push(format!("{}|nonce:{}", self.route, n.trim()));
+        }
+        SEEN.lock().remember(&keys, expires_at, now, cache_size)
+    }
+}
+
+// ---- Chống replay ----
+
+// khóa (route|sig:... / route|nonce:...) -> hết hạn (unix giây), kèm chỉ mục theo thời điểm hết hạn
+// để dọn mục hết hạn từ đầu mà không phải duyệt cả bảng
+#[derive(Default)]
+struct ReplayCache {
+    seen: HashMap<String, u64>,
+    by_expiry: BTreeSet<(u64, String)>,
+}
+
+impl ReplayCache {
+    fn purge_expired(&mut self, now: u64) {
+        while let Some((exp, _)) = self.by_expiry.first() {
+            if *exp > now { break; }
+            if let Some((_, k)) = self.by_expiry.pop_first() { self.seen.remove(&k); }
+        }
+    }
+
+    // Replayed nếu một trong các khóa đã được dùng; ReplayCacheFull nếu không còn chỗ mà không phải
+    // bỏ mục còn hiệu lực (bỏ chúng sẽ mở lại cửa cho replay); ngược lại ghi nhớ tất cả
+    fn remember(&mut self, keys: &[String], expires_at: u64, now: u64, cache_size: usize) -> Result<(), SignatureError> {
+        self.purge_expired(now);
+        if keys.iter().any(|k| self.seen.contains_key(k)) { return Err(SignatureError::Replayed); }
+        if self.seen.len() + keys.len() > cache_size { return Err(SignatureError::ReplayCacheFull); }
+        for k in keys {
+            self.seen.insert(k.clone(), expires_at);
+            self.by_expiry.insert((expires_at, k.clone()));
+        }
+        Ok(())
+    }
+}
+
+static SEEN: Lazy<Mutex<ReplayCache>> = Lazy::new(|| Mutex::new(ReplayCache::default()));
+
+// Số mục đang nhớ (hiển thị trên Admin)
+pub fn replay_cache_len(now: u64) -> usize {
+    let mut seen = SEEN.lock();
+    seen.purge_expired(now);
+    seen.seen.len()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SignatureConfig {
+    // Route được bảo vệ, sắp từ cụ thể nhất
+    pub routes: Vec<RoutePolicy>,
+    pub max_body_bytes: usize,
+    pub replay_cache_size: usize,
+    pub errors: Vec<String>,
+}
+
+impl SignatureConfig {
+    // Đọc từ feature_extras.request_signature; khóa phẳng (trừ secret) là mặc định cho mọi route:
+    // {"tolerance_secs": 300, "routes": {"/hooks/github": {"scheme": "github", "secrets": ["env:GITHUB_WEBHOOK_SECRET"]}}}
+    pub fn from_extras(extras: Option<&Value>) -> Self {
+        let empty = Map::new();
+        let obj = extras.and_then(|v| v.as_object()).unwrap_or(&empty);
+        let size = |k: &str, d: usize| obj.get(k).and_then(|v| v.as_u64()).filter(|n| *n > 0).map(|n| n as usize).unwrap_or(d);
+        let mut errors = Vec::new();
+        let mut routes = Vec::new();
+        if let Some(map) = obj.get("routes").and_then(|v| v.as_object()) {
+            for (route, over) in map {
+                let Some(over) = over.as_object() else {
+                    errors.push(format!("{}: route config must be an object", route));
+                    continue;
+                };
+                let (p, e) = RoutePolicy::compile(route, over, obj);
+                errors.extend(e);
+                routes.push(p);
+            }
+        }
+        routes.sort_by_key(|p| std::cmp::Reverse(route_specificity(&p.route)));
+        Self {
+            routes,
+            max_body_bytes: size("max_body_bytes", DEFAULT_MAX_BODY_BYTES),
+            replay_cache_size: size("replay_cache_size", DEFAULT_REPLAY_CACHE_SIZE),
+            errors,
+        }
+    }
+
+    // None = route không yêu cầu chữ ký
+    pub fn policy_for(&self, path: &str) -> Option<&RoutePolicy> {
+        self.routes.iter().find(|p| route_matches(&p.route, path))
+    }
+}
+
+// Cấu hình được cache; chỉ phân tích lại (và đọc lại biến môi trường) khi feature_extras.request_signature thay đổi
+static CACHE: ConfigCache<SignatureConfig> = ConfigCache::new();
+
+pub fn config_for(extras: Option<&Value>) -> Arc<SignatureConfig> {
+    CACHE.get(extras, SignatureConfig::from_extras)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn policy(route: Value) -> (RoutePolicy, Vec<String>) {
+        RoutePolicy::compile("/hooks/x", route.as_object().unwrap(), &Map::new())
+    }
+
+    // Mỗi test dùng route riêng để bộ nhớ chống replay (dùng chung toàn cục) không ảnh hưởng lẫn nhau
+    fn route_policy(route: &str, cfg: Value) -> RoutePolicy {
+        let (p, errors) = RoutePolicy::compile(route, cfg.as_object().unwrap(), &Map::new());
+        assert!(errors.is_empty(), "{:?}", errors);
+        p
+    }
+
+    fn headers(pairs: &[(&str, String)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect();
+        move |name| map.get(name).cloned()
+    }
+
+    fn hmac_headers(secret: &str, ts: u64, body: &[u8]) -> impl Fn(&str) -> Option<String> {
+        let payload = [format!("{}.", ts).into_bytes(), body.to_vec()].concat();
+        headers(&[
+            ("x-signature", format!("sha256={}", sign(Algorithm::Sha256, secret.as_bytes(), &payload))),
+            ("x-signature-timestamp", ts.to_string()),
+        ])
+    }
+
+    #[test]
+    fn valid_signatures_per_scheme() {
+        std::env::set_var("RS_TEST_SCHEMES", "scheme-secret");
+        let now = 1_700_000_000;
+        let body = br#"{"event":"ping"}"#;
+
+        let hmac = route_policy("/hooks/hmac", json!({"secret": "env:RS_TEST_SCHEMES"}));
+        assert_eq!(hmac.verify(hmac_headers("scheme-secret", now, body), body, now, 100), Ok(()));
+        assert_eq!(hmac.verify(hmac_headers("scheme-secret", now, body), b"tampered", now, 100), Err(SignatureError::Invalid));
+        // Thiếu timestamp (require_timestamp mặc định bật)
+        let no_ts = headers(&[("x-signature", format!("sha256={}", sign(Algorithm::Sha256, b"scheme-secret", body)))]);
+        assert_eq!(hmac.verify(no_ts, body, now, 100), Err(SignatureError::MissingTimestamp));
+
+        let sha512 = route_policy("/hooks/sha512", json!({"secret": "env:RS_TEST_SCHEMES", "algorithm": "sha512", "require_timestamp": false}));
+        let sig = sign(Algorithm::Sha512, b"scheme-secret", body);
+        assert_eq!(sha512.verify(headers(&[("x-signature", sig.clone())]), body, now, 100), Ok(()));
+        assert_eq!(sha512.verify(headers(&[("x-signature", format!("sha256={}", sig))]), body, now, 100), Err(SignatureError::Malformed));
+
+        let github = route_policy("/hooks/github", json!({"scheme": "github", "secret": "env:RS_TEST_SCHEMES"}));
+        let gh = headers(&[
+            ("x-hub-signature-256", format!("sha256={}", sign(Algorithm::Sha256, b"scheme-secret", body))),
+            ("x-github-delivery", "delivery-1".to_string()),
+        ]);
+        assert_eq!(github.verify(gh, body, now, 100), Ok(()));
+        assert_eq!(github.verify(headers(&[]), body, now, 100), Err(SignatureError::Missing));
+
+        let stripe = route_policy("/hooks/stripe", json!({"scheme": "stripe", "secret": "env:RS_TEST_SCHEMES"}));
+        let payload = [format!("{}.", now).into_bytes(), body.to_vec()].concat();
+        let good = sign(Algorithm::Sha256, b"scheme-secret", &payload);
+        let wrong = sign(Algorithm::Sha256, b"other-secret", &payload);
+        // Nhiều v1= (Stripe đang xoay secret): chỉ cần một chữ ký khớp; v0 bị bỏ qua
+        let value = format!("t={},v1={},v1={},v0={}", now, wrong, good, wrong);
+        assert_eq!(stripe.verify(headers(&[("stripe-signature", value)]), body, now, 100), Ok(()));
+        let only_wrong = format!("t={},v1={}", now, wrong);
+        assert_eq!(stripe.verify(headers(&[("stripe-signature", only_wrong)]), body, now, 100), Err(SignatureError::Invalid));
+        let no_v1 = format!("t={},v0={}", now, good);
+        assert_eq!(stripe.verify(headers(&[("stripe-signature", no_v1)]), body, now, 100), Err(SignatureError::Malformed));
+    }
+
+    #[test]
+    fn timestamp_tolerance_is_inclusive() {
+        std::env::set_var("RS_TEST_TOLERANCE", "tolerance-secret");
+        let p = route_policy("/hooks/tolerance", json!({"secret": "env:RS_TEST_TOLERANCE", "tolerance_secs": 300}));
+        let now = 1_700_000_000;
+        let check = |ts: u64| p.verify(hmac_headers("tolerance-secret", ts, b"x"), b"x", now, 100);
+        assert_eq!(check(now - 300), Ok(()));
+        assert_eq!(check(now + 300), Ok(()));
+        assert_eq!(check(now - 301), Err(SignatureError::TimestampOutOfTolerance));
+        assert_eq!(check(now + 301), Err(SignatureError::TimestampOutOfTolerance));
+    }
+
+    #[test]
+    fn rotated_out_secret_is_rejected() {
+        std::env::set_var("RS_TEST_ROTATE_NEW", "new-secret");
+        std::env::set_var("RS_TEST_ROTATE_OLD", "old-secret");
+        let both = route_policy("/hooks/rotate", json!({"secrets": ["env:RS_TEST_ROTATE_NEW", "env:RS_TEST_ROTATE_OLD"]}));
+        let now = 1_700_000_000;
+        assert_eq!(both.verify(hmac_headers("new-secret", now, b"a"), b"a", now, 100), Ok(()));
+        assert_eq!(both.verify(hmac_headers("old-secret", now, b"b"), b"b", now, 100), Ok(()));
+        let rotated = route_policy("/hooks/rotate", json!({"secrets": ["env:RS_TEST_ROTATE_NEW"]}));
+        assert_eq!(rotated.verify(hmac_headers("old-secret", now, b"c"), b"c", now, 100), Err(SignatureError::Invalid));
+    }
+
+    #[test]
+    fn replayed_signature_or_nonce_is_rejected() {
+        std::env::set_var("RS_TEST_REPLAY", "replay-secret");
+        let p = route_policy("/hooks/replay", json!({"secret": "env:RS_TEST_REPLAY"}));
+        let now = 1_700_000_000;
+        assert_eq!(p.verify(hmac_headers("replay-secret", now, b"a"), b"a", now, 100), Ok(()));
+        assert_eq!(p.verify(hmac_headers("replay-secret", now, b"a"), b"a", now + 1, 100), Err(SignatureError::Replayed));
+        assert_eq!(SignatureError::Replayed.status(), 409);
+
+        // Cùng nonce, chữ ký khác (body khác)
+        let with_nonce = |body: &[u8]| {
+            let h = hmac_headers("replay-secret", now, body);
+            move |name: &str| if name == "x-signature-nonce" { Some("nonce-1".to_string()) } else { h(name) }
+        };
+        assert_eq!(p.verify(with_nonce(b"b"), b"b", now, 100), Ok(()));
+        assert_eq!(p.verify(with_nonce(b"c"), b"c", now, 100), Err(SignatureError::Replayed));
+        // Chữ ký sai không được ghi nhớ: không chặn request hợp lệ sau đó
+        assert_eq!(p.verify(hmac_headers("wrong", now, b"d"), b"d", now, 100), Err(SignatureError::Invalid));
+        assert_eq!(p.verify(hmac_headers("replay-secret", now, b"d"), b"d", now, 100), Ok(()));
+    }
+
+    #[test]
+    fn expiry_does_not_overflow() {
+        std::env::set_var("RS_TEST_OVERFLOW", "overflow-secret");
+        let p = route_policy("/hooks/overflow", json!({"secret": "env:RS_TEST_OVERFLOW"}));
+        assert_eq!(p.verify(hmac_headers("overflow-secret", u64::MAX, b"a"), b"a", u64::MAX, 100), Ok(()));
+        let gh = route_policy("/hooks/overflow-gh", json!({"scheme": "github", "secret": "env:RS_TEST_OVERFLOW"}));
+        let sig = headers(&[("x-hub-signature-256", format!("sha256={}", sign(Algorithm::Sha256, b"overflow-secret", b"a")))]);
+        assert_eq!(gh.verify(sig, b"a", u64::MAX, 100), Ok(()));
+    }
+
+    #[test]
+    fn only_env_secrets_are_accepted() {
+        std::env::set_var("RS_TEST_SECRET", "from-env");
+        let (p, errors) = policy(json!({"scheme": "hmac", "secrets": ["literal", "env:RS_TEST_SECRET", "env:RS_TEST_UNSET"]}));
+        assert_eq!(p.secret_count, 1);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].contains("literal secrets are not accepted"));
+        assert!(errors[1].contains("RS_TEST_UNSET"));
+
+        let (p, _) = policy(json!({"scheme": "hmac", "secret": "literal"}));
+        let err = p.verify(|_| Some("sha256=00".to_string()), b"", now_secs(), 10).unwrap_err();
+        assert_eq!(err, SignatureError::NotConfigured);
+    }
+
+    #[test]
+    fn replay_cache_rejects_instead_of_evicting_live_entries() {
+        let mut c = ReplayCache::default();
+        let key = |s: &str| vec![s.to_string()];
+        assert_eq!(c.remember(&key("a"), 100, 10, 2), Ok(()));
+        assert_eq!(c.remember(&key("a"), 100, 10, 2), Err(SignatureError::Replayed));
+        assert_eq!(c.remember(&key("b"), 50, 10, 2), Ok(()));
+        assert_eq!(c.remember(&key("c"), 100, 10, 2), Err(SignatureError::ReplayCacheFull));
+        assert_eq!(SignatureError::ReplayCacheFull.status(), 503);
+        // "b" hết hạn -> có chỗ; "a" vẫn được nhớ
+        assert_eq!(c.remember(&key("c"), 100, 60, 2), Ok(()));
+        assert_eq!(c.remember(&key("a"), 100, 60, 3), Err(SignatureError::Replayed));
+        assert_eq!(c.seen.len(), c.by_expiry.len());
+    }
+}
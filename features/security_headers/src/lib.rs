@@ -0,0 +1,44 @@
+#![allow(non_snake_case)]
+#[cfg(feature = "plugin")]
+pub mod plugin {
+    use libc::c_char;
+    use std::ffi::CString;
+
+    // C-ABI symbol used by the dynamic loader to identify the feature
+    #[no_mangle]
+    pub extern "C" fn feature_name_security_headers() -> *mut c_char {
+        CString::new("security_headers").unwrap().into_raw()
+    }
+
+    // Manifest mô tả UI cấu hình cho Admin
+    // - Các khóa phẳng là mặc định cho mọi route plugin; chuỗi rỗng = không gửi header đó
+    // - routes: ghi đè từng khóa theo route, {"enabled": false} = không đặt header nào
+    fn manifest_json() -> String {
+        r#"{
+            "name": "security_headers",
+            "description": "CSP (kèm nonce cho handler HTML), HSTS, X-Frame-Options, X-Content-Type-Options, Referrer-Policy, Permissions-Policy; ghi đè theo route",
+            "settings": [
+              {"key": "csp", "type": "string", "label": "Content-Security-Policy ({nonce} = per-request nonce, empty = off)", "default": "default-src 'self'; script-src 'self' 'nonce-{nonce}'; style-src 'self' 'nonce-{nonce}'; object-src 'none'; base-uri 'self'; frame-ancestors 'none'"},
+              {"key": "csp_report_only", "type": "boolean", "label": "CSP Report-Only", "default": false},
+              {"key": "hsts", "type": "boolean", "label": "Strict-Transport-Security", "default": true},
+              {"key": "hsts_max_age", "type": "number", "label": "HSTS Max Age (seconds)", "default": 31536000},
+              {"key": "hsts_include_subdomains", "type": "boolean", "label": "HSTS includeSubDomains", "default": true},
+              {"key": "hsts_preload", "type": "boolean", "label": "HSTS preload", "default": false},
+              {"key": "frame_options", "type": "string", "label": "X-Frame-Options (DENY, SAMEORIGIN, empty = off)", "default": "DENY"},
+              {"key": "content_type_options", "type": "boolean", "label": "X-Content-Type-Options: nosniff", "default": true},
+              {"key": "referrer_policy", "type": "string", "label": "Referrer-Policy", "default": "strict-origin-when-cross-origin"},
+              {"key": "permissions_policy", "type": "string", "label": "Permissions-Policy", "default": "camera=(), microphone=(), geolocation=()"},
+              {"key": "routes", "type": "json", "label": "Per-Route Overrides (take precedence over defaults)", "default": {}, "example": {"/assets/*": {"csp": "default-src 'self'"}, "/embed/*": {"frame_options": "SAMEORIGIN"}, "/raw/*": {"enabled": false}}}
+            ]
+        }"#.to_string()
+    }
+
+    #[no_mangle]
+    pub extern "C" fn feature_manifest_security_headers() -> *mut c_char {
+        CString::new(manifest_json()).unwrap().into_raw()
+    }
+}
+
+// Logic chính sách (pure Rust) dùng bởi middleware của app (app/src/security_headers.rs)
+pub mod policy;
+pub use policy::{config_for, new_nonce, SecurityHeadersConfig, SecurityPolicy};
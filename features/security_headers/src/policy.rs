@@ -0,0 +1,340 @@
+// Chính sách security header: cấu hình mặc định + ghi đè theo route cho CSP, HSTS, X-Frame-Options,
+// X-Content-Type-Options, Referrer-Policy, Permissions-Policy; CSP có thể chứa "{nonce}" (nonce mới cho mỗi request)
+use module_utils::{route_matches, route_specificity, ConfigCache};
+use rand_core::{OsRng, RngCore};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+pub const NONCE_PLACEHOLDER: &str = "{nonce}";
+pub const DEFAULT_CSP: &str = "default-src 'self'; script-src 'self' 'nonce-{nonce}'; style-src 'self' 'nonce-{nonce}'; object-src 'none'; base-uri 'self'; frame-ancestors 'none'";
+pub const DEFAULT_HSTS_MAX_AGE: u64 = 31_536_000;
+pub const DEFAULT_FRAME_OPTIONS: &str = "DENY";
+pub const DEFAULT_REFERRER_POLICY: &str = "strict-origin-when-cross-origin";
+pub const DEFAULT_PERMISSIONS_POLICY: &str = "camera=(), microphone=(), geolocation=()";
+
+const REFERRER_POLICIES: &[&str] = &[
+    "no-referrer", "no-referrer-when-downgrade", "origin", "origin-when-cross-origin",
+    "same-origin", "strict-origin", "strict-origin-when-cross-origin", "unsafe-url",
+];
+
+// Các khóa cấu hình; None = lấy từ mặc định (khi ghi đè theo route) hoặc giá trị mặc định.
+// Chuỗi rỗng = không gửi header đó
+#[derive(Debug, Clone, Default)]
+struct HeaderSpec {
+    csp: Option<String>,
+    csp_report_only: Option<bool>,
+    hsts: Option<bool>,
+    hsts_max_age: Option<u64>,
+    hsts_include_subdomains: Option<bool>,
+    hsts_preload: Option<bool>,
+    frame_options: Option<String>,
+    content_type_options: Option<bool>,
+    referrer_policy: Option<String>,
+    permissions_policy: Option<String>,
+}
+
+// Nhận bool hoặc 0/1
+fn flag(v: Option<&Value>) -> Option<bool> {
+    v.map(|v| v.as_bool().unwrap_or_else(|| v.as_u64().is_some_and(|n| n != 0)))
+}
+
+fn text(v: Option<&Value>) -> Option<String> {
+    v.and_then(|v| v.as_str()).map(|s| s.trim().to_string())
+}
+
+impl HeaderSpec {
+    fn from_object(o: &Map<String, Value>) -> Self {
+        Self {
+            csp: text(o.get("csp")),
+            csp_report_only: flag(o.get("csp_report_only")),
+            hsts: flag(o.get("hsts")),
+            hsts_max_age: o.get("hsts_max_age").and_then(|v| v.as_u64()),
+            hsts_include_subdomains: flag(o.get("hsts_include_subdomains")),
+            hsts_preload: flag(o.get("hsts_preload")),
+            frame_options: text(o.get("frame_options")),
+            content_type_options: flag(o.get("content_type_options")),
+            referrer_policy: text(o.get("referrer_policy")),
+            permissions_policy: text(o.get("permissions_policy")),
+        }
+    }
+
+    // Giá trị của route ghi đè từng khóa của mặc định
+    fn merged(&self, over: &HeaderSpec) -> Self {
+        Self {
+            csp: over.csp.clone().or_else(|| self.csp.clone()),
+            csp_report_only: over.csp_report_only.or(self.csp_report_only),
+            hsts: over.hsts.or(self.hsts),
+            hsts_max_age: over.hsts_max_age.or(self.hsts_max_age),
+            hsts_include_subdomains: over.hsts_include_subdomains.or(self.hsts_include_subdomains),
+            hsts_preload: over.hsts_preload.or(self.hsts_preload),
+            frame_options: over.frame_options.clone().or_else(|| self.frame_options.clone()),
+            content_type_options: over.content_type_options.or(self.content_type_options),
+            referrer_policy: over.referrer_policy.clone().or_else(|| self.referrer_policy.clone()),
+            permissions_policy: over.permissions_policy.clone().or_else(|| self.permissions_policy.clone()),
+        }
+    }
+}
+
+// Giá trị header không được chứa ký tự điều khiển (xuống dòng = header injection)
+fn valid_value(s: &str) -> bool {
+    s.bytes().all(|b| b == b'\t' || (b' '..=b'~').contains(&b))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityPolicy {
+    // Pattern route áp dụng ("*" = mặc định)
+    pub route: String,
+    // Có thể chứa "{nonce}"
+    pub csp: Option<String>,
+    pub csp_report_only: bool,
+    pub hsts: Option<String>,
+    pub frame_options: Option<String>,
+    pub content_type_options: bool,
+    pub referrer_policy: Option<String>,
+    pub permissions_policy: Option<String>,
+}
+
+impl SecurityPolicy {
+    // Header có giá trị không hợp lệ bị bỏ (kèm lỗi), các header còn lại vẫn được gửi
+    fn compile(route: &str, spec: &HeaderSpec) -> (Self, Vec<String>) {
+        let mut errors = Vec::new();
+        let checked = |name: &str, v: Option<String>, errors: &mut Vec<String>| -> Option<String> {
+            let v = v.filter(|v| !v.is_empty())?;
+            if valid_value(&v) { return Some(v); }
+            errors.push(format!("{}: invalid {} value {:?}", route, name, v));
+            None
+        };
+        let csp = checked("csp", Some(spec.csp.clone().unwrap_or_else(|| DEFAULT_CSP.to_string())), &mut errors);
+        let permissions_policy = checked(
+            "permissions_policy",
+            Some(spec.permissions_policy.clone().unwrap_or_else(|| DEFAULT_PERMISSIONS_POLICY.to_string())),
+            &mut errors,
+        );
+
+        let frame_options = match spec.frame_options.as_deref().unwrap_or(DEFAULT_FRAME_OPTIONS).to_ascii_uppercase().as_str() {
+            "" => None,
+            v @ ("DENY" | "SAMEORIGIN") => Some(v.to_string()),
+            v => {
+                errors.push(format!("{}: frame_options must be DENY or SAMEORIGIN, got {:?}", route, v));
+                None
+            }
+        };
+
+        let referrer = spec.referrer_policy.as_deref().unwrap_or(DEFAULT_REFERRER_POLICY).to_ascii_lowercase();
+        // Cho phép danh sách dự phòng "no-referrer, strict-origin-when-cross-origin"
+        let referrer_policy = if referrer.is_empty() {
+            None
+        } else if referrer.split(',').map(|p| p.trim()).all(|p| REFERRER_POLICIES.contains(&p)) {
+            Some(referrer)
+        } else {
+            errors.push(format!("{}: invalid referrer_policy {:?}", route, referrer));
+            None
+        };
+
+        let hsts = spec.hsts.unwrap_or(true).then(|| {
+            let max_age = spec.hsts_max_age.unwrap_or(DEFAULT_HSTS_MAX_AGE);
+            let sub = spec.hsts_include_subdomains.unwrap_or(true);
+            let mut v = format!("max-age={}", max_age);
+            if sub { v.push_str("; includeSubDomains"); }
+            if spec.hsts_preload.unwrap_or(false) {
+                // Điều kiện của danh sách preload trình duyệt
+                if sub && max_age >= DEFAULT_HSTS_MAX_AGE {
+                    v.push_str("; preload");
+                } else {
+                    errors.push(format!("{}: hsts_preload requires hsts_include_subdomains and hsts_max_age >= {}", route, DEFAULT_HSTS_MAX_AGE));
+                }
+            }
+            v
+        });
+
+        let policy = Self {
+            route: route.to_string(),
+            csp,
+            csp_report_only: spec.csp_report_only.unwrap_or(false),
+            hsts,
+            frame_options,
+            content_type_options: spec.content_type_options.unwrap_or(true),
+            referrer_policy,
+            permissions_policy,
+        };
+        (policy, errors)
+    }
+
+    // CSP cần nonce mới cho mỗi request
+    pub fn uses_nonce(&self) -> bool {
+        self.csp.as_deref().is_some_and(|c| c.contains(NONCE_PLACEHOLDER))
+    }
+
+    // Header cần đặt; "{nonce}" trong CSP được thay bằng nonce của request
+    pub fn headers(&self, nonce: Option<&str>) -> Vec<(&'static str, String)> {
+        let mut out = Vec::new();
+        if let Some(csp) = &self.csp {
+            let name = if self.csp_report_only { "content-security-policy-report-only" } else { "content-security-policy" };
+            out.push((name, csp.replace(NONCE_PLACEHOLDER, nonce.unwrap_or_default())));
+        }
+        if let Some(v) = &self.hsts { out.push(("strict-transport-security", v.clone())); }
+        if let Some(v) = &self.frame_options { out.push(("x-frame-options", v.clone())); }
+        if self.content_type_options { out.push(("x-content-type-options", "nosniff".to_string())); }
+        if let Some(v) = &self.referrer_policy { out.push(("referrer-policy", v.clone())); }
+        if let Some(v) = &self.permissions_policy { out.push(("permissions-policy", v.clone())); }
+        out
+    }
+}
+
+// 128 bit ngẫu nhiên dạng hex (hợp lệ cho 'nonce-...' trong CSP)
+pub fn new_nonce() -> String {
+    let mut buf = [0u8; 16];
+    OsRng.fill_bytes(&mut buf);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SecurityHeadersConfig {
+    pub defaults: SecurityPolicy,
+    // Ghi đè theo route, sắp từ cụ thể nhất
+    pub routes: Vec<SecurityPolicy>,
+    // Route ghi đè với "enabled": false (không đặt header nào)
+    pub disabled_routes: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl SecurityHeadersConfig {
+    // Đọc từ feature_extras.security_headers: các khóa phẳng là mặc định, "routes" ghi đè từng khóa theo route
+    // {"frame_options": "DENY", "routes": {"/embed/*": {"frame_options": "SAMEORIGIN", "csp": ""}}}
+    pub fn from_extras(extras: Option<&Value>) -> Self {
+        let empty = Map::new();
+        let obj = extras.and_then(|v| v.as_object()).unwrap_or(&empty);
+        let base = HeaderSpec::from_object(obj);
+        let (defaults, mut errors) = SecurityPolicy::compile("*", &base);
+        let mut routes = Vec::new();
+        let mut disabled_routes = Vec::new();
+        if let Some(map) = obj.get("routes").and_then(|v| v.as_object()) {
+            for (route, over) in map {
+                let Some(over) = over.as_object() else {
+                    errors.push(format!("{}: route override must be an object", route));
+                    continue;
+                };
+                if over.get("enabled").and_then(|v| v.as_bool()) == Some(false) {
+                    disabled_routes.push(route.clone());
+                    continue;
+                }
+                let (p, e) = SecurityPolicy::compile(route, &base.merged(&HeaderSpec::from_object(over)));
+                errors.extend(e);
+                routes.push(p);
+            }
+        }
+        routes.sort_by_key(|p| std::cmp::Reverse(route_specificity(&p.route)));
+        Self { defaults, routes, disabled_routes, errors }
+    }
+
+    // Chính sách áp cho path; None = route bị tắt
+    pub fn policy_for(&self, path: &str) -> Option<&SecurityPolicy> {
+        let disabled = self.disabled_routes.iter().filter(|r| route_matches(r, path)).max_by_key(|r| route_specificity(r));
+        let over = self.routes.iter().find(|p| route_matches(&p.route, path));
+        // Ghi đè cụ thể hơn thắng
+        match (over, disabled) {
+            (Some(p), Some(d)) if route_specificity(d) > route_specificity(&p.route) => None,
+            (Some(p), _) => Some(p),
+            (None, Some(_)) => None,
+            (None, None) => Some(&self.defaults),
+        }
+    }
+}
+
+// Cấu hình được cache; chỉ phân tích lại khi feature_extras.security_headers thay đổi
+static CACHE: ConfigCache<SecurityHeadersConfig> = ConfigCache::new();
+
+pub fn config_for(extras: Option<&Value>) -> Arc<SecurityHeadersConfig> {
+    CACHE.get(extras, SecurityHeadersConfig::from_extras)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn header<'a>(headers: &'a [(&'static str, String)], name: &str) -> Option<&'a str> {
+        headers.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn route_override_takes_precedence() {
+        let cfg = SecurityHeadersConfig::from_extras(Some(&json!({
+            "frame_options": "DENY",
+            "referrer_policy": "no-referrer",
+            "routes": {
+                "/embed/*": {"frame_options": "SAMEORIGIN"},
+                "/embed/raw/*": {"enabled": false},
+                "/raw/*": {"enabled": false},
+                "/raw/page": {"csp": ""}
+            }
+        })));
+        assert!(cfg.errors.is_empty(), "{:?}", cfg.errors);
+        assert_eq!(cfg.policy_for("/greet").unwrap().frame_options.as_deref(), Some("DENY"));
+        let embed = cfg.policy_for("/embed/widget").unwrap();
+        assert_eq!(embed.frame_options.as_deref(), Some("SAMEORIGIN"));
+        // Khóa không ghi đè vẫn lấy từ mặc định
+        assert_eq!(embed.referrer_policy.as_deref(), Some("no-referrer"));
+        // Route tắt cụ thể hơn thắng ghi đè chung hơn, và ngược lại
+        assert!(cfg.policy_for("/embed/raw/x").is_none());
+        assert!(cfg.policy_for("/raw/other").is_none());
+        assert!(cfg.policy_for("/raw/page").unwrap().csp.is_none());
+    }
+
+    #[test]
+    fn nonce_is_unique_and_substituted() {
+        let cfg = SecurityHeadersConfig::from_extras(None);
+        let policy = cfg.policy_for("/page").unwrap();
+        assert!(policy.uses_nonce());
+        let (a, b) = (new_nonce(), new_nonce());
+        assert_ne!(a, b);
+        assert_eq!(a.len(), 32);
+        let first = policy.headers(Some(&a));
+        let second = policy.headers(Some(&b));
+        let csp = header(&first, "content-security-policy").unwrap();
+        assert!(csp.contains(&format!("script-src 'self' 'nonce-{}'", a)));
+        assert!(!csp.contains(NONCE_PLACEHOLDER));
+        assert_ne!(header(&first, "content-security-policy"), header(&second, "content-security-policy"));
+    }
+
+    #[test]
+    fn csp_without_placeholder_needs_no_nonce() {
+        let cfg = SecurityHeadersConfig::from_extras(Some(&json!({"csp": "default-src 'self'", "csp_report_only": true})));
+        let policy = cfg.policy_for("/page").unwrap();
+        assert!(!policy.uses_nonce());
+        let headers = policy.headers(None);
+        assert_eq!(header(&headers, "content-security-policy-report-only"), Some("default-src 'self'"));
+        assert!(header(&headers, "content-security-policy").is_none());
+    }
+
+    #[test]
+    fn hsts_only_when_configured() {
+        let off = SecurityHeadersConfig::from_extras(Some(&json!({
+            "hsts": false,
+            "routes": {"/secure/*": {"hsts": true, "hsts_max_age": 600, "hsts_include_subdomains": false}}
+        })));
+        assert!(header(&off.policy_for("/page").unwrap().headers(None), "strict-transport-security").is_none());
+        let secure = off.policy_for("/secure/x").unwrap().headers(None);
+        assert_eq!(header(&secure, "strict-transport-security"), Some("max-age=600"));
+
+        let on = SecurityHeadersConfig::from_extras(Some(&json!({"hsts_preload": true})));
+        assert_eq!(
+            header(&on.policy_for("/page").unwrap().headers(None), "strict-transport-security"),
+            Some("max-age=31536000; includeSubDomains; preload")
+        );
+        // preload không đủ điều kiện bị bỏ kèm lỗi
+        let weak = SecurityHeadersConfig::from_extras(Some(&json!({"hsts_preload": true, "hsts_max_age": 60})));
+        assert_eq!(weak.defaults.hsts.as_deref(), Some("max-age=60; includeSubDomains"));
+        assert_eq!(weak.errors.len(), 1);
+    }
+
+    #[test]
+    fn invalid_values_are_dropped() {
+        let cfg = SecurityHeadersConfig::from_extras(Some(&json!({"frame_options": "ALLOW-FROM x", "csp": "default-src\r\nx: y"})));
+        assert!(cfg.defaults.frame_options.is_none());
+        assert!(cfg.defaults.csp.is_none());
+        assert_eq!(cfg.errors.len(), 2);
+        assert!(cfg.defaults.content_type_options);
+    }
+}
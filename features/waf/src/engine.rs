@@ -0,0 +1,614 @@
+// Rule engine của WAF:
+// - mỗi rule có id, mức độ (severity -> điểm), các phần request cần xét (target),
+//   chuỗi biến đổi (transform) và một matcher (regex / contains / max_length)
+// - điểm của các rule khớp được cộng dồn (anomaly scoring); đạt ngưỡng thì chặn
+// - rule (hoặc cả engine) ở chế độ detect only chỉ ghi nhận, không góp điểm chặn
+use module_utils::ConfigCache;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+// Ruleset mặc định đi kèm crate
+pub const DEFAULT_RULES: &str = include_str!("../rules/default.json");
+pub const DEFAULT_THRESHOLD: u32 = 5;
+// Id cho các pattern kiểu cũ (feature_extras.waf.patterns)
+pub const LEGACY_PATTERN_BASE_ID: u32 = 100_000;
+// Đoạn giá trị khớp được giữ lại để log
+const SNIPPET_LEN: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    #[default]
+    Critical,
+    Error,
+    Warning,
+    Notice,
+}
+
+impl Severity {
+    // Điểm mặc định theo mức độ (giống OWASP CRS)
+    pub fn score(&self) -> u32 {
+        match self {
+            Self::Critical => 5,
+            Self::Error => 4,
+            Self::Warning => 3,
+            Self::Notice => 2,
+        }
+    }
+}
+
+// Định nghĩa rule dạng JSON (ruleset mặc định và feature_extras.waf.rules dùng chung định dạng)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSpec {
+    pub id: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub severity: Severity,
+    // Ghi đè điểm theo severity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<u32>,
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub transforms: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    // Khớp nếu chứa một trong các chuỗi (không phân biệt hoa thường)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contains: Vec<String>,
+    // Khớp nếu giá trị dài hơn n byte
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    // Chỉ ghi nhận khi khớp, không tính vào điểm chặn
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub detect_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Method,
+    Path,
+    // Query string thô
+    Query,
+    // path + "?" + query, chưa decode (giống cách so khớp cũ)
+    Uri,
+    // Giá trị / tên tham số query (đã decode)
+    Args,
+    ArgNames,
+    Headers,
+    HeaderNames,
+    Header(String),
+    Cookies,
+    CookieNames,
+    Body,
+    // Giá trị / tên key đã tách từ body JSON, form (text: toàn bộ body)
+    BodyArgs,
+    BodyArgNames,
+}
+
+impl Target {
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some(name) = s.strip_prefix("header:").map(|n| n.trim()).filter(|n| !n.is_empty()) {
+            return Some(Self::Header(name.to_ascii_lowercase()));
+        }
+        match s.to_ascii_lowercase().as_str() {
+            "method" => Some(Self::Method),
+            "path" => Some(Self::Path),
+            "query" => Some(Self::Query),
+            "uri" => Some(Self::Uri),
+            "args" => Some(Self::Args),
+            "arg_names" => Some(Self::ArgNames),
+            "headers" => Some(Self::Headers),
+            "header_names" => Some(Self::HeaderNames),
+            "cookies" => Some(Self::Cookies),
+            "cookie_names" => Some(Self::CookieNames),
+            "body" => Some(Self::Body),
+            "body_args" => Some(Self::BodyArgs),
+            "body_arg_names" => Some(Self::BodyArgNames),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    UrlDecode,
+    HtmlDecode,
+    Lowercase,
+    CompressWhitespace,
+    RemoveNulls,
+}
+
+impl Transform {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "url_decode" => Some(Self::UrlDecode),
+            "html_decode" => Some(Self::HtmlDecode),
+            "lowercase" => Some(Self::Lowercase),
+            "compress_whitespace" => Some(Self::CompressWhitespace),
+            "remove_nulls" => Some(Self::RemoveNulls),
+            _ => None,
+        }
+    }
+
+    fn apply<'a>(&self, v: Cow<'a, str>) -> Cow<'a, str> {
+        match self {
+            Self::UrlDecode => {
+                // Decode tối đa 2 lần để bắt cả payload bị encode kép (%252e)
+                let once = url_decode(&v);
+                if once.contains('%') { Cow::Owned(url_decode(&once)) } else { Cow::Owned(once) }
+            }
+            Self::HtmlDecode if v.contains('&') => Cow::Owned(html_decode(&v)),
+            Self::Lowercase if v.chars().any(|c| c.is_uppercase()) => Cow::Owned(v.to_lowercase()),
+            Self::CompressWhitespace if v.chars().any(char::is_whitespace) => {
+                Cow::Owned(v.split_whitespace().collect::<Vec<_>>().join(" "))
+            }
+            Self::RemoveNulls if v.contains('\0') => Cow::Owned(v.replace('\0', "")),
+            _ => v,
+        }
+    }
+}
+
+// Decode %XX và '+' (form encoding); byte không hợp lệ UTF-8 được thay bằng U+FFFD
+pub fn url_decode(s: &str) -> String {
+    let plus = s.replace('+', " ");
+    percent_encoding::percent_decode_str(&plus).decode_utf8_lossy().into_owned()
+}
+
+// Decode các entity HTML thường dùng để né bộ lọc: &lt; &gt; &quot; &apos; &amp; &#NN; &#xNN;
+pub fn html_decode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let end = rest.find(';').filter(|e| *e <= 10);
+        let decoded = end.and_then(|e| {
+            let ent = &rest[1..e];
+            let c = match ent {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "amp" => Some('&'),
+                "colon" => Some(':'),
+                "lpar" => Some('('),
+                "rpar" => Some(')'),
+                _ => ent
+                    .strip_prefix("#x")
+                    .or_else(|| ent.strip_prefix("#X"))
+                    .and_then(|h| u32::from_str_radix(h, 16).ok())
+                    .or_else(|| ent.strip_prefix('#').and_then(|d| d.parse::<u32>().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, e))
+        });
+        match decoded {
+            Some((c, e)) => {
+                out.push(c);
+                rest = &rest[e + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Regex(Regex),
+    Contains(Vec<String>),
+    MaxLength(usize),
+}
+
+impl Matcher {
+    // Trả về đoạn khớp (để log) nếu khớp
+    fn find(&self, v: &str) -> Option<String> {
+        match self {
+            Self::Regex(re) => re.find(v).map(|m| m.as_str().to_string()),
+            Self::Contains(needles) => {
+                let lower = v.to_lowercase();
+                needles.iter().find(|n| lower.contains(n.as_str())).cloned()
+            }
+            Self::MaxLength(n) => (v.len() > *n).then(|| format!("length {} > {}", v.len(), n)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pub spec: RuleSpec,
+    // "default" | "custom" | "pattern"
+    pub source: &'static str,
+    targets: Vec<Target>,
+    transforms: Vec<Transform>,
+    matcher: Matcher,
+}
+
+impl Rule {
+    pub fn compile(spec: RuleSpec, source: &'static str) -> Result<Self, String> {
+        let targets = spec
+            .targets
+            .iter()
+            .map(|t| Target::parse(t).ok_or_else(|| format!("rule {}: unknown target '{}'", spec.id, t)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if targets.is_empty() { return Err(format!("rule {}: no targets", spec.id)); }
+        let transforms = spec
+            .transforms
+            .iter()
+            .map(|t| Transform::parse(t).ok_or_else(|| format!("rule {}: unknown transform '{}'", spec.id, t)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let matcher = if let Some(re) = &spec.regex {
+            let re = RegexBuilder::new(re)
+                .size_limit(1 << 20)
+                .build()
+                .map_err(|e| format!("rule {}: invalid regex: {}", spec.id, e))?;
+            Matcher::Regex(re)
+        } else if !spec.contains.is_empty() {
+            Matcher::Contains(spec.contains.iter().map(|s| s.to_lowercase()).collect())
+        } else if let Some(n) = spec.max_length {
+            Matcher::MaxLength(n)
+        } else {
+            return Err(format!("rule {}: needs regex, contains or max_length", spec.id));
+        };
+        Ok(Self { spec, source, targets, transforms, matcher })
+    }
+
+    pub fn score(&self) -> u32 {
+        self.spec.score.unwrap_or_else(|| self.spec.severity.score())
+    }
+}
+
+// Các phần của request cần kiểm tra; header name viết thường
+#[derive(Debug, Default, Clone)]
+pub struct RequestParts {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    // Kết quả limits::parse_body; nhãn "body_names:..." là tên key
+    pub body_args: Vec<(String, String)>,
+}
+
+impl RequestParts {
+    fn args(&self) -> Vec<(String, String)> {
+        self.query
+            .as_deref()
+            .unwrap_or("")
+            .split('&')
+            .filter(|kv| !kv.is_empty())
+            .map(|kv| {
+                let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+                (url_decode(k), url_decode(v))
+            })
+            .collect()
+    }
+
+    fn cookies(&self) -> Vec<(String, String)> {
+        self.headers
+            .iter()
+            .filter(|(n, _)| n == "cookie")
+            .flat_map(|(_, v)| v.split(';'))
+            .filter_map(|kv| {
+                let (k, v) = kv.split_once('=')?;
+                Some((k.trim().to_string(), v.trim().to_string()))
+            })
+            .collect()
+    }
+
+    // (nhãn, giá trị) cho một target; nhãn dùng khi log, vd "args:q", "header:user-agent"
+    fn values(&self, t: &Target) -> Vec<(String, String)> {
+        let named = |prefix: &str, items: Vec<(String, String)>, names: bool| -> Vec<(String, String)> {
+            items
+                .into_iter()
+                .map(|(k, v)| if names { (format!("{}_names", prefix), k) } else { (format!("{}:{}", prefix, k), v) })
+                .collect()
+        };
+        match t {
+            Target::Method => vec![("method".into(), self.method.clone())],
+            Target::Path => vec![("path".into(), self.path.clone())],
+            Target::Query => self.query.iter().map(|q| ("query".into(), q.clone())).collect(),
+            Target::Uri => vec![(
+                "uri".into(),
+                match &self.query {
+                    Some(q) => format!("{}?{}", self.path, q),
+                    None => self.path.clone(),
+                },
+            )],
+            Target::Args => named("args", self.args(), false),
+            Target::ArgNames => named("arg", self.args(), true),
+            Target::Headers => named("header", self.headers.clone(), false),
+            Target::HeaderNames => named("header", self.headers.clone(), true),
+            Target::Header(name) => self
+                .headers
+                .iter()
+                .filter(|(n, _)| n == name)
+                .map(|(n, v)| (format!("header:{}", n), v.clone()))
+                .collect(),
+            Target::Cookies => named("cookie", self.cookies(), false),
+            Target::CookieNames => named("cookie", self.cookies(), true),
+            Target::Body => self.body.iter().map(|b| ("body".into(), b.clone())).collect(),
+            Target::BodyArgs => self.body_args.iter().filter(|(l, _)| !l.starts_with("body_names:")).cloned().collect(),
+            Target::BodyArgNames => self.body_args.iter().filter(|(l, _)| l.starts_with("body_names:")).cloned().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleMatch {
+    pub id: u32,
+    pub name: String,
+    pub severity: Severity,
+    pub score: u32,
+    // Phần request khớp, vd "args:q"
+    pub target: String,
+    // Đoạn giá trị khớp (đã qua transform, cắt ngắn)
+    pub matched: String,
+    pub detect_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Verdict {
+    // Điểm từ các rule đang chặn
+    pub score: u32,
+    // Điểm nếu mọi rule khớp đều được tính (kể cả detect only)
+    pub total_score: u32,
+    pub threshold: u32,
+    pub blocked: bool,
+    // Có bị chặn không nếu tắt mọi chế độ detect only; dùng để xem trước false positive
+    pub would_block: bool,
+    pub detect_only: bool,
+    pub matches: Vec<RuleMatch>,
+}
+
+impl Verdict {
+    pub fn rule_ids(&self) -> Vec<u32> {
+        self.matches.iter().map(|m| m.id).collect()
+    }
+}
+
+fn snippet(s: &str) -> String {
+    if s.len() <= SNIPPET_LEN { return s.to_string(); }
+    let mut end = SNIPPET_LEN;
+    while !s.is_char_boundary(end) { end -= 1; }
+    format!("{}…", &s[..end])
+}
+
+#[derive(Debug)]
+pub struct Engine {
+    pub rules: Vec<Rule>,
+    pub threshold: u32,
+    // mode = "detect_only": ghi nhận nhưng không chặn request nào
+    pub detect_only: bool,
+    // Rule cấu hình sai (bị bỏ qua)
+    pub errors: Vec<String>,
+}
+
+impl Engine {
+    // Dựng engine từ feature_extras.waf:
+    // default_rules (bool, mặc định true), disabled_rules ([id]), detect_only_rules ([id]), rules ([RuleSpec]),
+    // patterns (chuỗi con kiểu cũ trên uri + user-agent), anomaly_threshold, mode ("block" | "detect_only")
+    pub fn from_extras(extras: Option<&Value>) -> Self {
+        let get = |k: &str| extras.and_then(|o| o.get(k));
+        let mut errors = Vec::new();
+        let mut specs: Vec<(RuleSpec, &'static str)> = Vec::new();
+        if get("default_rules").and_then(|v| v.as_bool()).unwrap_or(true) {
+            match serde_json::from_str::<Vec<RuleSpec>>(DEFAULT_RULES) {
+                Ok(list) => specs.extend(list.into_iter().map(|r| (r, "default"))),
+                Err(e) => errors.push(format!("default ruleset: {}", e)),
+            }
+        }
+        for (i, v) in get("rules").and_then(|v| v.as_array()).into_iter().flatten().enumerate() {
+            match serde_json::from_value::<RuleSpec>(v.clone()) {
+                Ok(spec) => {
+                    // Rule tùy biến cùng id thay thế rule mặc định
+                    specs.retain(|(s, _)| s.id != spec.id);
+                    specs.push((spec, "custom"));
+                }
+                Err(e) => errors.push(format!("rules[{}]: {}", i, e)),
+            }
+        }
+        let patterns = get("patterns").and_then(|v| v.as_array()).into_iter().flatten().filter_map(|v| v.as_str());
+        for (i, p) in patterns.filter(|p| !p.is_empty()).enumerate() {
+            specs.push((
+                RuleSpec {
+                    id: LEGACY_PATTERN_BASE_ID + i as u32,
+                    name: format!("Custom pattern '{}'", p),
+                    severity: Severity::Critical,
+                    score: None,
+                    targets: vec!["uri".into(), "header:user-agent".into()],
+                    transforms: vec![],
+                    regex: None,
+                    contains: vec![p.to_string()],
+                    max_length: None,
+                    tags: vec!["pattern".into()],
+                    detect_only: false,
+                },
+                "pattern",
+            ));
+        }
+        let disabled: Vec<u64> = get("disabled_rules")
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().filter_map(|x| x.as_u64()).collect())
+            .unwrap_or_default();
+        let detect_only_rules: Vec<u64> = get("detect_only_rules")
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().filter_map(|x| x.as_u64()).collect())
+            .unwrap_or_default();
+        let mut rules = Vec::new();
+        for (mut spec, source) in specs {
+            if disabled.contains(&(spec.id as u64)) { continue; }
+            if detect_only_rules.contains(&(spec.id as u64)) { spec.detect_only = true; }
+            match Rule::compile(spec, source) {
+                Ok(r) => rules.push(r),
+                Err(e) => errors.push(e),
+            }
+        }
+        let threshold = get("anomaly_threshold")
+            .and_then(|v| v.as_u64())
+            .filter(|n| *n > 0)
+            .map(|n| n.min(u32::MAX as u64) as u32)
+            .unwrap_or(DEFAULT_THRESHOLD);
+        let detect_only = get("mode").and_then(|v| v.as_str()).is_some_and(|m| m.trim().eq_ignore_ascii_case("detect_only"));
+        Self { rules, threshold, detect_only, errors }
+    }
+
+    pub fn inspect(&self, req: &RequestParts) -> Verdict {
+        let mut matches = Vec::new();
+        for rule in &self.rules {
+            // Mỗi rule tính điểm tối đa một lần (lần khớp đầu tiên)
+            let hit = rule.targets.iter().flat_map(|t| req.values(t)).find_map(|(label, value)| {
+                let v = rule.transforms.iter().fold(Cow::Borrowed(value.as_str()), |v, t| t.apply(v));
+                rule.matcher.find(&v).map(|m| (label, m))
+            });
+            if let Some((target, matched)) = hit {
+                matches.push(RuleMatch {
+                    id: rule.spec.id,
+                    name: rule.spec.name.clone(),
+                    severity: rule.spec.severity,
+                    score: rule.score(),
+                    target,
+                    matched: snippet(&matched),
+                    detect_only: rule.spec.detect_only,
+                });
+            }
+        }
+        let score = matches.iter().filter(|m| !m.detect_only).map(|m| m.score).sum();
+        let total_score = matches.iter().map(|m| m.score).sum();
+        Verdict {
+            score,
+            total_score,
+            threshold: self.threshold,
+            blocked: !self.detect_only && score >= self.threshold,
+            would_block: total_score >= self.threshold,
+            detect_only: self.detect_only,
+            matches,
+        }
+    }
+
+    // Danh sách rule cho Admin
+    pub fn describe(&self) -> Value {
+        let rules: Vec<Value> = self
+            .rules
+            .iter()
+            .map(|r| {
+                let mut v = serde_json::to_value(&r.spec).unwrap_or_default();
+                v["score"] = r.score().into();
+                v["source"] = r.source.into();
+                v
+            })
+            .collect();
+        serde_json::json!({
+            "threshold": self.threshold,
+            "mode": if self.detect_only { "detect_only" } else { "block" },
+            "rules": rules,
+            "errors": self.errors,
+        })
+    }
+}
+
+// Engine được cache theo cấu hình; chỉ biên dịch lại regex khi feature_extras.waf thay đổi
+static CACHE: ConfigCache<Engine> = ConfigCache::new();
+
+pub fn engine_for(extras: Option<&Value>) -> Arc<Engine> {
+    CACHE.get(extras, Engine::from_extras)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(path: &str, query: Option<&str>) -> RequestParts {
+        RequestParts {
+            method: "GET".into(),
+            path: path.into(),
+            query: query.map(|q| q.to_string()),
+            headers: vec![("user-agent".into(), "Mozilla/5.0".into())],
+            ..Default::default()
+        }
+    }
+
+    fn with_header(mut req: RequestParts, name: &str, value: &str) -> RequestParts {
+        req.headers.retain(|(n, _)| n != name);
+        req.headers.push((name.into(), value.into()));
+        req
+    }
+
+    fn with_body_arg(mut req: RequestParts, value: &str) -> RequestParts {
+        req.body_args.push(("body:$.v".into(), value.into()));
+        req
+    }
+
+    fn matched(engine: &Engine, req: &RequestParts) -> Vec<u32> {
+        engine.inspect(req).rule_ids()
+    }
+
+    #[test]
+    fn html_decode_entities() {
+        assert_eq!(html_decode("&lt;script&gt;"), "<script>");
+        assert_eq!(html_decode("&#60;&#x3C;&#X3c;"), "<<<");
+        assert_eq!(html_decode("javascript&colon;alert&lpar;1&rpar;"), "javascript:alert(1)");
+        assert_eq!(html_decode("&quot;&apos;&amp;lt;"), "\"'&lt;");
+        // Entity lạ / không đóng / quá dài được giữ nguyên
+        assert_eq!(html_decode("a & b &unknown; &#xZZ; &lt"), "a & b &unknown; &#xZZ; &lt");
+        assert_eq!(html_decode("&#1114112;"), "&#1114112;");
+    }
+
+    #[test]
+    fn url_decode_runs_twice_for_double_encoding() {
+        assert_eq!(url_decode("a+b%20c"), "a b c");
+        assert_eq!(url_decode("%ff"), "\u{fffd}");
+        let t = Transform::UrlDecode;
+        assert_eq!(t.apply(Cow::Borrowed("%253Cscript%253E")), "<script>");
+        assert_eq!(t.apply(Cow::Borrowed("%2e%2e%2f")), "../");
+        // Chỉ decode tối đa 2 lần
+        assert_eq!(t.apply(Cow::Borrowed("%25252e")), "%2e");
+        // Query đã được decode một lần khi tách args, transform decode thêm hai lần
+        let engine = Engine::from_extras(None);
+        assert!(matched(&engine, &get("/greet", Some("q=%25253Cscript%25253E"))).contains(&941100));
+    }
+
+    #[test]
+    fn each_default_rule_has_a_hit_and_a_clean_request() {
+        let engine = Engine::from_extras(None);
+        assert!(engine.errors.is_empty(), "{:?}", engine.errors);
+        let cases: Vec<(u32, RequestParts, RequestParts)> = vec![
+            (913100, with_header(get("/", None), "user-agent", "sqlmap/1.7"), get("/", None)),
+            (920100, with_header(get("/", None), "user-agent", &"a".repeat(1025)), with_header(get("/", None), "user-agent", &"a".repeat(1024))),
+            (920200, get("/", Some("q=a%2500b")), get("/", Some("q=a%2520b"))),
+            (930100, get("/files", Some("name=..%2F..%2Fsecret")), get("/files", Some("name=a..b.txt"))),
+            (930120, with_body_arg(get("/", None), "/ETC/passwd"), with_body_arg(get("/", None), "/etc/hosts.allow")),
+            (932100, get("/", Some("host=1.1.1.1;%20cat%20/x")), get("/", Some("host=category"))),
+            (933100, get("/", Some("f=php://filter")), get("/", Some("f=graphql"))),
+            (941100, get("/", Some("q=%3Cscript%3Ealert(1)")), get("/", Some("q=scripting"))),
+            (941110, get("/", Some("q=%22%20onerror=alert(1)")), get("/", Some("q=online"))),
+            (941120, get("/", Some("u=javascript%26colon%3Balert(1)")), get("/", Some("u=https://example.com"))),
+            (941130, get("/", Some("q=%3Ciframe%20src=x%3E")), get("/", Some("q=iframe"))),
+            (942100, get("/", Some("id=1%20UNION/**/SELECT%20pw")), get("/", Some("q=union%20members%20select"))),
+            (942110, get("/", Some("id=1%27%20or%201=1")), get("/", Some("q=this%20or%20that"))),
+            (942120, get("/", Some("id=1;%20DROP%20TABLE%20users")), get("/", Some("q=drop%20shipping"))),
+            (942130, get("/", Some("id=1%20and%20sleep(5)")), get("/", Some("q=sleepy"))),
+            (942140, get("/", Some("user=admin%27--")), get("/", Some("user=o%27brien"))),
+            (944100, with_header(get("/", None), "x-api-version", "${jndi:ldap://x}"), with_header(get("/", None), "x-api-version", "${version}")),
+        ];
+        let ids: Vec<u32> = engine.rules.iter().map(|r| r.spec.id).collect();
+        assert_eq!(ids.len(), cases.len(), "every default rule needs a case");
+        for (id, hit, clean) in &cases {
+            assert!(ids.contains(id), "rule {} is not a default rule", id);
+            assert!(matched(&engine, hit).contains(id), "rule {} did not match {:?}", id, hit);
+            let clean_ids = matched(&engine, clean);
+            assert!(clean_ids.is_empty(), "clean request for rule {} matched {:?}", id, clean_ids);
+        }
+    }
+}
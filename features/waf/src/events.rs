@@ -0,0 +1,179 @@
+// Nhật ký WAF trong bộ nhớ: ring buffer các request bị chặn hoặc khớp rule (detect only / dưới ngưỡng)
+// Mỗi sự kiện có correlation id, trả về cho client trong response bị chặn để đối chiếu
+use crate::engine::{RuleMatch, Verdict};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+pub const DEFAULT_CAPACITY: usize = 500;
+const MAX_CAPACITY: usize = 10_000;
+// Độ dài tối đa của query / user-agent lưu trong sự kiện
+const FIELD_LEN: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Blocked,
+    // Khớp rule nhưng không chặn (detect only hoặc chưa đạt ngưỡng)
+    Detected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WafEvent {
+    pub correlation_id: String,
+    pub timestamp: String,
+    pub action: Action,
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    // X-Request-Id do client/proxy gửi kèm (nếu có)
+    pub request_id: Option<String>,
+    pub score: u32,
+    pub total_score: u32,
+    pub threshold: u32,
+    pub would_block: bool,
+    pub rule_ids: Vec<u32>,
+    pub matches: Vec<RuleMatch>,
+}
+
+// Thông tin request để ghi sự kiện
+#[derive(Debug, Default, Clone)]
+pub struct RequestSummary {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EventStats {
+    pub blocked: u64,
+    pub detected: u64,
+    // Số lần khớp theo rule id
+    pub by_rule: BTreeMap<u32, u64>,
+    pub capacity: usize,
+    pub buffered: usize,
+}
+
+struct Log {
+    events: VecDeque<WafEvent>,
+    capacity: usize,
+    stats: EventStats,
+}
+
+static LOG: Lazy<Mutex<Log>> = Lazy::new(|| {
+    Mutex::new(Log { events: VecDeque::new(), capacity: DEFAULT_CAPACITY, stats: EventStats::default() })
+});
+
+fn truncate(s: &str, n: usize) -> String {
+    if s.len() <= n { return s.to_string(); }
+    let mut end = n;
+    while !s.is_char_boundary(end) { end -= 1; }
+    format!("{}…", &s[..end])
+}
+
+// 16 ký tự hex ngẫu nhiên
+pub fn new_correlation_id() -> String {
+    let mut buf = [0u8; 8];
+    OsRng.fill_bytes(&mut buf);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Số sự kiện tối đa được giữ (feature_extras.waf.event_buffer_size)
+pub fn set_capacity(n: usize) {
+    let n = n.clamp(1, MAX_CAPACITY);
+    let mut log = LOG.lock();
+    log.capacity = n;
+    while log.events.len() > n { log.events.pop_front(); }
+}
+
+pub fn capacity_from_extras(extras: Option<&serde_json::Value>) -> usize {
+    extras
+        .and_then(|o| o.get("event_buffer_size"))
+        .and_then(|v| v.as_u64())
+        .filter(|n| *n > 0)
+        .map(|n| n as usize)
+        .unwrap_or(DEFAULT_CAPACITY)
+}
+
+// Ghi sự kiện cho verdict có rule khớp; trả về sự kiện đã ghi (None nếu không có gì khớp)
+pub fn record(req: &RequestSummary, verdict: &Verdict, correlation_id: String) -> Option<WafEvent> {
+    if verdict.matches.is_empty() { return None; }
+    let event = WafEvent {
+        correlation_id,
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        action: if verdict.blocked { Action::Blocked } else { Action::Detected },
+        method: req.method.clone(),
+        path: truncate(&req.path, FIELD_LEN),
+        query: req.query.as_deref().map(|q| truncate(q, FIELD_LEN)),
+        client_ip: req.client_ip.clone(),
+        user_agent: req.user_agent.as_deref().map(|u| truncate(u, FIELD_LEN)),
+        request_id: req.request_id.as_deref().map(|r| truncate(r, 64)),
+        score: verdict.score,
+        total_score: verdict.total_score,
+        threshold: verdict.threshold,
+        would_block: verdict.would_block,
+        rule_ids: verdict.rule_ids(),
+        matches: verdict.matches.clone(),
+    };
+    let mut log = LOG.lock();
+    match event.action {
+        Action::Blocked => log.stats.blocked += 1,
+        Action::Detected => log.stats.detected += 1,
+    }
+    for id in &event.rule_ids {
+        *log.stats.by_rule.entry(*id).or_default() += 1;
+    }
+    if log.events.len() >= log.capacity { log.events.pop_front(); }
+    log.events.push_back(event.clone());
+    Some(event)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EventQuery {
+    pub action: Option<Action>,
+    pub rule: Option<u32>,
+    pub path: Option<String>,
+    pub client_ip: Option<String>,
+    // RFC3339; so sánh chuỗi vì mọi timestamp đều ghi theo UTC cùng định dạng
+    pub since: Option<String>,
+    pub limit: Option<usize>,
+}
+
+// Sự kiện theo bộ lọc, mới nhất trước
+pub fn query(q: &EventQuery) -> Vec<WafEvent> {
+    let limit = q.limit.unwrap_or(100).min(MAX_CAPACITY);
+    LOG.lock()
+        .events
+        .iter()
+        .rev()
+        .filter(|e| q.action.is_none_or(|a| e.action == a))
+        .filter(|e| q.rule.is_none_or(|r| e.rule_ids.contains(&r)))
+        .filter(|e| q.path.as_ref().is_none_or(|p| e.path.contains(p.as_str())))
+        .filter(|e| q.client_ip.as_ref().is_none_or(|ip| e.client_ip.as_ref() == Some(ip)))
+        .filter(|e| q.since.as_ref().is_none_or(|s| e.timestamp.as_str() >= s.as_str()))
+        .take(limit)
+        .cloned()
+        .collect()
+}
+
+pub fn find(correlation_id: &str) -> Option<WafEvent> {
+    LOG.lock().events.iter().rev().find(|e| e.correlation_id == correlation_id).cloned()
+}
+
+pub fn stats() -> EventStats {
+    let log = LOG.lock();
+    EventStats { capacity: log.capacity, buffered: log.events.len(), ..log.stats.clone() }
+}
+
+pub fn clear() {
+    let mut log = LOG.lock();
+    log.events.clear();
+    log.stats = EventStats::default();
+}
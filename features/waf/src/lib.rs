@@ -2,6 +2,13 @@
 #[cfg(feature = "plugin")] use libc::c_char;
 #[cfg(feature = "plugin")] use std::ffi::CString;
 
+pub mod engine;
+pub mod events;
+pub mod limits;
+pub use engine::{engine_for, Engine, RequestParts, RuleMatch, RuleSpec, Severity, Verdict};
+pub use events::{RequestSummary, WafEvent};
+pub use limits::{BodyKind, Limits, Violation};
+
 // C-ABI symbol used by the dynamic loader to identify the feature
 #[cfg(feature = "plugin")]
 #[no_mangle]
@@ -11,16 +18,21 @@ pub extern "C" fn feature_name_waf() -> *mut c_char {
 
 // ---- Pure Rust logic below (used by the main app via rlib) ----
 
+// Giữ API cũ: kiểm tra uri + User-Agent bằng ruleset mặc định
 pub fn is_malicious(uri: &str, user_agent: Option<&str>) -> bool {
-    let uri_l = uri.to_lowercase();
-    let suspicious = [
-        "<script", "%3cscript", "javascript:", "onerror=", "onload=",
-        "<img", "<svg", "../", "union select", "select%20", "or 1=1",
-        "drop table", "insert%20", "update%20", "delete%20",
-    ];
-    if suspicious.iter().any(|p| uri_l.contains(p)) { return true; }
-    if let Some(ua) = user_agent { if ua.len() > 1024 { return true; } }
-    false
+    let (path, query) = match uri.split_once('?') {
+        Some((p, q)) => (p, Some(q.to_string())),
+        None => (uri, None),
+    };
+    let req = RequestParts {
+        method: "GET".to_string(),
+        path: path.to_string(),
+        query,
+        headers: user_agent.map(|ua| vec![("user-agent".to_string(), ua.to_string())]).unwrap_or_default(),
+        body: None,
+        body_args: Vec::new(),
+    };
+    engine_for(None).inspect(&req).blocked
 }
 
 // Manifest để UI Admin tự động sinh theo code của feature
@@ -29,8 +41,22 @@ pub fn is_malicious(uri: &str, user_agent: Option<&str>) -> bool {
 pub extern "C" fn feature_manifest_waf() -> *mut c_char {
     let json = r#"{
         "name": "waf",
+        "description": "Rule engine: regex/literal theo path, query args, header, cookie, body (JSON/form/text); giới hạn kích thước body/header, độ sâu JSON; chấm điểm anomaly theo severity",
         "settings": [
-          {"key": "patterns", "type": "string_list", "label": "WAF Patterns", "default": []}
+          {"key": "mode", "type": "string", "label": "Mode (block, detect_only = log matches without blocking)", "default": "block"},
+          {"key": "anomaly_threshold", "type": "number", "label": "Anomaly Score Threshold (critical = 5, error = 4, warning = 3, notice = 2)", "default": 5},
+          {"key": "default_rules", "type": "boolean", "label": "Use Built-in Ruleset", "default": true},
+          {"key": "disabled_rules", "type": "json", "label": "Disabled Rule IDs", "default": [], "example": [941130, 942140]},
+          {"key": "detect_only_rules", "type": "json", "label": "Detect-only Rule IDs (log, never block)", "default": [], "example": [941130]},
+          {"key": "event_buffer_size", "type": "number", "label": "Event Log Size (recent hits/blocks kept in memory)", "default": 500},
+          {"key": "rules", "type": "json", "label": "Custom Rules", "default": [], "example": [{"id": 1001, "name": "Block admin probes", "severity": "critical", "targets": ["path"], "transforms": ["url_decode", "lowercase"], "regex": "^/(?:wp-admin|phpmyadmin)", "detect_only": false}]},
+          {"key": "inspect_body", "type": "boolean", "label": "Inspect Request Bodies (max_body_bytes always applies)", "default": true},
+          {"key": "max_body_bytes", "type": "number", "label": "Max Body Size (bytes, 413 above)", "default": 1048576},
+          {"key": "max_json_depth", "type": "number", "label": "Max JSON Nesting Depth", "default": 32},
+          {"key": "max_json_keys", "type": "number", "label": "Max JSON Object Keys (total)", "default": 1000},
+          {"key": "max_headers", "type": "number", "label": "Max Header Count (431 above)", "default": 100},
+          {"key": "max_header_size", "type": "number", "label": "Max Header Size (name + value, bytes)", "default": 8192},
+          {"key": "patterns", "type": "string_list", "label": "WAF Patterns (literal, uri + user-agent)", "default": []}
         ]
     }"#;
     CString::new(json).unwrap().into_raw()
@@ -0,0 +1,236 @@
+// Giới hạn kích thước request và phân tích body cho WAF:
+// - số lượng / kích thước header, kích thước body, độ sâu và số key của JSON
+// - body JSON / form / text được tách thành các cặp (nhãn, giá trị) cho target body_args;
+//   content type khác (octet-stream, multipart...) được dò JSON, không phải JSON thì kiểm tra như text
+// Vi phạm giới hạn luôn bị chặn (kể cả ở detect only) vì đây là bảo vệ tài nguyên, không phải heuristic
+use crate::engine::{url_decode, RuleMatch, Severity, Verdict};
+use serde_json::Value;
+
+pub const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+pub const DEFAULT_MAX_JSON_DEPTH: usize = 32;
+pub const DEFAULT_MAX_JSON_KEYS: usize = 1000;
+pub const DEFAULT_MAX_HEADERS: usize = 100;
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 8 * 1024;
+
+// Id rule (nhóm protocol) cho từng loại vi phạm, để hiển thị chung với rule khác trong nhật ký
+pub const RULE_BODY_TOO_LARGE: u32 = 920300;
+pub const RULE_INVALID_BODY: u32 = 920310;
+pub const RULE_JSON_TOO_DEEP: u32 = 920320;
+pub const RULE_JSON_TOO_MANY_KEYS: u32 = 920330;
+pub const RULE_TOO_MANY_HEADERS: u32 = 920340;
+pub const RULE_HEADER_TOO_LARGE: u32 = 920350;
+
+#[derive(Debug, Clone)]
+pub struct Limits {
+    // Đọc và kiểm tra body (JSON, form, text)
+    pub inspect_body: bool,
+    pub max_body_bytes: usize,
+    pub max_json_depth: usize,
+    pub max_json_keys: usize,
+    pub max_headers: usize,
+    // Kích thước tối đa của một header (tên + giá trị)
+    pub max_header_size: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            inspect_body: true,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_json_depth: DEFAULT_MAX_JSON_DEPTH,
+            max_json_keys: DEFAULT_MAX_JSON_KEYS,
+            max_headers: DEFAULT_MAX_HEADERS,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+        }
+    }
+}
+
+impl Limits {
+    // Đọc từ feature_extras.waf; 0 hoặc thiếu = giá trị mặc định
+    pub fn from_extras(extras: Option<&Value>) -> Self {
+        let get = |k: &str| extras.and_then(|o| o.get(k));
+        let num = |k: &str, d: usize| get(k).and_then(|v| v.as_u64()).filter(|n| *n > 0).map(|n| n as usize).unwrap_or(d);
+        Self {
+            inspect_body: get("inspect_body").and_then(|v| v.as_bool()).unwrap_or(true),
+            max_body_bytes: num("max_body_bytes", DEFAULT_MAX_BODY_BYTES),
+            max_json_depth: num("max_json_depth", DEFAULT_MAX_JSON_DEPTH),
+            max_json_keys: num("max_json_keys", DEFAULT_MAX_JSON_KEYS),
+            max_headers: num("max_headers", DEFAULT_MAX_HEADERS),
+            max_header_size: num("max_header_size", DEFAULT_MAX_HEADER_SIZE),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub rule_id: u32,
+    // HTTP status trả về: 413 body quá lớn, 431 header, 400 body sai định dạng / vượt giới hạn JSON
+    pub status: u16,
+    pub title: &'static str,
+    pub detail: String,
+    // Phần request vi phạm, vd "body", "header:cookie"
+    pub target: String,
+}
+
+impl Violation {
+    fn new(rule_id: u32, status: u16, title: &'static str, target: impl Into<String>, detail: String) -> Self {
+        Self { rule_id, status, title, detail, target: target.into() }
+    }
+
+    pub fn body_too_large(limit: usize) -> Self {
+        Self::new(RULE_BODY_TOO_LARGE, 413, "Payload Too Large", "body", format!("request body exceeds {} bytes", limit))
+    }
+
+    // Verdict tương ứng để ghi vào nhật ký WAF
+    pub fn verdict(&self, threshold: u32) -> Verdict {
+        let m = RuleMatch {
+            id: self.rule_id,
+            name: self.title.to_string(),
+            severity: Severity::Critical,
+            score: Severity::Critical.score(),
+            target: self.target.clone(),
+            matched: self.detail.clone(),
+            detect_only: false,
+        };
+        Verdict {
+            score: m.score,
+            total_score: m.score,
+            threshold,
+            blocked: true,
+            would_block: true,
+            detect_only: false,
+            matches: vec![m],
+        }
+    }
+}
+
+pub fn check_headers(limits: &Limits, headers: &[(String, String)]) -> Result<(), Violation> {
+    if headers.len() > limits.max_headers {
+        return Err(Violation::new(
+            RULE_TOO_MANY_HEADERS, 431, "Request Header Fields Too Large", "headers",
+            format!("{} headers exceed the limit of {}", headers.len(), limits.max_headers),
+        ));
+    }
+    if let Some((name, value)) = headers.iter().find(|(n, v)| n.len() + v.len() > limits.max_header_size) {
+        return Err(Violation::new(
+            RULE_HEADER_TOO_LARGE, 431, "Request Header Fields Too Large", format!("header:{}", name),
+            format!("header {} is {} bytes, limit {}", name, name.len() + value.len(), limits.max_header_size),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyKind {
+    Json,
+    Form,
+    Text,
+    // multipart, nhị phân...: dò JSON, còn lại kiểm tra như text (client không né được WAF bằng Content-Type)
+    Other,
+}
+
+pub fn body_kind(content_type: Option<&str>) -> BodyKind {
+    let Some(ct) = content_type else { return BodyKind::Text };
+    let mime = ct.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    if mime == "application/json" || mime.ends_with("+json") {
+        BodyKind::Json
+    } else if mime == "application/x-www-form-urlencoded" {
+        BodyKind::Form
+    } else if mime.starts_with("text/") || mime.is_empty() || mime == "application/xml" || mime.ends_with("+xml") || mime == "application/graphql" {
+        BodyKind::Text
+    } else {
+        BodyKind::Other
+    }
+}
+
+// Duyệt JSON (không đệ quy), kiểm tra độ sâu / số key và gom giá trị lá theo đường dẫn "$.a[0].b"
+fn json_args(limits: &Limits, root: &Value) -> Result<Vec<(String, String)>, Violation> {
+    let mut out = Vec::new();
+    let mut keys = 0usize;
+    let mut stack: Vec<(String, &Value, usize)> = vec![("$".to_string(), root, 0)];
+    while let Some((path, v, depth)) = stack.pop() {
+        if depth > limits.max_json_depth {
+            return Err(Violation::new(
+                RULE_JSON_TOO_DEEP, 400, "JSON Body Too Deep", "body",
+                format!("JSON nesting exceeds depth {}", limits.max_json_depth),
+            ));
+        }
+        match v {
+            Value::Object(map) => {
+                keys += map.len();
+                if keys > limits.max_json_keys {
+                    return Err(Violation::new(
+                        RULE_JSON_TOO_MANY_KEYS, 400, "JSON Body Too Many Keys", "body",
+                        format!("JSON body has more than {} keys", limits.max_json_keys),
+                    ));
+                }
+                for (k, child) in map {
+                    // Tên key cũng là dữ liệu do client kiểm soát
+                    out.push((format!("body_names:{}", path), k.clone()));
+                    stack.push((format!("{}.{}", path, k), child, depth + 1));
+                }
+            }
+            Value::Array(items) => {
+                for (i, child) in items.iter().enumerate() {
+                    stack.push((format!("{}[{}]", path, i), child, depth + 1));
+                }
+            }
+            Value::String(s) => out.push((format!("body:{}", path), s.clone())),
+            Value::Number(n) => out.push((format!("body:{}", path), n.to_string())),
+            Value::Bool(_) | Value::Null => {}
+        }
+    }
+    Ok(out)
+}
+
+// Tách body thành (nhãn, giá trị) cho target body_args / body_arg_names.
+// Nhãn "body_names:..." là tên key/tham số, còn lại là giá trị
+pub fn parse_body(limits: &Limits, kind: BodyKind, bytes: &[u8]) -> Result<Vec<(String, String)>, Violation> {
+    if bytes.is_empty() { return Ok(Vec::new()); }
+    match kind {
+        BodyKind::Json => {
+            let v: Value = serde_json::from_slice(bytes).map_err(|e| {
+                Violation::new(RULE_INVALID_BODY, 400, "Invalid Request Body", "body", format!("invalid JSON body: {}", e))
+            })?;
+            json_args(limits, &v)
+        }
+        BodyKind::Form => {
+            let text = String::from_utf8_lossy(bytes);
+            let mut out = Vec::new();
+            for kv in text.split('&').filter(|kv| !kv.is_empty()) {
+                let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+                let k = url_decode(k);
+                out.push(("body_names:form".to_string(), k.clone()));
+                out.push((format!("body:{}", k), url_decode(v)));
+            }
+            Ok(out)
+        }
+        BodyKind::Text => Ok(vec![("body".to_string(), String::from_utf8_lossy(bytes).into_owned())]),
+        BodyKind::Other => {
+            let looks_json = bytes.iter().find(|b| !b.is_ascii_whitespace()).is_some_and(|b| *b == b'{' || *b == b'[');
+            match serde_json::from_slice::<Value>(bytes) {
+                Ok(v) if looks_json => json_args(limits, &v),
+                _ => Ok(vec![("body".to_string(), String::from_utf8_lossy(bytes).into_owned())]),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_content_types_are_still_inspected() {
+        let limits = Limits::default();
+        assert_eq!(body_kind(Some("application/octet-stream")), BodyKind::Other);
+        let json = parse_body(&limits, BodyKind::Other, br#" {"q": "1 union select"}"#).unwrap();
+        assert!(json.contains(&("body:$.q".to_string(), "1 union select".to_string())));
+        let multipart = b"--x\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n<script>\r\n--x--";
+        let text = parse_body(&limits, BodyKind::Other, multipart).unwrap();
+        assert!(text.len() == 1 && text[0].0 == "body" && text[0].1.contains("<script>"));
+        // JSON vượt giới hạn vẫn bị chặn dù Content-Type không phải JSON
+        let deep = format!("{}{}", "[".repeat(40), "]".repeat(40));
+        assert_eq!(parse_body(&limits, BodyKind::Other, deep.as_bytes()).unwrap_err().rule_id, RULE_JSON_TOO_DEEP);
+    }
+}
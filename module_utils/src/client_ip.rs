@@ -0,0 +1,148 @@
+// IP client được giải quyết một lần cho mỗi request (app gắn vào request extension),
+// mọi feature và plugin dùng chung kết quả này thay vì tự đọc header / ConnectInfo
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientIpSource {
+    // IP của kết nối TCP (không qua proxy tin cậy)
+    Connection,
+    // Header Forwarded (RFC 7239)
+    Forwarded,
+    XForwardedFor,
+    XRealIp,
+}
+
+impl ClientIpSource {
+    // Tên header trong cấu hình client_ip_header: "x-forwarded-for" | "forwarded" | "x-real-ip"
+    pub fn from_header_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "forwarded" => Some(Self::Forwarded),
+            "x-forwarded-for" => Some(Self::XForwardedFor),
+            "x-real-ip" => Some(Self::XRealIp),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientIp {
+    pub ip: IpAddr,
+    // IP kết nối thực tế (proxy gần nhất nếu có)
+    pub peer: IpAddr,
+    pub source: ClientIpSource,
+}
+
+// Danh sách IP/CIDR proxy tin cậy; giá trị sai bị bỏ qua
+pub fn parse_trusted<S: AsRef<str>>(list: &[S]) -> Vec<IpNet> {
+    list.iter()
+        .filter_map(|s| {
+            let s = s.as_ref().trim();
+            s.parse::<IpNet>().ok().or_else(|| s.parse::<IpAddr>().ok().map(IpNet::from))
+        })
+        .collect()
+}
+
+fn is_trusted(ip: &IpAddr, trusted: &[IpNet]) -> bool {
+    trusted.iter().any(|n| n.contains(ip))
+}
+
+// Giá trị for= của Forwarded: 192.0.2.1, "192.0.2.1:8080", "[2001:db8::1]:443"; unknown / _obfuscated -> None
+fn forwarded_node(v: &str) -> Option<IpAddr> {
+    let v = v.trim().trim_matches('"');
+    if let Some(rest) = v.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    v.parse().ok().or_else(|| v.rsplit_once(':').and_then(|(host, _)| host.parse::<std::net::Ipv4Addr>().ok()).map(IpAddr::V4))
+}
+
+fn forwarded_chain(v: &str) -> Vec<Option<IpAddr>> {
+    v.split(',')
+        .filter_map(|elem| {
+            elem.split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(k, _)| k.trim().eq_ignore_ascii_case("for"))
+                .map(|(_, v)| forwarded_node(v))
+        })
+        .collect()
+}
+
+// Duyệt chuỗi hop từ phải sang trái, bỏ qua proxy tin cậy; hop đầu tiên không tin cậy là client.
+// Gặp giá trị không phải IP (client tự chèn) thì dừng ở hop tin cậy gần nhất
+fn walk(peer: IpAddr, chain: &[Option<IpAddr>], trusted: &[IpNet]) -> IpAddr {
+    let mut last = peer;
+    for hop in chain.iter().rev() {
+        let Some(ip) = hop else { return last };
+        if !is_trusted(ip, trusted) { return *ip; }
+        last = *ip;
+    }
+    last
+}
+
+// Header chỉ được tin khi kết nối đến từ proxy tin cậy, và chỉ đọc đúng header mà proxy đặt (`source`);
+// các header proxy khác do client tự gửi bị bỏ qua. Connection = không đọc header nào.
+// `header` trả về mọi giá trị của header đã nối bằng ", "
+pub fn resolve(peer: IpAddr, header: impl Fn(&str) -> Option<String>, trusted: &[IpNet], source: ClientIpSource) -> ClientIp {
+    let direct = ClientIp { ip: peer, peer, source: ClientIpSource::Connection };
+    if !is_trusted(&peer, trusted) { return direct; }
+    match source {
+        ClientIpSource::Connection => direct,
+        ClientIpSource::Forwarded => {
+            let chain = header("forwarded").map(|v| forwarded_chain(&v)).unwrap_or_default();
+            if chain.is_empty() { return direct; }
+            ClientIp { ip: walk(peer, &chain, trusted), peer, source }
+        }
+        ClientIpSource::XForwardedFor => match header("x-forwarded-for") {
+            Some(v) => {
+                let chain: Vec<Option<IpAddr>> = v.split(',').map(|h| h.trim().parse().ok()).collect();
+                ClientIp { ip: walk(peer, &chain, trusted), peer, source }
+            }
+            None => direct,
+        },
+        ClientIpSource::XRealIp => match header("x-real-ip").and_then(|v| v.trim().parse().ok()) {
+            Some(ip) => ClientIp { ip, peer, source },
+            None => direct,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(name: &str) -> Option<String> {
+        match name {
+            "forwarded" => Some("for=198.51.100.1".to_string()),
+            "x-forwarded-for" => Some("203.0.113.9, 10.0.0.2".to_string()),
+            "x-real-ip" => Some("192.0.2.77".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn only_the_configured_header_is_read() {
+        let trusted = parse_trusted(&["10.0.0.0/8"]);
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+        let xff = resolve(peer, headers, &trusted, ClientIpSource::XForwardedFor);
+        assert_eq!((xff.ip.to_string(), xff.source), ("203.0.113.9".to_string(), ClientIpSource::XForwardedFor));
+        assert_eq!(resolve(peer, headers, &trusted, ClientIpSource::Forwarded).ip.to_string(), "198.51.100.1");
+        assert_eq!(resolve(peer, headers, &trusted, ClientIpSource::XRealIp).ip.to_string(), "192.0.2.77");
+        assert_eq!(resolve(peer, headers, &trusted, ClientIpSource::Connection).ip, peer);
+        // Header đã cấu hình không có: dùng IP kết nối, không thử header khác
+        let only_forwarded = |n: &str| (n == "forwarded").then(|| "for=198.51.100.1".to_string());
+        assert_eq!(resolve(peer, only_forwarded, &trusted, ClientIpSource::XForwardedFor).source, ClientIpSource::Connection);
+        // Kết nối không từ proxy tin cậy: bỏ qua header
+        let direct: IpAddr = "192.0.2.1".parse().unwrap();
+        assert_eq!(resolve(direct, headers, &trusted, ClientIpSource::XForwardedFor).ip, direct);
+    }
+
+    #[test]
+    fn header_names_from_config() {
+        assert_eq!(ClientIpSource::from_header_name("X-Forwarded-For"), Some(ClientIpSource::XForwardedFor));
+        assert_eq!(ClientIpSource::from_header_name("forwarded"), Some(ClientIpSource::Forwarded));
+        assert_eq!(ClientIpSource::from_header_name("x-real-ip"), Some(ClientIpSource::XRealIp));
+        assert_eq!(ClientIpSource::from_header_name("true-client-ip"), None);
+    }
+}
@@ -0,0 +1,65 @@
+// Ngữ cảnh request được app truyền sang plugin qua ABI ctx (JSON)
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+// Danh tính đã được xác thực bởi các feature (oauth2, api key...)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Principal {
+    pub subject: Option<String>,
+    pub scopes: Vec<String>,
+    // Chỉ các claim được cấu hình để chuyển tiếp (oauth2.forward_claims)
+    pub claims: Map<String, Value>,
+    pub api_key_id: Option<String>,
+    // "jwt" | "api_key" | ...
+    pub auth_method: Option<String>,
+}
+
+impl Principal {
+    pub fn is_authenticated(&self) -> bool {
+        self.subject.is_some() || self.api_key_id.is_some()
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+
+    pub fn has_all_scopes(&self, scopes: &[&str]) -> bool {
+        scopes.iter().all(|s| self.has_scope(s))
+    }
+
+    pub fn claim(&self, key: &str) -> Option<&Value> {
+        self.claims.get(key)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestContext {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    // Header đã bỏ các giá trị nhạy cảm (authorization, cookie, x-api-key)
+    pub headers: BTreeMap<String, String>,
+    pub principal: Option<Principal>,
+    // IP client đã giải quyết qua proxy tin cậy (ClientIp)
+    pub client_ip: Option<String>,
+    // Nonce CSP của request (security_headers): dùng cho <script nonce="..."> / <style nonce="...">
+    pub csp_nonce: Option<String>,
+}
+
+// Extension request do security_headers gắn khi CSP có "{nonce}"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CspNonce(pub String);
+
+impl RequestContext {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_ascii_lowercase()).map(|s| s.as_str())
+    }
+
+    // Thuộc tính nonce="..." để chèn vào thẻ <script>/<style>; rỗng khi không có CSP nonce
+    pub fn nonce_attr(&self) -> String {
+        self.csp_nonce.as_deref().map(|n| format!(" nonce=\"{}\"", n)).unwrap_or_default()
+    }
+}
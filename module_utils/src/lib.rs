@@ -1,3 +1,12 @@
+mod client_ip;
+mod context;
+mod route;
+mod secret;
+pub use client_ip::{parse_trusted, resolve as resolve_client_ip, ClientIp, ClientIpSource};
+pub use context::{CspNonce, Principal, RequestContext};
+pub use route::{best_route_match, normalize_route, route_matches, route_specificity, ConfigCache};
+pub use secret::{constant_time_eq, is_env_ref, resolve_secret, SECRET_ENV_PREFIX};
+
 // Exported macro: read_asset!
 #[macro_export]
 macro_rules! read_asset {
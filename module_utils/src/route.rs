@@ -0,0 +1,143 @@
+// Khớp route dùng chung cho các feature (cors, security_headers, request_signature, quota, ip_filter,
+// oauth2, api_key, rate_limit) và cache cấu hình đã biên dịch theo feature_extras.
+//
+// Cú pháp pattern:
+// - "/greet/hi"  khớp chính xác (bỏ qua "/" cuối)
+// - "/greet/*"   khớp "/greet" và mọi path con, không khớp "/greeting"
+// - "*"          khớp mọi path
+// - "*" ở vị trí khác là glob khớp mọi ký tự (kể cả "/"), vd "/api/*/admin", "/v*/users"
+use serde_json::Value;
+use std::sync::{Arc, RwLock};
+
+pub fn normalize_route(p: &str) -> String {
+    let p = p.trim();
+    if p == "/" { return "/".to_string(); }
+    p.trim_end_matches('/').to_string()
+}
+
+pub fn route_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim();
+    let path = normalize_route(path);
+    if pattern == "*" { return true; }
+    if let Some(prefix) = pattern.strip_suffix("/*") {
+        if !prefix.contains('*') {
+            let prefix = normalize_route(prefix);
+            return path == prefix || path.starts_with(&format!("{}/", prefix.trim_end_matches('/')));
+        }
+        // Glob kết thúc bằng "/*" cũng khớp chính phần trước nó
+        if glob(prefix, &path) { return true; }
+    }
+    if pattern.contains('*') { return glob(pattern, &path); }
+    normalize_route(pattern) == path
+}
+
+// Glob "*" = chuỗi ký tự bất kỳ (thuật toán backtracking một sao, O(n*m) trường hợp xấu nhất)
+fn glob(pattern: &str, text: &str) -> bool {
+    let (p, s) = (pattern.as_bytes(), text.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while j < s.len() {
+        if i < p.len() && p[i] == b'*' {
+            star = Some((i, j));
+            i += 1;
+        } else if i < p.len() && p[i] == s[j] {
+            i += 1;
+            j += 1;
+        } else if let Some((si, sj)) = star {
+            i = si + 1;
+            j = sj + 1;
+            star = Some((si, sj + 1));
+        } else {
+            return false;
+        }
+    }
+    p[i..].iter().all(|c| *c == b'*')
+}
+
+// Độ cụ thể để chọn pattern thắng khi nhiều pattern cùng khớp: chính xác > pattern có "*" dài hơn > ngắn hơn
+pub fn route_specificity(pattern: &str) -> (bool, usize) {
+    let pattern = pattern.trim();
+    (!pattern.contains('*'), pattern.len())
+}
+
+// Pattern cụ thể nhất trong số các pattern khớp path
+pub fn best_route_match<'a, T>(items: impl IntoIterator<Item = (&'a str, T)>, path: &str) -> Option<T> {
+    items
+        .into_iter()
+        .filter(|(p, _)| route_matches(p, path))
+        .max_by_key(|(p, _)| route_specificity(p))
+        .map(|(_, v)| v)
+}
+
+// Cấu hình đã biên dịch (regex, CIDR...) được giữ lại tới khi feature_extras của feature đổi.
+// So sánh trực tiếp giá trị JSON (không serialize) dưới khóa đọc, nên đường đi thường không phải chờ nhau.
+pub struct ConfigCache<T> {
+    slot: RwLock<Option<(Value, Arc<T>)>>,
+}
+
+impl<T> ConfigCache<T> {
+    pub const fn new() -> Self {
+        Self { slot: RwLock::new(None) }
+    }
+
+    pub fn get(&self, extras: Option<&Value>, build: impl FnOnce(Option<&Value>) -> T) -> Arc<T> {
+        let key = extras.unwrap_or(&Value::Null);
+        {
+            let slot = self.slot.read().unwrap_or_else(|e| e.into_inner());
+            if let Some((k, v)) = slot.as_ref() {
+                if k == key { return v.clone(); }
+            }
+        }
+        let built = Arc::new(build(extras));
+        *self.slot.write().unwrap_or_else(|e| e.into_inner()) = Some((key.clone(), built.clone()));
+        built
+    }
+}
+
+impl<T> Default for ConfigCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn matches_exact_prefix_and_glob() {
+        assert!(route_matches("/greet/hi", "/greet/hi/"));
+        assert!(!route_matches("/greet/hi", "/greet/hi/x"));
+        assert!(route_matches("/greet/*", "/greet"));
+        assert!(route_matches("/greet/*", "/greet/a/b"));
+        assert!(!route_matches("/greet/*", "/greeting"));
+        assert!(route_matches("/*", "/"));
+        assert!(route_matches("*", "/anything"));
+        assert!(route_matches("/api/*/admin", "/api/v1/admin"));
+        assert!(!route_matches("/api/*/admin", "/api/v1/users"));
+        assert!(route_matches("/v*/users", "/v2/users"));
+        assert!(route_matches("/api/*/items/*", "/api/v1/items"));
+    }
+
+    #[test]
+    fn most_specific_pattern_wins() {
+        let items = [("/greet/*", 1), ("/greet/hi", 2), ("*", 3), ("/greet/hi/*", 4)];
+        assert_eq!(best_route_match(items.iter().map(|(p, v)| (*p, *v)), "/greet/hi"), Some(2));
+        assert_eq!(best_route_match(items.iter().map(|(p, v)| (*p, *v)), "/greet/hi/x"), Some(4));
+        assert_eq!(best_route_match(items.iter().map(|(p, v)| (*p, *v)), "/greet/x"), Some(1));
+        assert_eq!(best_route_match(items.iter().map(|(p, v)| (*p, *v)), "/other"), Some(3));
+    }
+
+    #[test]
+    fn cache_rebuilds_only_when_extras_change() {
+        static CACHE: ConfigCache<usize> = ConfigCache::new();
+        let builds = std::cell::Cell::new(0);
+        let build = |_: Option<&Value>| { builds.set(builds.get() + 1); builds.get() };
+        let a = json!({"x": 1});
+        assert_eq!(*CACHE.get(Some(&a), build), 1);
+        assert_eq!(*CACHE.get(Some(&json!({"x": 1})), build), 1);
+        assert_eq!(*CACHE.get(Some(&json!({"x": 2})), build), 2);
+        assert_eq!(*CACHE.get(None, build), 3);
+    }
+}
@@ -0,0 +1,24 @@
+// Giá trị bí mật trong cấu hình: chuỗi trực tiếp hoặc tham chiếu "env:NAME" (đọc từ biến môi trường,
+// để secret không phải nằm trong features.json / lịch sử cấu hình)
+pub const SECRET_ENV_PREFIX: &str = "env:";
+
+pub fn is_env_ref(raw: &str) -> bool {
+    raw.trim().starts_with(SECRET_ENV_PREFIX)
+}
+
+// Giá trị thực của secret; Err khi biến môi trường được tham chiếu không có hoặc rỗng
+pub fn resolve_secret(raw: &str) -> Result<String, String> {
+    let raw = raw.trim();
+    match raw.strip_prefix(SECRET_ENV_PREFIX) {
+        Some(name) => match std::env::var(name.trim()) {
+            Ok(v) if !v.is_empty() => Ok(v),
+            _ => Err(format!("environment variable {} is not set", name.trim())),
+        },
+        None => Ok(raw.to_string()),
+    }
+}
+
+// So sánh secret/hash/token không rẽ nhánh theo vị trí byte khác nhau (chống timing attack)
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
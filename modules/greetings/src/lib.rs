@@ -1,4 +1,5 @@
 use plugin_macro::{def_get, def_post, def_put, def_delete, declare_routes};
+use module_utils::{Principal, RequestContext};
 use serde_json::json;
 
 // GET routes (no body)
@@ -12,14 +13,15 @@ pub fn greet_bye() -> String {
     "Goodbye! 👋".to_string()
 }
 
+// Nhận RequestContext để gắn nonce CSP (security_headers) cho thẻ <style> inline
 #[def_get("/greet/html")]
-pub fn greet_html() -> String {
+pub fn greet_html(ctx: &RequestContext) -> String {
     r#"
     <!DOCTYPE html>
     <html>
     <head>
         <title>Greetings API</title>
-        <style>
+        <style{nonce}>
             body { font-family: Arial, sans-serif; margin: 40px; }
             .greeting { color: #2563eb; font-size: 24px; }
             .method { color: #059669; font-weight: bold; }
@@ -35,6 +37,7 @@ pub fn greet_html() -> String {
             <li><span class="method">GET</span> <code class="endpoint">/greet/hi</code> - Text greeting</li>
             <li><span class="method">GET</span> <code class="endpoint">/greet/bye</code> - Goodbye text</li>
             <li><span class="method">GET</span> <code class="endpoint">/greet/info</code> - JSON info</li>
+            <li><span class="method">GET</span> <code class="endpoint">/greet/whoami</code> - Authenticated principal</li>
         </ul>
         
         <h3>POST/PUT/DELETE Routes:</h3>
@@ -47,7 +50,7 @@ pub fn greet_html() -> String {
         <p><em>Try using curl or Postman to test the POST/PUT/DELETE endpoints with JSON body!</em></p>
     </body>
     </html>
-    "#.to_string()
+    "#.replace("{nonce}", &ctx.nonce_attr())
 }
 
 #[def_get("/greet/info")]
@@ -56,7 +59,7 @@ pub fn greet_info() -> serde_json::Value {
         "module": "greetings",
         "version": "2.0.0",
         "routes": {
-            "get": ["/greet/hi", "/greet/bye", "/greet/html", "/greet/info"],
+            "get": ["/greet/hi", "/greet/bye", "/greet/html", "/greet/info", "/greet/whoami"],
             "post": ["/greet/user"],
             "put": ["/greet/message"],
             "delete": ["/greet/reset"]
@@ -65,6 +68,22 @@ pub fn greet_info() -> serde_json::Value {
     })
 }
 
+// GET route nhận Principal: danh tính đã được oauth2 xác thực, module tự quyết định quyền
+#[def_get("/greet/whoami")]
+pub fn whoami(principal: &Principal) -> String {
+    if !principal.is_authenticated() {
+        return r#"error:401:{"error":"authentication required"}"#.to_string();
+    }
+    json!({
+        "subject": principal.subject,
+        "scopes": principal.scopes,
+        "claims": principal.claims,
+        "api_key_id": principal.api_key_id,
+        "auth_method": principal.auth_method,
+        "can_write": principal.has_scope("greet:write")
+    }).to_string()
+}
+
 // POST route (with body)
 #[def_post("/greet/user")]
 pub fn create_user(body: &str) -> serde_json::Value {
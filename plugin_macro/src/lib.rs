@@ -262,8 +262,35 @@ fn def_with_method(attr: TokenStream, item: TokenStream, method: &str) -> TokenS
     let args_str = attr.to_string();
     let path_str = if args_str.is_empty() { format!("/{}", fn_name) } else { args_str.trim_matches('"').to_string() };
     let register_fn_name = format_ident!("__register_{}", fn_name);
+    // Handler nhận Principal/RequestContext -> sinh wrapper theo ABI ctx
+    let ctx_args = ctx_call_args(inputs);
+    let abi_tokens = if ctx_args.is_some() { quote! { entry["abi"] = serde_json::json!("ctx"); } } else { quote! {} };
+    let wrapper_ident = match method {
+        "post" => &post_wrapper_name,
+        "put" => &put_wrapper_name,
+        "delete" => &delete_wrapper_name,
+        _ => &get_wrapper_name,
+    };
     // Generate method-specific wrapper according to provided method
-    let wrapper_tokens = match method {
+    let wrapper_tokens = if let Some(call_args) = ctx_args {
+        quote! {
+            #[no_mangle]
+            #[allow(clippy::not_unsafe_ptr_arg_deref)]
+            pub extern "C" fn #wrapper_ident(ctx_ptr: *const u8, ctx_len: usize, body_ptr: *const u8, body_len: usize) -> *mut std::os::raw::c_char {
+                use std::ffi::CString;
+                let out = std::panic::catch_unwind(|| {
+                    let ctx_slice = if ctx_ptr.is_null() { &[][..] } else { unsafe { std::slice::from_raw_parts(ctx_ptr, ctx_len) } };
+                    let __ctx: module_utils::RequestContext = serde_json::from_slice(ctx_slice).unwrap_or_default();
+                    let __principal: module_utils::Principal = __ctx.principal.clone().unwrap_or_default();
+                    let body_slice = if body_ptr.is_null() { &[][..] } else { unsafe { std::slice::from_raw_parts(body_ptr, body_len) } };
+                    let body_str = std::str::from_utf8(body_slice).unwrap_or("");
+                    let result = #fn_name(#(#call_args),*);
+                    result.to_string()
+                }).unwrap_or_else(|_| "error:500:panic".into());
+                CString::new(out).unwrap().into_raw()
+            }
+        }
+    } else { match method {
         "get" => quote! {
             #[no_mangle]
             pub extern "C" fn #get_wrapper_name() -> *mut std::os::raw::c_char {
@@ -277,6 +304,7 @@ fn def_with_method(attr: TokenStream, item: TokenStream, method: &str) -> TokenS
         },
         "post" => quote! {
             #[no_mangle]
+            #[allow(clippy::not_unsafe_ptr_arg_deref)]
             pub extern "C" fn #post_wrapper_name(body_ptr: *const u8, body_len: usize) -> *mut std::os::raw::c_char {
                 use std::ffi::CString;
                 let out = std::panic::catch_unwind(|| {
@@ -290,6 +318,7 @@ fn def_with_method(attr: TokenStream, item: TokenStream, method: &str) -> TokenS
         },
         "put" => quote! {
             #[no_mangle]
+            #[allow(clippy::not_unsafe_ptr_arg_deref)]
             pub extern "C" fn #put_wrapper_name(body_ptr: *const u8, body_len: usize) -> *mut std::os::raw::c_char {
                 use std::ffi::CString;
                 let out = std::panic::catch_unwind(|| {
@@ -303,6 +332,7 @@ fn def_with_method(attr: TokenStream, item: TokenStream, method: &str) -> TokenS
         },
         "delete" => quote! {
             #[no_mangle]
+            #[allow(clippy::not_unsafe_ptr_arg_deref)]
             pub extern "C" fn #delete_wrapper_name(body_ptr: *const u8, body_len: usize) -> *mut std::os::raw::c_char {
                 use std::ffi::CString;
                 let out = std::panic::catch_unwind(|| {
@@ -315,11 +345,13 @@ fn def_with_method(attr: TokenStream, item: TokenStream, method: &str) -> TokenS
             }
         },
         _ => quote! {}
-    };
+    } };
 
     let gen = quote! {
         #vis fn #fn_name(#inputs) #output #block
+        #[allow(non_snake_case)]
         pub fn #route_path_fn() -> &'static str { #path_str }
+        #[allow(non_snake_case)]
         pub fn #method_fn() -> &'static str { #method }
         #wrapper_tokens
 
@@ -338,6 +370,7 @@ fn def_with_method(attr: TokenStream, item: TokenStream, method: &str) -> TokenS
                 "delete" => { entry["delete"] = serde_json::json!(stringify!(#delete_wrapper_name)); },
                 _ => { entry["get"] = serde_json::json!(stringify!(#get_wrapper_name)); }
             }
+            #abi_tokens
             // Push into registry declared by declare_routes!()
             crate::__plugin_routes::__push_route(entry);
         }
@@ -345,6 +378,33 @@ fn def_with_method(attr: TokenStream, item: TokenStream, method: &str) -> TokenS
     gen.into()
 }
 
+// Nếu handler có tham số Principal/RequestContext (theo giá trị hoặc tham chiếu) thì trả về
+// danh sách đối số để gọi trong wrapper ctx; các tham số còn lại nhận body dạng &str.
+fn ctx_call_args(inputs: &syn::punctuated::Punctuated<syn::FnArg, syn::token::Comma>) -> Option<Vec<proc_macro2::TokenStream>> {
+    let mut uses_ctx = false;
+    let mut args = Vec::new();
+    for input in inputs {
+        let syn::FnArg::Typed(pt) = input else { continue };
+        let (by_ref, inner) = match &*pt.ty {
+            syn::Type::Reference(r) => (true, &*r.elem),
+            t => (false, t),
+        };
+        let last = match inner {
+            syn::Type::Path(tp) => tp.path.segments.last().map(|seg| seg.ident.to_string()),
+            _ => None,
+        };
+        let arg = match (last.as_deref(), by_ref) {
+            (Some("Principal"), true) => { uses_ctx = true; quote! { &__principal } }
+            (Some("Principal"), false) => { uses_ctx = true; quote! { __principal.clone() } }
+            (Some("RequestContext"), true) => { uses_ctx = true; quote! { &__ctx } }
+            (Some("RequestContext"), false) => { uses_ctx = true; quote! { __ctx.clone() } }
+            _ => quote! { body_str },
+        };
+        args.push(arg);
+    }
+    if uses_ctx { Some(args) } else { None }
+}
+
 // declare_routes! macro: define a registry and export routes_manifest automatically.
 #[proc_macro]
 pub fn declare_routes(_input: TokenStream) -> TokenStream {